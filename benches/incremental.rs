@@ -0,0 +1,34 @@
+//! Panning an interactive view: rebuilding a projector every frame against
+//! turning the previous one.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use e2p::{Deg, PerspectiveParams, Projector, rotation};
+
+const PANO_SIZE: (u32, u32) = (8192, 4096);
+
+fn params() -> PerspectiveParams {
+    PerspectiveParams::new().fov(Deg(90.0)).output_size(1920, 1080)
+}
+
+fn panning(c: &mut Criterion) {
+    let step = rotation::euler_to_matrix(0.5, 0.0, 0.0);
+    let mut group = c.benchmark_group("pan one step");
+
+    let mut yaw = 0.0;
+    group.bench_function("rebuild", |b| {
+        b.iter(|| {
+            yaw += 0.5;
+            Projector::new(&params().yaw(Deg(yaw)), PANO_SIZE).unwrap()
+        })
+    });
+
+    let mut projector = Projector::new(&params(), PANO_SIZE).unwrap();
+    group.bench_function("rotate", |b| b.iter(|| projector.rotate(&step).unwrap()));
+
+    let tilt = rotation::euler_to_matrix(0.5, 0.2, 0.0);
+    group.bench_function("rotate with tilt", |b| b.iter(|| projector.rotate(&tilt).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, panning);
+criterion_main!(benches);
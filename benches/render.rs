@@ -0,0 +1,93 @@
+//! Map construction, remapping and whole renders of a 1080p view across
+//! panorama resolutions and backends.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use e2p::{Backend, Deg, Equirectangular, PerspectiveParams, Precision, Projector};
+use opencv::core::{CV_8UC3, Scalar};
+use opencv::prelude;
+
+const PANO_WIDTHS: [u32; 3] = [4096, 8192, 16384];
+
+fn params() -> PerspectiveParams {
+    PerspectiveParams::new().fov(Deg(90.0)).yaw(Deg(30.0)).pitch(Deg(10.0)).output_size(1920, 1080)
+}
+
+fn panorama(width: u32) -> prelude::Mat {
+    prelude::Mat::new_rows_cols_with_default((width / 2) as i32, width as i32, CV_8UC3, Scalar::all(128.0)).unwrap()
+}
+
+fn backends() -> Vec<Backend> {
+    let mut backends = vec![Backend::Cpu, Backend::OpenCl];
+    #[cfg(feature = "cuda")]
+    backends.push(Backend::Cuda);
+    backends.into_iter().filter(|backend| backend.is_available()).collect()
+}
+
+fn map_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map build");
+    group.throughput(Throughput::Elements(1920 * 1080));
+    for precision in [Precision::F32, Precision::F64] {
+        for width in PANO_WIDTHS {
+            let params = params().precision(precision);
+            group.bench_with_input(BenchmarkId::new(format!("{precision:?}"), width), &width, |b, &width| {
+                b.iter(|| Projector::new(&params, (width, width / 2)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn remap(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remap");
+    group.throughput(Throughput::Elements(1920 * 1080));
+    for backend in backends() {
+        for width in PANO_WIDTHS {
+            let pano = panorama(width);
+            let projector = Projector::new(&params().backend(backend), (width, width / 2)).unwrap();
+            let mut dst = prelude::Mat::default();
+            group.bench_with_input(BenchmarkId::new(format!("{backend:?}"), width), &width, |b, _| {
+                b.iter(|| projector.apply_into(&pano, &mut dst).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn remap_bands(c: &mut Criterion) {
+    // A 4K view, where banding matters most.
+    let pano = panorama(16384);
+    let mut group = c.benchmark_group("remap bands");
+    group.throughput(Throughput::Elements(3840 * 2160));
+    for (name, rows) in [("one call", Some(2160)), ("auto", None), ("16 rows", Some(16))] {
+        let params = params().output_size(3840, 2160);
+        let params = match rows {
+            Some(rows) => params.remap_band_rows(rows),
+            None => params,
+        };
+        let projector = Projector::new(&params, (16384, 8192)).unwrap();
+        let mut dst = prelude::Mat::default();
+        group.bench_function(name, |b| b.iter(|| projector.apply_into(&pano, &mut dst).unwrap()));
+    }
+    group.finish();
+}
+
+fn end_to_end(c: &mut Criterion) {
+    // Every iteration builds its maps, as a render of a new view would.
+    e2p::cache::set_capacity(0);
+    let mut group = c.benchmark_group("end to end");
+    group.throughput(Throughput::Elements(1920 * 1080));
+    for backend in backends() {
+        for width in PANO_WIDTHS {
+            let pano = Equirectangular::from_mat(panorama(width)).unwrap();
+            let params = params().backend(backend);
+            group.bench_with_input(BenchmarkId::new(format!("{backend:?}"), width), &width, |b, _| {
+                b.iter(|| params.project(&pano).unwrap())
+            });
+        }
+    }
+    group.finish();
+    e2p::cache::set_capacity(e2p::cache::DEFAULT_CAPACITY);
+}
+
+criterion_group!(benches, map_build, remap, remap_bands, end_to_end);
+criterion_main!(benches);
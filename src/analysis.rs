@@ -0,0 +1,89 @@
+//! Per-pixel measurements of how a projection samples the sphere.
+
+use crate::projection::{Projection, Vec3};
+
+fn unit(v: Vec3) -> Vec3 {
+    let norm = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    v.map(|c| c / norm)
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Solid angle of the spherical triangle `a`, `b`, `c` (unit vectors), by
+/// Van Oosterom and Strackee.
+fn triangle_solid_angle(a: Vec3, b: Vec3, c: Vec3) -> f64 {
+    let cross = [b[1] * c[2] - b[2] * c[1], b[2] * c[0] - b[0] * c[2], b[0] * c[1] - b[1] * c[0]];
+    let numerator = dot(a, cross).abs();
+    let denominator = 1.0 + dot(a, b) + dot(b, c) + dot(c, a);
+    2.0 * numerator.atan2(denominator)
+}
+
+/// Steradians of the sphere each output pixel of `projection` covers,
+/// measured between the directions through its four corners. Pixels with
+/// a corner outside the projection's image area are 0. The reciprocal is
+/// the pixel density, and the map sums to about 4π for projections that
+/// cover the sphere once.
+pub fn solid_angle_map<P: Projection + ?Sized>(projection: &P) -> ndarray::Array2<f32> {
+    let (width, height) = projection.output_size();
+    let (width, height) = (width as usize, height as usize);
+    let corners = ndarray::Array2::from_shape_fn((height + 1, width + 1), |(v, u)| {
+        projection.direction_for_pixel(u as f64 - 0.5, v as f64 - 0.5).map(unit)
+    });
+    ndarray::Array2::from_shape_fn((height, width), |(v, u)| {
+        match (corners[[v, u]], corners[[v, u + 1]], corners[[v + 1, u + 1]], corners[[v + 1, u]]) {
+            (Some(a), Some(b), Some(c), Some(d)) => (triangle_solid_angle(a, b, c) + triangle_solid_angle(a, c, d)) as f32,
+            _ => 0.0,
+        }
+    })
+}
+
+/// Local stretch of a projection at every output pixel (Tissot's
+/// indicatrix): how many pixels one radian on the sphere spans along the
+/// most and least magnified directions. Pixels where the projection has no
+/// direction are 0 in every map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TissotMaps {
+    /// Largest magnification, in pixels per radian.
+    pub scale_max: ndarray::Array2<f32>,
+    /// Smallest magnification, in pixels per radian.
+    pub scale_min: ndarray::Array2<f32>,
+    /// `scale_max / scale_min`: 1 where the projection is locally
+    /// conformal, growing with shape distortion.
+    pub anisotropy: ndarray::Array2<f32>,
+}
+
+/// Computes [`TissotMaps`] for `projection` from central differences of
+/// its pixel directions.
+pub fn tissot_maps<P: Projection + ?Sized>(projection: &P) -> TissotMaps {
+    let (width, height) = projection.output_size();
+    let shape = (height as usize, width as usize);
+    let mut maps = TissotMaps {
+        scale_max: ndarray::Array2::zeros(shape),
+        scale_min: ndarray::Array2::zeros(shape),
+        anisotropy: ndarray::Array2::zeros(shape),
+    };
+    let at = |u: f64, v: f64| projection.direction_for_pixel(u, v).map(unit);
+    for ((v, u), scale_max) in maps.scale_max.indexed_iter_mut() {
+        let (x, y) = (u as f64, v as f64);
+        let (Some(left), Some(right), Some(up), Some(down)) = (at(x - 0.5, y), at(x + 0.5, y), at(x, y - 0.5), at(x, y + 0.5)) else {
+            continue;
+        };
+        // Columns of the Jacobian: radians moved per pixel along u and v.
+        let du: Vec3 = std::array::from_fn(|i| right[i] - left[i]);
+        let dv: Vec3 = std::array::from_fn(|i| down[i] - up[i]);
+        let (a, b, c) = (dot(du, du), dot(du, dv), dot(dv, dv));
+        let mean = (a + c) / 2.0;
+        let spread = (((a - c) / 2.0).powi(2) + b * b).sqrt();
+        let (stretch_max, stretch_min) = ((mean + spread).sqrt(), (mean - spread).max(0.0).sqrt());
+        if stretch_min <= 0.0 {
+            continue;
+        }
+        // Radians per pixel invert to pixels per radian.
+        *scale_max = (1.0 / stretch_min) as f32;
+        maps.scale_min[[v, u]] = (1.0 / stretch_max) as f32;
+        maps.anisotropy[[v, u]] = (stretch_max / stretch_min) as f32;
+    }
+    maps
+}
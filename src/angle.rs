@@ -0,0 +1,58 @@
+/// An angle in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deg(pub f64);
+
+/// An angle in radians.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rad(pub f64);
+
+/// A unit-checked angle, built from [`Deg`] or [`Rad`].
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Angle {
+    radians: f64,
+}
+
+impl Angle {
+    pub fn from_degrees(degrees: f64) -> Angle {
+        Angle { radians: degrees.to_radians() }
+    }
+
+    pub fn from_radians(radians: f64) -> Angle {
+        Angle { radians }
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.radians.to_degrees()
+    }
+
+    pub fn radians(self) -> f64 {
+        self.radians
+    }
+}
+
+impl From<Deg> for Angle {
+    fn from(d: Deg) -> Self {
+        Angle::from_degrees(d.0)
+    }
+}
+
+impl From<Rad> for Angle {
+    fn from(r: Rad) -> Self {
+        Angle::from_radians(r.0)
+    }
+}
+
+impl From<Angle> for Deg {
+    fn from(a: Angle) -> Self {
+        Deg(a.degrees())
+    }
+}
+
+impl From<Angle> for Rad {
+    fn from(a: Angle) -> Self {
+        Rad(a.radians())
+    }
+}
@@ -0,0 +1,485 @@
+//! Transfers annotations between the sphere and perspective views.
+//!
+//! Longitude and latitude are in degrees with latitude positive above the
+//! horizon, as in [`coords`](crate::coords).
+
+use opencv::core::Vector;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::coords;
+use crate::distortion::Distortion;
+use crate::error::Result;
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::rotation::{self, Matrix3};
+use crate::sampling::Border;
+
+/// Longest step, in degrees, between samples when an edge is traced.
+const EDGE_STEP: f64 = 1.0;
+
+/// A region bounded by two meridians and two parallels. A `lon_max`
+/// smaller than `lon_min` wraps across the ±180° meridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LonLatBox {
+    pub lon_min: f64,
+    pub lon_max: f64,
+    pub lat_min: f64,
+    pub lat_max: f64,
+}
+
+impl LonLatBox {
+    pub fn new(lon_min: f64, lon_max: f64, lat_min: f64, lat_max: f64) -> LonLatBox {
+        LonLatBox { lon_min, lon_max, lat_min, lat_max }
+    }
+
+    /// Longitude span going east from `lon_min`, in `(0, 360]`.
+    pub fn lon_span(&self) -> f64 {
+        let span = (self.lon_max - self.lon_min).rem_euclid(360.0);
+        if span == 0.0 && self.lon_max != self.lon_min { 360.0 } else { span }
+    }
+
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        let east = (lon - self.lon_min).rem_euclid(360.0);
+        east <= self.lon_span() && lat >= self.lat_min.min(self.lat_max) && lat <= self.lat_min.max(self.lat_max)
+    }
+
+    pub fn crosses_seam(&self) -> bool {
+        self.lon_min + self.lon_span() > 180.0
+    }
+
+    /// Closed outline traced along the parallels and meridians.
+    pub fn outline(&self) -> Vec<(f64, f64)> {
+        let span = self.lon_span();
+        let steps = (span / EDGE_STEP).ceil().max(1.0) as usize;
+        let lat_steps = ((self.lat_max - self.lat_min).abs() / EDGE_STEP).ceil().max(1.0) as usize;
+        let lon_at = |i: usize| self.lon_min + span * i as f64 / steps as f64;
+        let lat_at = |i: usize| self.lat_min + (self.lat_max - self.lat_min) * i as f64 / lat_steps as f64;
+        let mut points = Vec::with_capacity(2 * (steps + lat_steps) + 1);
+        points.extend((0..steps).map(|i| (lon_at(i), self.lat_max)));
+        points.extend((0..lat_steps).map(|i| (lon_at(steps), lat_at(lat_steps - i))));
+        points.extend((0..steps).map(|i| (lon_at(steps - i), self.lat_min)));
+        points.extend((0..=lat_steps).map(|i| (self.lon_min, lat_at(i))));
+        points
+    }
+
+    /// Pixel rectangles `(min, max)` covering the region on a
+    /// `pano_width`x`pano_height` equirectangular image: one, or two when
+    /// the region crosses the ±180° meridian.
+    pub fn equirect_rects(&self, pano_width: u32, pano_height: u32) -> Vec<((f64, f64), (f64, f64))> {
+        let (lat_min, lat_max) = (self.lat_min.min(self.lat_max), self.lat_min.max(self.lat_max));
+        let rect = |lon_min: f64, lon_max: f64| {
+            let (x0, y0) = coords::lonlat_to_equirect(lon_min, lat_max, pano_width, pano_height);
+            let (x1, y1) = coords::lonlat_to_equirect(lon_max, lat_min, pano_width, pano_height);
+            ((x0, y0), (x1, y1))
+        };
+        let start = (self.lon_min + 180.0).rem_euclid(360.0) - 180.0;
+        let end = start + self.lon_span();
+        if end > 180.0 {
+            vec![rect(start, 180.0), rect(-180.0, end - 360.0)]
+        } else {
+            vec![rect(start, end)]
+        }
+    }
+}
+
+/// A direction seen in a view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectedPoint {
+    /// Pixel position, possibly outside the image; `None` when the
+    /// direction is behind the camera.
+    pub pixel: Option<(f64, f64)>,
+    /// Whether the pixel lies inside the image.
+    pub in_view: bool,
+}
+
+/// Pixel bounding box of a projected region.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectedBox {
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+    /// Whether part of the region lies outside the image or behind the
+    /// camera, so the box was cut at the image edges.
+    pub clipped: bool,
+}
+
+/// A view pixel lifted back onto the sphere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiftedPoint {
+    pub lon: f64,
+    pub lat: f64,
+    /// Position on the equirectangular image the view was taken from.
+    pub equirect: (f64, f64),
+}
+
+/// Camera data needed to map world directions into one view.
+struct ViewGeometry {
+    k: Matrix3,
+    distortion: Option<Distortion>,
+    world_to_camera: Matrix3,
+    width: f64,
+    height: f64,
+}
+
+impl ViewGeometry {
+    fn new(params: &PerspectiveParams) -> Result<ViewGeometry> {
+        params.validate()?;
+        Ok(ViewGeometry {
+            k: params.intrinsics(),
+            distortion: params.distortion_coefficients(),
+            world_to_camera: rotation::transpose(&params.rotation()?),
+            width: params.width as f64,
+            height: params.height as f64,
+        })
+    }
+
+    fn camera_ray(&self, lon: f64, lat: f64) -> [f64; 3] {
+        rotation::apply(&self.world_to_camera, coords::lonlat_to_ray(lon, lat))
+    }
+
+    fn project(&self, ray: [f64; 3]) -> Option<(f64, f64)> {
+        coords::project_lens(&self.k, self.distortion.as_ref(), ray)
+    }
+
+    fn inside(&self, (x, y): (f64, f64)) -> bool {
+        x >= -0.5 && y >= -0.5 && x <= self.width - 0.5 && y <= self.height - 0.5
+    }
+
+    /// Clips a 2D segment to the image with Liang-Barsky.
+    fn clip_to_image(&self, a: (f64, f64), b: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let (mut t0, mut t1) = (0.0f64, 1.0f64);
+        let edges = [
+            (-dx, a.0 + 0.5),
+            (dx, self.width - 0.5 - a.0),
+            (-dy, a.1 + 0.5),
+            (dy, self.height - 0.5 - a.1),
+        ];
+        for (p, q) in edges {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        (t0 <= t1).then(|| ((a.0 + t0 * dx, a.1 + t0 * dy), (a.0 + t1 * dx, a.1 + t1 * dy)))
+    }
+}
+
+/// Camera rays with z below this are treated as behind the camera.
+const NEAR: f64 = 1e-6;
+
+/// Pixel of every `(lon, lat)` point in the view.
+pub fn project_points(params: &PerspectiveParams, points: &[(f64, f64)]) -> Result<Vec<ProjectedPoint>> {
+    let view = ViewGeometry::new(params)?;
+    Ok(points
+        .iter()
+        .map(|&(lon, lat)| {
+            let pixel = view.project(view.camera_ray(lon, lat));
+            ProjectedPoint {
+                pixel,
+                in_view: pixel.is_some_and(|p| view.inside(p)),
+            }
+        })
+        .collect())
+}
+
+/// Visible pieces of a polyline through `(lon, lat)` points, in pixels.
+/// Consecutive points are joined along straight lines in longitude and
+/// latitude (the way they look on the equirectangular image, taking the
+/// short way around the ±180° meridian), which may curve in the view.
+/// Parts behind the camera or outside the image are cut away, so one line
+/// can come back as several pieces.
+pub fn project_polyline(params: &PerspectiveParams, points: &[(f64, f64)]) -> Result<Vec<Vec<(f64, f64)>>> {
+    let view = ViewGeometry::new(params)?;
+    Ok(clip_polyline(&view, &densify(points)))
+}
+
+/// Pixel bounding box of a spherical region in the view, or `None` if no
+/// part of it is visible.
+pub fn project_box(params: &PerspectiveParams, region: &LonLatBox) -> Result<Option<ProjectedBox>> {
+    let view = ViewGeometry::new(params)?;
+    let outline = region.outline();
+    let pieces = clip_polyline(&view, &outline);
+    let fully_visible = pieces.len() == 1 && pieces[0].len() == outline.len();
+
+    // The visible part is bounded by the outline pieces and by the stretches
+    // of the image border that lie inside the region.
+    let mut extent: Vec<(f64, f64)> = pieces.into_iter().flatten().collect();
+    if !fully_visible {
+        const SAMPLES: usize = 64;
+        let (right, bottom) = (view.width - 0.5, view.height - 0.5);
+        for i in 0..=SAMPLES {
+            let t = i as f64 / SAMPLES as f64;
+            let (x, y) = (-0.5 + t * view.width, -0.5 + t * view.height);
+            for pixel in [(x, -0.5), (x, bottom), (-0.5, y), (right, y)] {
+                let (lon, lat) = coords::pixel_to_lonlat(params, pixel.0, pixel.1)?;
+                if region.contains(lon, lat) {
+                    extent.push(pixel);
+                }
+            }
+        }
+    }
+    if extent.is_empty() {
+        return Ok(None);
+    }
+    let (mut min, mut max) = ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY));
+    for (x, y) in extent {
+        min = (min.0.min(x), min.1.min(y));
+        max = (max.0.max(x), max.1.max(y));
+    }
+    Ok(Some(ProjectedBox {
+        min,
+        max,
+        clipped: !fully_visible,
+    }))
+}
+
+/// Longitude, latitude and equirectangular position of every view pixel,
+/// for a panorama of `pano_size` `(width, height)`.
+pub fn lift_points(params: &PerspectiveParams, pixels: &[(f64, f64)], pano_size: (u32, u32)) -> Result<Vec<LiftedPoint>> {
+    params.validate()?;
+    pixels
+        .iter()
+        .map(|&(u, v)| {
+            let (lon, lat) = coords::pixel_to_lonlat(params, u, v)?;
+            Ok(LiftedPoint {
+                lon,
+                lat,
+                equirect: coords::lonlat_to_equirect(lon, lat, pano_size.0, pano_size.1),
+            })
+        })
+        .collect()
+}
+
+/// Smallest [`LonLatBox`] enclosing the pixel box `min`-`max` of a view.
+/// Boxes straddling the ±180° meridian come back with `lon_max < lon_min`;
+/// boxes around a pole span every longitude and reach that pole.
+pub fn lift_box(params: &PerspectiveParams, min: (f64, f64), max: (f64, f64)) -> Result<LonLatBox> {
+    params.validate()?;
+    const SAMPLES: usize = 64;
+    let (x0, y0, x1, y1) = (min.0.min(max.0), min.1.min(max.1), min.0.max(max.0), min.1.max(max.1));
+    let mut lons = Vec::with_capacity(4 * (SAMPLES + 1));
+    let (mut lat_min, mut lat_max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for i in 0..=SAMPLES {
+        let t = i as f64 / SAMPLES as f64;
+        let (x, y) = (x0 + t * (x1 - x0), y0 + t * (y1 - y0));
+        for (u, v) in [(x, y0), (x, y1), (x0, y), (x1, y)] {
+            let (lon, lat) = coords::pixel_to_lonlat(params, u, v)?;
+            lons.push(lon);
+            lat_min = lat_min.min(lat);
+            lat_max = lat_max.max(lat);
+        }
+    }
+
+    let inside = |pixel: Option<(f64, f64)>| pixel.is_some_and(|(u, v)| u >= x0 && u <= x1 && v >= y0 && v <= y1);
+    let north = inside(coords::lonlat_to_pixel(params, 0.0, 90.0)?);
+    let south = inside(coords::lonlat_to_pixel(params, 0.0, -90.0)?);
+    if north || south {
+        return Ok(LonLatBox::new(
+            -180.0,
+            180.0,
+            if south { -90.0 } else { lat_min },
+            if north { 90.0 } else { lat_max },
+        ));
+    }
+
+    // The box covers the complement of the widest gap between sampled
+    // longitudes, which is where it crosses the seam if anywhere.
+    lons.sort_by(f64::total_cmp);
+    let mut gap = (lons[0] + 360.0 - lons[lons.len() - 1], 0);
+    for (i, pair) in lons.windows(2).enumerate() {
+        if pair[1] - pair[0] > gap.0 {
+            gap = (pair[1] - pair[0], i + 1);
+        }
+    }
+    let lon_min = lons[gap.1];
+    let lon_max = lons[(gap.1 + lons.len() - 1) % lons.len()];
+    Ok(LonLatBox::new(lon_min, lon_max, lat_min, lat_max))
+}
+
+/// Closed `(lon, lat)` outline of the region a view covers: its four image
+/// edges, each a great-circle arc, sampled every [`EDGE_STEP`] degrees.
+pub fn footprint(params: &PerspectiveParams) -> Result<Vec<(f64, f64)>> {
+    params.validate()?;
+    let (right, bottom) = (params.width as f64 - 0.5, params.height as f64 - 0.5);
+    let corners = [(-0.5, -0.5), (right, -0.5), (right, bottom), (-0.5, bottom), (-0.5, -0.5)];
+    let mut outline = Vec::new();
+    for pair in corners.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let (ra, rb) = (coords::pixel_to_ray(params, a.0, a.1)?, coords::pixel_to_ray(params, b.0, b.1)?);
+        let angle = (ra[0] * rb[0] + ra[1] * rb[1] + ra[2] * rb[2]).clamp(-1.0, 1.0).acos().to_degrees();
+        let steps = (angle / EDGE_STEP).ceil().max(1.0) as usize;
+        for i in 0..steps {
+            let t = i as f64 / steps as f64;
+            outline.push(coords::pixel_to_lonlat(params, a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1))?);
+        }
+    }
+    outline.push(outline[0]);
+    Ok(outline)
+}
+
+/// [`footprint`] in pixels of a `pano_width`x`pano_height` equirectangular
+/// image, split into pieces wherever it crosses the ±180° meridian.
+pub fn footprint_equirect(params: &PerspectiveParams, pano_width: u32, pano_height: u32) -> Result<Vec<Vec<(f64, f64)>>> {
+    let to_pixel = |(lon, lat): (f64, f64)| coords::lonlat_to_equirect(lon, lat, pano_width, pano_height);
+    let outline = footprint(params)?;
+    let mut pieces = Vec::new();
+    let mut current = vec![to_pixel(outline[0])];
+    for pair in outline.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let dlon = b.0 - a.0;
+        if dlon.abs() > 180.0 {
+            // Leave through one side of the image and come back on the other.
+            let edge = if dlon < 0.0 { 180.0 } else { -180.0 };
+            let t = (edge - a.0) / (dlon - dlon.signum() * 360.0);
+            let lat = a.1 + t * (b.1 - a.1);
+            current.push(to_pixel((edge, lat)));
+            pieces.push(std::mem::replace(&mut current, vec![to_pixel((-edge, lat))]));
+        }
+        current.push(to_pixel(b));
+    }
+    pieces.push(current);
+    Ok(pieces)
+}
+
+/// Draws the footprint of `params` onto `image`, a `pano_size` equirectangular
+/// frame or a crop of one at `offset`, as an antialiased `thickness` pixel
+/// line in the RGBA `color`.
+pub fn draw_footprint(
+    image: &mut prelude::Mat,
+    params: &PerspectiveParams,
+    pano_size: (u32, u32),
+    offset: (u32, u32),
+    color: [f64; 4],
+    thickness: i32,
+) -> Result<()> {
+    let color = Border::Constant(color).to_cv(image.channels()).1;
+    let pieces = footprint_equirect(params, pano_size.0, pano_size.1)?;
+    // A crop straddling the seam holds the right end of the frame on its
+    // left, one longitude period (`width - 1` pixels) further on.
+    for shift in [0.0, pano_size.0 as f64 - 1.0] {
+        let origin = (offset.0 as f64 - shift, offset.1 as f64);
+        draw_polylines(image, &pieces, origin, color, thickness)?;
+    }
+    Ok(())
+}
+
+/// Draws antialiased open polylines with subpixel precision, `origin`
+/// being subtracted from every point.
+pub(crate) fn draw_polylines(
+    image: &mut prelude::Mat,
+    pieces: &[Vec<(f64, f64)>],
+    origin: (f64, f64),
+    color: opencv::core::Scalar,
+    thickness: i32,
+) -> Result<()> {
+    const SHIFT: i32 = 4;
+    let scale = (1 << SHIFT) as f64;
+    let lines: Vector<Vector<opencv::core::Point>> = pieces
+        .iter()
+        .map(|piece| {
+            piece
+                .iter()
+                .map(|&(x, y)| opencv::core::Point::new(((x - origin.0) * scale).round() as i32, ((y - origin.1) * scale).round() as i32))
+                .collect()
+        })
+        .collect();
+    opencv::imgproc::polylines(image, &lines, false, color, thickness, opencv::imgproc::LINE_AA, SHIFT)?;
+    Ok(())
+}
+
+impl Equirectangular {
+    /// Copy of the panorama with the footprint of every view outlined in
+    /// the RGBA `color`, to check what each extraction covers.
+    pub fn draw_footprints(&self, views: &[PerspectiveParams], color: [f64; 4], thickness: i32) -> Result<prelude::Mat> {
+        let mut image = self.as_mat().try_clone()?;
+        let (pano_size, offset) = match self.crop() {
+            Some(crop) => ((crop.full_width, crop.full_height), (crop.left, crop.top)),
+            None => ((self.width(), self.height()), (0, 0)),
+        };
+        for view in views {
+            draw_footprint(&mut image, view, pano_size, offset, color, thickness)?;
+        }
+        Ok(image)
+    }
+}
+
+/// Inserts points so no step exceeds [`EDGE_STEP`] in longitude or
+/// latitude, taking the short way around in longitude.
+fn densify(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut out = Vec::with_capacity(points.len());
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let dlon = (b.0 - a.0 + 180.0).rem_euclid(360.0) - 180.0;
+        let dlat = b.1 - a.1;
+        let steps = (dlon.abs().max(dlat.abs()) / EDGE_STEP).ceil().max(1.0) as usize;
+        out.extend((0..steps).map(|i| {
+            let t = i as f64 / steps as f64;
+            (a.0 + dlon * t, a.1 + dlat * t)
+        }));
+    }
+    out.extend(points.last());
+    out
+}
+
+/// Projects a densified polyline, cutting it at the near plane and the
+/// image edges.
+fn clip_polyline(view: &ViewGeometry, points: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    let rays: Vec<_> = points.iter().map(|&(lon, lat)| view.camera_ray(lon, lat)).collect();
+    if rays.len() == 1 {
+        return view.project(rays[0]).filter(|&p| view.inside(p)).map(|p| vec![p]).into_iter().collect();
+    }
+    let mut pieces: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+    let mut flush = |current: &mut Vec<(f64, f64)>| {
+        if current.len() > 1 {
+            pieces.push(std::mem::take(current));
+        } else {
+            current.clear();
+        }
+    };
+    for pair in rays.windows(2) {
+        let (mut a, mut b) = (pair[0], pair[1]);
+        if a[2] < NEAR && b[2] < NEAR {
+            flush(&mut current);
+            continue;
+        }
+        let cut_start = a[2] < NEAR;
+        let cut_end = b[2] < NEAR;
+        if cut_start || cut_end {
+            let t = (NEAR - a[2]) / (b[2] - a[2]);
+            let hit: [f64; 3] = std::array::from_fn(|i| a[i] + t * (b[i] - a[i]));
+            if cut_start {
+                a = hit;
+            } else {
+                b = hit;
+            }
+        }
+        let (Some(pa), Some(pb)) = (view.project(a), view.project(b)) else {
+            flush(&mut current);
+            continue;
+        };
+        match view.clip_to_image(pa, pb) {
+            Some((ca, cb)) => {
+                let starts_fresh = cut_start || ca != pa || current.is_empty();
+                if starts_fresh {
+                    flush(&mut current);
+                    current.push(ca);
+                }
+                current.push(cb);
+                if cut_end || cb != pb {
+                    flush(&mut current);
+                }
+            }
+            None => flush(&mut current),
+        }
+    }
+    flush(&mut current);
+    pieces
+}
@@ -0,0 +1,72 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::projection::EquirectProjection;
+use crate::sampling::RenderOptions;
+use crate::source::{self, PerspectiveSource, SourceProjection};
+
+/// An image on an equirectangular canvas together with its coverage.
+pub struct EquirectLayer {
+    pub image: prelude::Mat,
+    /// `CV_8U`, 255 where the canvas pixel was covered by the source image.
+    pub mask: prelude::Mat,
+}
+
+/// Renders any source image onto a full equirectangular canvas of
+/// `canvas_size` `(width, height)`. Uncovered pixels are filled with the
+/// border color, black unless set otherwise.
+pub fn to_equirectangular<S: SourceProjection + ?Sized>(
+    src: &prelude::Mat,
+    source: &S,
+    canvas_size: (u32, u32),
+    options: &RenderOptions,
+) -> Result<EquirectLayer> {
+    let (width, height) = canvas_size;
+    let canvas = EquirectProjection::new(width, height);
+    let (image, mask) = source::render_with_mask(src, source, &canvas, options)?;
+    Ok(EquirectLayer { image, mask })
+}
+
+/// A perspective image with the view it was taken with, e.g. a view
+/// rendered by [`PerspectiveParams::project`] and then edited.
+pub struct Perspective {
+    image: prelude::Mat,
+    params: PerspectiveParams,
+}
+
+impl Perspective {
+    pub fn new(image: prelude::Mat, params: PerspectiveParams) -> Result<Perspective> {
+        params.validate()?;
+        if image.cols() != params.width as i32 || image.rows() != params.height as i32 {
+            return Err(Error::InvalidParameter(format!(
+                "image is {}x{} but the view is {}x{}",
+                image.cols(),
+                image.rows(),
+                params.width,
+                params.height
+            )));
+        }
+        Ok(Perspective { image, params })
+    }
+
+    pub fn image(&self) -> &prelude::Mat {
+        &self.image
+    }
+
+    pub fn params(&self) -> &PerspectiveParams {
+        &self.params
+    }
+
+    /// Splats the image back onto an equirectangular canvas of
+    /// `canvas_size` `(width, height)` using the view's own render options.
+    pub fn to_equirectangular(&self, canvas_size: (u32, u32)) -> Result<EquirectLayer> {
+        self.to_equirectangular_with(canvas_size, &self.params.render_options())
+    }
+
+    pub fn to_equirectangular_with(&self, canvas_size: (u32, u32), options: &RenderOptions) -> Result<EquirectLayer> {
+        let source = PerspectiveSource::new(&self.params)?;
+        to_equirectangular(&self.image, &source, canvas_size, options)
+    }
+}
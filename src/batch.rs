@@ -0,0 +1,200 @@
+//! Pipelined batch rendering of many panoramas: decoding, projection and
+//! encoding run in their own threads connected by bounded channels, so every
+//! core stays busy while only a few panoramas are in memory at once.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::sync::Mutex;
+
+use opencv::prelude;
+
+use crate::error::{Error, Result};
+use crate::output::EncodeOptions;
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::pool;
+use crate::stats::{self, Stage};
+
+/// Renders the same views of every input file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    views: Vec<PerspectiveParams>,
+    workers: usize,
+    encoders: usize,
+    queue_depth: usize,
+    encode: EncodeOptions,
+}
+
+/// Outcome of [`Batch::run`]. A failing file does not stop the others.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Number of views written.
+    pub written: usize,
+    /// Inputs or outputs that failed, with the reason.
+    pub failures: Vec<(PathBuf, Error)>,
+}
+
+type Rendered = (PathBuf, prelude::Mat);
+
+/// Receives from a channel shared by several workers until it closes.
+fn next<T>(receiver: &Mutex<Receiver<T>>) -> Option<T> {
+    receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv().ok()
+}
+
+pub(crate) fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| Error::InvalidParameter(format!("{} is not valid UTF-8", path.display())))
+}
+
+/// `path` made absolute with symlinks resolved as far as it exists, so
+/// outputs that do not exist yet compare equal to the inputs they would
+/// replace.
+pub(crate) fn resolve(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent().map(|parent| parent.canonicalize()), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name),
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// Encodes `image` to `path` in the format its extension names.
+pub(crate) fn write(path: &Path, image: &prelude::Mat, options: &EncodeOptions) -> Result<()> {
+    let _timer = stats::time(Stage::Encode);
+    if !opencv::imgcodecs::imwrite(path_str(path)?, image, &options.to_cv())? {
+        return Err(Error::Io(std::io::Error::other(format!("could not encode {}", path.display()))));
+    }
+    Ok(())
+}
+
+impl Batch {
+    /// A batch rendering `views` of each input, with one projection worker
+    /// per core.
+    pub fn new(views: Vec<PerspectiveParams>) -> Batch {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Batch {
+            views,
+            workers: cores,
+            encoders: cores.div_ceil(2),
+            queue_depth: 2,
+            encode: EncodeOptions::default(),
+        }
+    }
+
+    /// Threads projecting views.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Threads encoding and writing outputs.
+    pub fn encoders(mut self, encoders: usize) -> Self {
+        self.encoders = encoders;
+        self
+    }
+
+    /// Decoded panoramas waiting for a projection worker. At most this many
+    /// plus one per worker are held in memory.
+    pub fn queue_depth(mut self, depth: usize) -> Self {
+        self.queue_depth = depth;
+        self
+    }
+
+    pub fn encode_options(mut self, options: EncodeOptions) -> Self {
+        self.encode = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.encode.validate()?;
+        if self.views.is_empty() {
+            return Err(Error::InvalidParameter("a batch needs at least one view".to_string()));
+        }
+        if self.workers == 0 || self.encoders == 0 || self.queue_depth == 0 {
+            return Err(Error::InvalidParameter(format!(
+                "workers, encoders and queue depth must be non-zero, got {}, {} and {}",
+                self.workers, self.encoders, self.queue_depth
+            )));
+        }
+        for view in &self.views {
+            view.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Renders every view of every input, writing view `i` of `input` to
+    /// `output(input, i)` in the format its extension names.
+    pub fn run<I, F>(&self, inputs: I, output: F) -> Result<BatchReport>
+    where
+        I: IntoIterator<Item = PathBuf>,
+        I::IntoIter: Send,
+        F: Fn(&Path, usize) -> PathBuf + Sync,
+    {
+        self.validate()?;
+        let inputs = inputs.into_iter();
+        let (decoded_tx, decoded_rx) = sync_channel::<(PathBuf, Equirectangular)>(self.queue_depth);
+        let (rendered_tx, rendered_rx) = sync_channel::<Rendered>(self.queue_depth * self.views.len());
+        let (decoded_rx, rendered_rx) = (Mutex::new(decoded_rx), Mutex::new(rendered_rx));
+        let failures = Mutex::new(Vec::new());
+        let fail = |path: PathBuf, error: Error| failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push((path, error));
+        let written = Mutex::new(0);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // Owned here so the workers see the channel close.
+                let decoded_tx = decoded_tx;
+                for path in inputs {
+                    match path_str(&path) {
+                        Ok(name) => match Equirectangular::new(name) {
+                            Ok(pano) => {
+                                if decoded_tx.send((path, pano)).is_err() {
+                                    return;
+                                }
+                            }
+                            Err(error) => fail(path, error),
+                        },
+                        Err(error) => fail(path, error),
+                    }
+                }
+            });
+            for _ in 0..self.workers {
+                let rendered_tx = rendered_tx.clone();
+                scope.spawn(|| {
+                    let rendered_tx = rendered_tx;
+                    while let Some((path, pano)) = next(&decoded_rx) {
+                        for (index, view) in self.views.iter().enumerate() {
+                            match view.project(&pano) {
+                                Ok(image) => {
+                                    if rendered_tx.send((output(&path, index), image)).is_err() {
+                                        return;
+                                    }
+                                }
+                                Err(error) => fail(path.clone(), error),
+                            }
+                        }
+                    }
+                });
+            }
+            drop(rendered_tx);
+            for _ in 0..self.encoders {
+                scope.spawn(|| {
+                    while let Some((path, image)) = next(&rendered_rx) {
+                        match write(&path, &image, &self.encode) {
+                            Ok(()) => *written.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += 1,
+                            Err(error) => fail(path, error),
+                        }
+                        pool::recycle(image);
+                    }
+                });
+            }
+        });
+
+        let mut failures = failures.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Workers finish in any order; report failures the same way every run.
+        failures.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(BatchReport {
+            written: written.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+            failures,
+        })
+    }
+}
@@ -0,0 +1,127 @@
+use opencv::core::Vector;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::error::{Error, Result};
+use crate::maps;
+use crate::projection::{Projection, Vec3};
+
+/// Per-pixel weight over the output of `projection`, 0 where the
+/// projection has no direction.
+pub(crate) fn weight_map<P, F>(projection: &P, weight: F) -> ndarray::Array2<f32>
+where
+    P: Projection + ?Sized,
+    F: Fn(Vec3) -> f32,
+{
+    let (width, height) = projection.output_size();
+    ndarray::Array2::from_shape_fn((height as usize, width as usize), |(v, u)| {
+        projection.direction_for_pixel(u as f64, v as f64).map_or(0.0, &weight)
+    })
+}
+
+/// Blends images of the same size and type by per-pixel weights, which are
+/// normalized to sum to one wherever any of them is positive. Pixels where
+/// every weight is 0 come out as 0.
+pub(crate) fn weighted_average(images: &[prelude::Mat], mut weights: Vec<ndarray::Array2<f32>>) -> Result<prelude::Mat> {
+    let first = images
+        .first()
+        .ok_or_else(|| Error::InvalidParameter("nothing to blend".to_string()))?;
+    let (rows, cols, typ, channels) = (first.rows(), first.cols(), first.typ(), first.channels());
+    if images.len() != weights.len() || images.iter().any(|image| image.rows() != rows || image.cols() != cols || image.typ() != typ) {
+        return Err(Error::InvalidParameter("blended images must share size and type, one weight map each".to_string()));
+    }
+
+    let mut total = ndarray::Array2::<f32>::zeros((rows as usize, cols as usize));
+    for weight in &weights {
+        total += weight;
+    }
+    for weight in &mut weights {
+        ndarray::Zip::from(weight).and(&total).for_each(|w, &t| *w = if t > 0.0 { *w / t } else { 0.0 });
+    }
+
+    let float_type = opencv::core::CV_MAKETYPE(opencv::core::CV_32F, channels);
+    let mut acc = prelude::Mat::new_rows_cols_with_default(rows, cols, float_type, opencv::core::Scalar::all(0.0))?;
+    for (image, weight) in images.iter().zip(&weights) {
+        let mut image_f = prelude::Mat::default();
+        image.convert_to(&mut image_f, opencv::core::CV_32F, 1.0, 0.0)?;
+        let plane = maps::to_mat(weight)?;
+        let mut weight_n = prelude::Mat::default();
+        opencv::core::merge(&Vector::<prelude::Mat>::from_iter((0..channels).map(|_| plane.clone())), &mut weight_n)?;
+        let mut term = prelude::Mat::default();
+        opencv::core::multiply(&image_f, &weight_n, &mut term, 1.0, -1)?;
+        let mut sum = prelude::Mat::default();
+        opencv::core::add(&acc, &term, &mut sum, &opencv::core::no_array(), -1)?;
+        acc = sum;
+    }
+    let mut out = prelude::Mat::default();
+    acc.convert_to(&mut out, first.depth(), 1.0, 0.0)?;
+    Ok(out)
+}
+
+fn laplacian_pyramid(image: &prelude::Mat, levels: usize) -> Result<Vec<prelude::Mat>> {
+    let mut gaussian = Vec::with_capacity(levels);
+    gaussian.push(image.try_clone()?);
+    for _ in 1..levels {
+        let mut down = prelude::Mat::default();
+        opencv::imgproc::pyr_down(gaussian.last().expect("pyramid has a base"), &mut down, opencv::core::Size::default(), opencv::core::BORDER_DEFAULT)?;
+        gaussian.push(down);
+    }
+    let mut laplacian = Vec::with_capacity(levels);
+    for pair in gaussian.windows(2) {
+        let mut up = prelude::Mat::default();
+        opencv::imgproc::pyr_up(&pair[1], &mut up, pair[0].size()?, opencv::core::BORDER_DEFAULT)?;
+        let mut detail = prelude::Mat::default();
+        opencv::core::subtract(&pair[0], &up, &mut detail, &opencv::core::no_array(), -1)?;
+        laplacian.push(detail);
+    }
+    laplacian.push(gaussian.pop().expect("pyramid has a base"));
+    Ok(laplacian)
+}
+
+/// Multiband (Laplacian pyramid) blend: low frequencies are mixed over wide
+/// transitions and fine detail over narrow ones, which hides exposure
+/// differences without ghosting. Weights are normalized per level like in
+/// [`weighted_average`].
+pub(crate) fn multiband(images: &[prelude::Mat], weights: Vec<ndarray::Array2<f32>>, levels: u32) -> Result<prelude::Mat> {
+    let first = images
+        .first()
+        .ok_or_else(|| Error::InvalidParameter("nothing to blend".to_string()))?;
+    if images.len() != weights.len() {
+        return Err(Error::InvalidParameter("blended images need one weight map each".to_string()));
+    }
+    let smallest = first.rows().min(first.cols()).max(1) as u32;
+    let levels = levels.clamp(1, smallest.ilog2() + 1) as usize;
+
+    let mut image_pyramids = Vec::with_capacity(images.len());
+    let mut weight_pyramids = Vec::with_capacity(images.len());
+    for (image, weight) in images.iter().zip(&weights) {
+        let mut image_f = prelude::Mat::default();
+        image.convert_to(&mut image_f, opencv::core::CV_32F, 1.0, 0.0)?;
+        image_pyramids.push(laplacian_pyramid(&image_f, levels)?);
+
+        let mut pyramid = vec![maps::to_mat(weight)?];
+        for _ in 1..levels {
+            let mut down = prelude::Mat::default();
+            opencv::imgproc::pyr_down(pyramid.last().expect("pyramid has a base"), &mut down, opencv::core::Size::default(), opencv::core::BORDER_DEFAULT)?;
+            pyramid.push(down);
+        }
+        weight_pyramids.push(pyramid);
+    }
+
+    let mut blended = Vec::with_capacity(levels);
+    for level in 0..levels {
+        let bands: Vec<_> = image_pyramids.iter().map(|p| p[level].clone()).collect();
+        let level_weights = weight_pyramids.iter().map(|p| maps::to_array(&p[level])).collect::<Result<Vec<_>>>()?;
+        blended.push(weighted_average(&bands, level_weights)?);
+    }
+
+    let mut result = blended.pop().expect("at least one level");
+    while let Some(detail) = blended.pop() {
+        let mut up = prelude::Mat::default();
+        opencv::imgproc::pyr_up(&result, &mut up, detail.size()?, opencv::core::BORDER_DEFAULT)?;
+        opencv::core::add(&up, &detail, &mut result, &opencv::core::no_array(), -1)?;
+    }
+    let mut out = prelude::Mat::default();
+    result.convert_to(&mut out, first.depth(), 1.0, 0.0)?;
+    Ok(out)
+}
@@ -0,0 +1,87 @@
+//! Process-wide least-recently-used cache of perspective sampling maps, so
+//! repeated renders of the same view (viewers, web services) skip the
+//! geometry entirely and only cost a `remap`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::Result;
+use crate::maps::RemapMaps;
+use crate::params::PerspectiveParams;
+use crate::perspective::CroppedPanoParams;
+use crate::rotation::Matrix3;
+
+/// Number of views kept unless changed with [`set_capacity`].
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// Everything the maps of a view depend on.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MapKey {
+    pub(crate) params: PerspectiveParams,
+    pub(crate) pano_size: (u32, u32),
+    pub(crate) crop: Option<CroppedPanoParams>,
+    pub(crate) orientation: Option<Matrix3>,
+}
+
+struct MapCache {
+    capacity: usize,
+    /// Least recently used first.
+    entries: Vec<(MapKey, Arc<RemapMaps>)>,
+}
+
+static CACHE: Mutex<MapCache> = Mutex::new(MapCache {
+    capacity: DEFAULT_CAPACITY,
+    entries: Vec::new(),
+});
+
+fn cache() -> MutexGuard<'static, MapCache> {
+    // The cache holds no invariants a panicking thread could break.
+    CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The cached maps for `key`, or the result of `build` stored under it.
+/// The lock is not held while building, so threads rendering different
+/// views never wait on each other.
+pub(crate) fn get_or_build(key: MapKey, build: impl FnOnce() -> Result<RemapMaps>) -> Result<Arc<RemapMaps>> {
+    {
+        let mut cache = cache();
+        if let Some(index) = cache.entries.iter().position(|(cached, _)| *cached == key) {
+            let entry = cache.entries.remove(index);
+            let maps = entry.1.clone();
+            cache.entries.push(entry);
+            return Ok(maps);
+        }
+    }
+    let maps = Arc::new(build()?);
+    let mut cache = cache();
+    if cache.capacity > 0 {
+        while cache.entries.len() >= cache.capacity {
+            cache.entries.remove(0);
+        }
+        cache.entries.push((key, maps.clone()));
+    }
+    Ok(maps)
+}
+
+/// Sets how many views are kept, evicting the least recently used ones
+/// beyond it. Each entry holds two `f32` maps of the view's output size;
+/// 0 disables caching.
+pub fn set_capacity(capacity: usize) {
+    let mut cache = cache();
+    cache.capacity = capacity;
+    let excess = cache.entries.len().saturating_sub(capacity);
+    cache.entries.drain(..excess);
+}
+
+pub fn capacity() -> usize {
+    cache().capacity
+}
+
+/// Number of views currently cached.
+pub fn len() -> usize {
+    cache().entries.len()
+}
+
+/// Drops every cached view.
+pub fn clear() {
+    cache().entries.clear();
+}
@@ -0,0 +1,148 @@
+//! Point-wise conversions between perspective pixels, world directions,
+//! spherical coordinates and equirectangular pixels.
+//!
+//! Directions use the camera frame convention (x right, y down, z forward
+//! at longitude 0). Longitude and latitude are in degrees, with latitude
+//! positive above the horizon.
+
+use crate::distortion::Distortion;
+use crate::error::Result;
+use crate::params::PerspectiveParams;
+use crate::rotation::{self, Matrix3};
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let norm = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    v.map(|c| c / norm)
+}
+
+/// Camera-space ray through `(u, v)` for intrinsics `k`, with z = 1.
+pub(crate) fn unproject(k: &Matrix3, u: f64, v: f64) -> [f64; 3] {
+    let y = (v - k[1][2]) / k[1][1];
+    let x = (u - k[0][2] - k[0][1] * y) / k[0][0];
+    [x, y, 1.0]
+}
+
+/// Output pixel for a camera-space ray, or `None` if it points behind the camera.
+pub(crate) fn project(k: &Matrix3, ray: [f64; 3]) -> Option<(f64, f64)> {
+    if ray[2] <= 0.0 {
+        return None;
+    }
+    let (x, y) = (ray[0] / ray[2], ray[1] / ray[2]);
+    Some((k[0][0] * x + k[0][1] * y + k[0][2], k[1][1] * y + k[1][2]))
+}
+
+/// Like [`unproject`] through a lens with `distortion`.
+pub(crate) fn unproject_lens(k: &Matrix3, distortion: Option<&Distortion>, u: f64, v: f64) -> [f64; 3] {
+    let [x, y, _] = unproject(k, u, v);
+    let (x, y) = distortion.map_or((x, y), |d| d.undistort(x, y));
+    [x, y, 1.0]
+}
+
+/// Like [`project`] through a lens with `distortion`.
+pub(crate) fn project_lens(k: &Matrix3, distortion: Option<&Distortion>, ray: [f64; 3]) -> Option<(f64, f64)> {
+    let Some(distortion) = distortion else {
+        return project(k, ray);
+    };
+    if ray[2] <= 0.0 {
+        return None;
+    }
+    let (x, y) = distortion.distort(ray[0] / ray[2], ray[1] / ray[2]);
+    project(k, [x, y, 1.0])
+}
+
+/// Unit world direction seen through output pixel `(u, v)` of a view.
+pub fn pixel_to_ray(params: &PerspectiveParams, u: f64, v: f64) -> Result<[f64; 3]> {
+    let ray = unproject_lens(&params.intrinsics(), params.distortion_coefficients().as_ref(), u, v);
+    Ok(normalize(rotation::apply(&params.rotation()?, ray)))
+}
+
+/// Output pixel of a view that sees world direction `dir`, or `None` if the
+/// direction is behind the camera. The pixel may lie outside the output.
+pub fn ray_to_pixel(params: &PerspectiveParams, dir: [f64; 3]) -> Result<Option<(f64, f64)>> {
+    let camera = rotation::apply(&rotation::transpose(&params.rotation()?), dir);
+    Ok(project_lens(&params.intrinsics(), params.distortion_coefficients().as_ref(), camera))
+}
+
+pub fn ray_to_lonlat(dir: [f64; 3]) -> (f64, f64) {
+    let [x, y, z] = normalize(dir);
+    (x.atan2(z).to_degrees(), -y.asin().to_degrees())
+}
+
+pub fn lonlat_to_ray(lon: f64, lat: f64) -> [f64; 3] {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    [lat.cos() * lon.sin(), -lat.sin(), lat.cos() * lon.cos()]
+}
+
+pub fn pixel_to_lonlat(params: &PerspectiveParams, u: f64, v: f64) -> Result<(f64, f64)> {
+    Ok(ray_to_lonlat(pixel_to_ray(params, u, v)?))
+}
+
+pub fn lonlat_to_pixel(params: &PerspectiveParams, lon: f64, lat: f64) -> Result<Option<(f64, f64)>> {
+    ray_to_pixel(params, lonlat_to_ray(lon, lat))
+}
+
+/// Equirectangular pixel `(x, y)` for a point on the sphere, matching the
+/// sampling used by the projections.
+pub fn lonlat_to_equirect(lon: f64, lat: f64, pano_width: u32, pano_height: u32) -> (f64, f64) {
+    let x = (lon / 360.0 + 0.5) * (pano_width as f64 - 1.0);
+    let y = (-lat / 180.0 + 0.5) * (pano_height as f64 - 1.0);
+    (x, y)
+}
+
+pub fn equirect_to_lonlat(x: f64, y: f64, pano_width: u32, pano_height: u32) -> (f64, f64) {
+    let lon = (x / (pano_width as f64 - 1.0) - 0.5) * 360.0;
+    let lat = -(y / (pano_height as f64 - 1.0) - 0.5) * 180.0;
+    (lon, lat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Deg;
+
+    #[test]
+    fn pixels_round_trip_through_rays() {
+        for params in [
+            PerspectiveParams::new().output_size(64, 48),
+            PerspectiveParams::new()
+                .fov(Deg(120.0))
+                .yaw(Deg(-135.0))
+                .pitch(Deg(40.0))
+                .roll(Deg(15.0))
+                .output_size(80, 40),
+        ] {
+            for (u, v) in [(0.0, 0.0), (31.5, 23.5), (63.0, 10.25), (5.0, 39.0)] {
+                let ray = pixel_to_ray(&params, u, v).unwrap();
+                let (x, y) = ray_to_pixel(&params, ray).unwrap().unwrap();
+                assert!((x - u).abs() < 1e-9 && (y - v).abs() < 1e-9, "({u}, {v}) came back as ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn lonlat_round_trips_through_rays() {
+        for (lon, lat) in [(0.0, 0.0), (90.0, 45.0), (-179.0, -60.0), (135.5, 89.0), (-30.0, -10.0)] {
+            let ray = lonlat_to_ray(lon, lat);
+            assert!((ray.iter().map(|c| c * c).sum::<f64>() - 1.0).abs() < 1e-12);
+            let (x, y) = ray_to_lonlat(ray);
+            assert!((x - lon).abs() < 1e-9 && (y - lat).abs() < 1e-9, "({lon}, {lat}) came back as ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn view_center_looks_at_its_yaw_and_pitch() {
+        let params = PerspectiveParams::new().yaw(Deg(60.0)).pitch(Deg(20.0)).output_size(65, 33);
+        let (lon, lat) = pixel_to_lonlat(&params, 32.0, 16.0).unwrap();
+        assert!((lon - 60.0).abs() < 1e-9 && (lat - 20.0).abs() < 1e-9, "center looks at ({lon}, {lat})");
+        assert!(lonlat_to_pixel(&params, -120.0, -20.0).unwrap().is_none());
+    }
+
+    #[test]
+    fn equirect_pixels_round_trip() {
+        let (x, y) = lonlat_to_equirect(-180.0, 90.0, 400, 200);
+        assert_eq!((x, y), (0.0, 0.0));
+        let (lon, lat) = equirect_to_lonlat(123.5, 77.25, 400, 200);
+        let (x, y) = lonlat_to_equirect(lon, lat, 400, 200);
+        assert!((x - 123.5).abs() < 1e-9 && (y - 77.25).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,508 @@
+use opencv::core::Vector;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::Deg;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::projection::{EquirectProjection, Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::{Interpolation, RenderOptions};
+use crate::source::{self, SourceProjection};
+
+/// One face of a cube map, named by the direction it looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CubeFace {
+    /// +X, longitude 90.
+    Right,
+    /// -X, longitude -90.
+    Left,
+    /// +Y in cube map terms, the zenith.
+    Up,
+    /// -Y in cube map terms, the nadir.
+    Down,
+    /// +Z, longitude 0.
+    Front,
+    /// -Z, longitude 180.
+    Back,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::Right,
+        CubeFace::Left,
+        CubeFace::Up,
+        CubeFace::Down,
+        CubeFace::Front,
+        CubeFace::Back,
+    ];
+
+    /// 90 degree view of this face. Side faces are upright; the up face's
+    /// bottom edge and the down face's top edge touch the front face.
+    pub fn view(self, face_size: u32) -> PerspectiveParams {
+        let params = PerspectiveParams::new()
+            .fov_x(Deg(90.0))
+            .fov_y(Deg(90.0))
+            .output_size(face_size, face_size);
+        match self {
+            CubeFace::Right => params.look_at(Deg(90.0), Deg(0.0)),
+            CubeFace::Left => params.look_at(Deg(-90.0), Deg(0.0)),
+            CubeFace::Front => params.look_at(Deg(0.0), Deg(0.0)),
+            CubeFace::Back => params.look_at(Deg(180.0), Deg(0.0)),
+            CubeFace::Up => params.look_at_with_up(Deg(0.0), Deg(90.0), [0.0, 0.0, -1.0]),
+            CubeFace::Down => params.look_at_with_up(Deg(0.0), Deg(-90.0), [0.0, 0.0, 1.0]),
+        }
+    }
+}
+
+/// Order in which faces are laid out, following common tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FaceOrder {
+    /// `GL_TEXTURE_CUBE_MAP_POSITIVE_X` onward: +X, -X, +Y, -Y, +Z, -Z.
+    #[default]
+    OpenGl,
+    /// Unity's six-sided skybox slots: front, back, left, right, up, down.
+    Unity,
+    /// ffmpeg `v360` default `rludfb`.
+    Ffmpeg,
+    Custom([CubeFace; 6]),
+}
+
+impl FaceOrder {
+    pub fn faces(self) -> [CubeFace; 6] {
+        use CubeFace::*;
+        match self {
+            FaceOrder::OpenGl | FaceOrder::Ffmpeg => [Right, Left, Up, Down, Front, Back],
+            FaceOrder::Unity => [Front, Back, Left, Right, Up, Down],
+            FaceOrder::Custom(faces) => faces,
+        }
+    }
+}
+
+/// How pixels are distributed over each face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CubemapStyle {
+    /// Plain 90 degree perspective faces.
+    #[default]
+    Standard,
+    /// Equi-Angular Cubemap (EAC, as used by YouTube): pixels are evenly
+    /// spaced in angle rather than on the face plane, so density stays
+    /// nearly uniform towards the face edges.
+    EquiAngular,
+}
+
+impl CubemapStyle {
+    /// Face plane coordinate (`x / z` in the face's camera frame) for a
+    /// normalized pixel coordinate in `[-1, 1]`.
+    fn to_plane(self, s: f64) -> f64 {
+        match self {
+            CubemapStyle::Standard => s,
+            CubemapStyle::EquiAngular => (s * std::f64::consts::FRAC_PI_4).tan(),
+        }
+    }
+
+    fn from_plane(self, t: f64) -> f64 {
+        match self {
+            CubemapStyle::Standard => t,
+            CubemapStyle::EquiAngular => t.atan() / std::f64::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// One cube face in a given style.
+pub(crate) struct FaceView {
+    rotation: Matrix3,
+    style: CubemapStyle,
+    size: u32,
+}
+
+impl FaceView {
+    pub(crate) fn new(face: CubeFace, style: CubemapStyle, size: u32) -> Result<FaceView> {
+        Ok(FaceView {
+            rotation: face.view(size).rotation()?,
+            style,
+            size,
+        })
+    }
+}
+
+impl Projection for FaceView {
+    fn output_size(&self) -> (u32, u32) {
+        (self.size, self.size)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        Some(face_ray(&self.rotation, self.style, self.size, u, v))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CubemapParams {
+    pub face_size: u32,
+    pub order: FaceOrder,
+    pub style: CubemapStyle,
+    pub options: RenderOptions,
+}
+
+impl CubemapParams {
+    pub fn new(face_size: u32) -> CubemapParams {
+        CubemapParams {
+            face_size,
+            order: FaceOrder::default(),
+            style: CubemapStyle::default(),
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn order(mut self, order: FaceOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn style(mut self, style: CubemapStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.face_size == 0 {
+            return Err(Error::InvalidParameter("cube face size must be non-zero".to_string()));
+        }
+        let faces = self.order.faces();
+        if CubeFace::ALL.iter().any(|face| !faces.contains(face)) {
+            return Err(Error::InvalidParameter(format!("face order {faces:?} must list every face once")));
+        }
+        Ok(())
+    }
+
+    /// The view of every face, in `order`.
+    pub fn views(&self) -> Vec<PerspectiveParams> {
+        self.order
+            .faces()
+            .iter()
+            .map(|face| {
+                face.view(self.face_size)
+                    .with_render_options(&self.options)
+            })
+            .collect()
+    }
+}
+
+/// Arrangement of all six faces in one atlas image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CubeLayout {
+    /// 4x3 cross with up and down above and below the front face:
+    /// `. U . . / L F R B / . D . .`.
+    #[default]
+    HorizontalCross,
+    /// 3x4 cross with the back face below down, rotated 180 degrees:
+    /// `. U . / L F R / . D . / . B .`.
+    VerticalCross,
+    /// Faces in cube map order, three per row.
+    Grid3x2,
+    /// Faces in cube map order side by side.
+    Strip6x1,
+    /// Faces in cube map order stacked vertically.
+    Strip1x6,
+}
+
+/// Cells of `layout` as rows of optional faces; grid and strip cells follow
+/// the face order `o`.
+fn layout_cells(layout: CubeLayout, o: [CubeFace; 6]) -> Vec<Vec<Option<CubeFace>>> {
+    use CubeFace::*;
+    match layout {
+        CubeLayout::HorizontalCross => vec![
+            vec![None, Some(Up), None, None],
+            vec![Some(Left), Some(Front), Some(Right), Some(Back)],
+            vec![None, Some(Down), None, None],
+        ],
+        CubeLayout::VerticalCross => vec![
+            vec![None, Some(Up), None],
+            vec![Some(Left), Some(Front), Some(Right)],
+            vec![None, Some(Down), None],
+            vec![None, Some(Back), None],
+        ],
+        CubeLayout::Grid3x2 => vec![
+            vec![Some(o[0]), Some(o[1]), Some(o[2])],
+            vec![Some(o[3]), Some(o[4]), Some(o[5])],
+        ],
+        CubeLayout::Strip6x1 => vec![o.iter().copied().map(Some).collect()],
+        CubeLayout::Strip1x6 => o.iter().copied().map(|face| vec![Some(face)]).collect(),
+    }
+}
+
+/// The six faces of a cube map in a chosen order.
+pub struct CubeMap {
+    face_size: u32,
+    order: [CubeFace; 6],
+    style: CubemapStyle,
+    faces: Vec<prelude::Mat>,
+}
+
+impl CubeMap {
+    pub(crate) fn from_faces(face_size: u32, order: [CubeFace; 6], style: CubemapStyle, faces: Vec<prelude::Mat>) -> CubeMap {
+        CubeMap { face_size, order, style, faces }
+    }
+
+    /// Wraps six square faces of the same size and type, listed in `order`.
+    pub fn new(faces: Vec<prelude::Mat>, order: FaceOrder) -> Result<CubeMap> {
+        let order = order.faces();
+        if faces.len() != 6 || CubeFace::ALL.iter().any(|face| !order.contains(face)) {
+            return Err(Error::InvalidParameter(format!("a cube map needs six faces in a complete order, got {} in {order:?}", faces.len())));
+        }
+        let (size, typ) = (faces[0].cols(), faces[0].typ());
+        if size == 0 || faces.iter().any(|face| face.cols() != size || face.rows() != size || face.typ() != typ) {
+            return Err(Error::InvalidParameter("cube faces must be non-empty squares of one size and type".to_string()));
+        }
+        Ok(CubeMap::from_faces(size as u32, order, CubemapStyle::Standard, faces))
+    }
+
+    /// Cuts the faces out of an atlas made with [`to_atlas`](Self::to_atlas).
+    pub fn from_atlas(atlas: &prelude::Mat, layout: CubeLayout, order: FaceOrder) -> Result<CubeMap> {
+        let order = order.faces();
+        let cells = layout_cells(layout, order);
+        let (rows, cols) = (cells.len() as i32, cells[0].len() as i32);
+        let size = atlas.cols() / cols;
+        if size == 0 || atlas.cols() != size * cols || atlas.rows() != size * rows {
+            return Err(Error::InvalidParameter(format!(
+                "a {}x{} atlas does not split into {cols}x{rows} square cells",
+                atlas.cols(),
+                atlas.rows()
+            )));
+        }
+        let mut faces = Vec::with_capacity(6);
+        for face in order {
+            let (row, col) = cells
+                .iter()
+                .enumerate()
+                .find_map(|(r, cells)| cells.iter().position(|cell| *cell == Some(face)).map(|c| (r as i32, c as i32)))
+                .ok_or_else(|| Error::InvalidParameter(format!("{face:?} is not in the {layout:?} layout")))?;
+            let cell = prelude::Mat::roi(atlas, opencv::core::Rect::new(col * size, row * size, size, size))?;
+            if face == CubeFace::Back && layout == CubeLayout::VerticalCross {
+                let mut rotated = prelude::Mat::default();
+                opencv::core::rotate(&*cell, &mut rotated, opencv::core::ROTATE_180)?;
+                faces.push(rotated);
+            } else {
+                faces.push(cell.try_clone()?);
+            }
+        }
+        Ok(CubeMap::from_faces(size as u32, order, CubemapStyle::Standard, faces))
+    }
+
+    /// Declares how pixels are distributed over the faces, for cube maps
+    /// built from images.
+    pub fn with_style(mut self, style: CubemapStyle) -> CubeMap {
+        self.style = style;
+        self
+    }
+
+    pub fn style(&self) -> CubemapStyle {
+        self.style
+    }
+
+    pub fn face_size(&self) -> u32 {
+        self.face_size
+    }
+
+    pub fn order(&self) -> [CubeFace; 6] {
+        self.order
+    }
+
+    pub fn face(&self, face: CubeFace) -> &prelude::Mat {
+        let index = self.order.iter().position(|f| *f == face).expect("cube maps hold every face");
+        &self.faces[index]
+    }
+
+    /// Faces in layout order.
+    pub fn faces(&self) -> impl Iterator<Item = (CubeFace, &prelude::Mat)> {
+        self.order.iter().copied().zip(self.faces.iter())
+    }
+
+    pub fn into_faces(self) -> Vec<prelude::Mat> {
+        self.faces
+    }
+
+    /// Combines the faces into a single atlas image; unused cells are black.
+    pub fn to_atlas(&self, layout: CubeLayout) -> Result<prelude::Mat> {
+        let reference = &self.faces[0];
+        let size = self.face_size as i32;
+        let blank = prelude::Mat::new_rows_cols_with_default(size, size, reference.typ(), opencv::core::Scalar::all(0.0))?;
+
+        let mut rows = Vector::<prelude::Mat>::new();
+        for cells in layout_cells(layout, self.order) {
+            let mut row = Vector::<prelude::Mat>::new();
+            for cell in cells {
+                match cell {
+                    Some(CubeFace::Back) if layout == CubeLayout::VerticalCross => {
+                        let mut rotated = prelude::Mat::default();
+                        opencv::core::rotate(self.face(CubeFace::Back), &mut rotated, opencv::core::ROTATE_180)?;
+                        row.push(rotated);
+                    }
+                    Some(face) => row.push(self.face(face).try_clone()?),
+                    None => row.push(blank.try_clone()?),
+                }
+            }
+            let mut joined = prelude::Mat::default();
+            opencv::core::hconcat(&row, &mut joined)?;
+            rows.push(joined);
+        }
+        let mut atlas = prelude::Mat::default();
+        opencv::core::vconcat(&rows, &mut atlas)?;
+        Ok(atlas)
+    }
+}
+
+/// Extra pixels around each face in [`CubeSource`] strips, enough for the
+/// widest interpolation kernel (Lanczos4) to stay on the right face.
+const FACE_PAD: u32 = 4;
+
+/// Direction of face pixel `(u, v)`, which may lie outside the face.
+fn face_ray(rotation: &Matrix3, style: CubemapStyle, size: u32, u: f64, v: f64) -> Vec3 {
+    let size = size as f64;
+    let x = style.to_plane(2.0 * (u + 0.5) / size - 1.0);
+    let y = style.to_plane(2.0 * (v + 0.5) / size - 1.0);
+    rotation::apply(rotation, [x, y, 1.0])
+}
+
+/// A cube map as a source: its faces side by side, each padded by a few
+/// pixels copied from the neighboring faces so interpolation across face
+/// edges is seamless. Built with [`CubeMap::source`].
+pub struct CubeSource {
+    rotations: [Matrix3; 6],
+    style: CubemapStyle,
+    size: u32,
+    pad: u32,
+}
+
+impl CubeSource {
+    fn new(cube: &CubeMap, pad: u32) -> Result<CubeSource> {
+        let mut rotations = [rotation::IDENTITY; 6];
+        for (rotation, face) in rotations.iter_mut().zip(cube.order) {
+            *rotation = face.view(cube.face_size).rotation()?;
+        }
+        Ok(CubeSource {
+            rotations,
+            style: cube.style,
+            size: cube.face_size,
+            pad,
+        })
+    }
+
+    fn tile(&self) -> u32 {
+        self.size + 2 * self.pad
+    }
+}
+
+impl SourceProjection for CubeSource {
+    fn source_size(&self) -> (u32, u32) {
+        (6 * self.tile(), self.tile())
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        // The face whose forward axis is closest to `dir`.
+        let forward = |r: &Matrix3| r[0][2] * dir[0] + r[1][2] * dir[1] + r[2][2] * dir[2];
+        let (index, rotation) = self
+            .rotations
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| forward(a).total_cmp(&forward(b)))?;
+        let [x, y, z] = rotation::apply(&rotation::transpose(rotation), dir);
+        if z <= 0.0 {
+            return None;
+        }
+        let size = self.size as f64;
+        let edge = |t: f64| ((self.style.from_plane(t) + 1.0) * size / 2.0 - 0.5).clamp(0.0, size - 1.0);
+        let offset = self.pad as f64;
+        let u = edge(x / z) + offset + (index as u32 * self.tile()) as f64;
+        let v = edge(y / z) + offset;
+        Some((u as f32, v as f32))
+    }
+}
+
+/// A padded face tile of a [`CubeSource`] strip.
+struct PaddedFace<'a> {
+    source: &'a CubeSource,
+    index: usize,
+}
+
+impl Projection for PaddedFace<'_> {
+    fn output_size(&self) -> (u32, u32) {
+        (self.source.tile(), self.source.tile())
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let pad = self.source.pad as f64;
+        let rotation = &self.source.rotations[self.index];
+        Some(face_ray(rotation, self.source.style, self.source.size, u - pad, v - pad))
+    }
+}
+
+impl CubeMap {
+    /// The padded face strip and its source geometry, for
+    /// [`reproject`](crate::reproject()) and friends.
+    pub fn source(&self) -> Result<(prelude::Mat, CubeSource)> {
+        let faces = self.faces.iter().map(|face| face.try_clone()).collect::<opencv::Result<Vector<_>>>()?;
+        let mut strip = prelude::Mat::default();
+        opencv::core::hconcat(&faces, &mut strip)?;
+
+        let bare = CubeSource::new(self, 0)?;
+        let padded = CubeSource::new(self, FACE_PAD)?;
+        let nearest = RenderOptions {
+            interpolation: Interpolation::Nearest,
+            ..RenderOptions::default()
+        };
+        let tiles = (0..6)
+            .map(|index| source::render(&strip, &bare, &PaddedFace { source: &padded, index }, &nearest))
+            .collect::<Result<Vec<_>>>()?;
+        let mut padded_strip = prelude::Mat::default();
+        opencv::core::hconcat(&Vector::<prelude::Mat>::from_iter(tiles), &mut padded_strip)?;
+        Ok((padded_strip, padded))
+    }
+
+    /// Reassembles a full equirectangular panorama `width` pixels wide.
+    pub fn to_equirectangular(&self, width: u32, options: &RenderOptions) -> Result<Equirectangular> {
+        if width < 2 {
+            return Err(Error::InvalidParameter(format!("panorama width must be at least 2, got {width}")));
+        }
+        let (strip, source) = self.source()?;
+        let frame = EquirectProjection::new(width, width / 2);
+        Equirectangular::from_mat(source::render(&strip, &source, &frame, options)?)
+    }
+}
+
+impl Equirectangular {
+    /// Builds a panorama from six cube faces; see [`CubeMap::to_equirectangular`].
+    pub fn from_cubemap(cube: &CubeMap, width: u32) -> Result<Equirectangular> {
+        cube.to_equirectangular(width, &RenderOptions::default())
+    }
+
+    /// Renders the six 90 degree cube faces in OpenGL order.
+    pub fn to_cubemap(&self, face_size: u32) -> Result<CubeMap> {
+        self.to_cubemap_with(&CubemapParams::new(face_size))
+    }
+
+    pub fn to_cubemap_with(&self, params: &CubemapParams) -> Result<CubeMap> {
+        params.validate()?;
+        let faces = match params.style {
+            CubemapStyle::Standard => self.get_perspectives(&params.views())?,
+            CubemapStyle::EquiAngular => params
+                .order
+                .faces()
+                .iter()
+                .map(|&face| self.render_projection(&FaceView::new(face, params.style, params.face_size)?, &params.options))
+                .collect::<Result<Vec<_>>>()?,
+        };
+        Ok(CubeMap::from_faces(params.face_size, params.order.faces(), params.style, faces))
+    }
+}
@@ -0,0 +1,306 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::{Angle, Deg};
+use crate::backproject::{self, EquirectLayer};
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+use crate::source::SourceProjection;
+
+/// A central cylindrical view of a horizontal band of the sphere: columns
+/// are evenly spaced in longitude and rows follow `tan(latitude)`, so
+/// verticals stay straight. Geometry is undistorted when
+/// `width / height = fov_x / (2 tan(fov_y / 2))` with `fov_x` in radians.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CylindricalParams {
+    pub(crate) fov_x: f64,
+    pub(crate) fov_y: f64,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for CylindricalParams {
+    fn default() -> Self {
+        CylindricalParams::new()
+    }
+}
+
+impl CylindricalParams {
+    pub fn new() -> CylindricalParams {
+        CylindricalParams {
+            fov_x: 360.0,
+            fov_y: 90.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            width: 4096,
+            height: 1304,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Horizontal extent in longitude, up to 360 degrees.
+    pub fn fov_x(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_x = fov.into().degrees();
+        self
+    }
+
+    /// Vertical extent, below 180 degrees.
+    pub fn fov_y(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_y = fov.into().degrees();
+        self
+    }
+
+    /// Longitude at the center column.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    /// Tilts the cylinder axis; level horizons need 0.
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    pub fn output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fov_x > 0.0 && self.fov_x <= 360.0) {
+            return Err(Error::InvalidParameter(format!("horizontal fov must be in (0, 360] degrees, got {}", self.fov_x)));
+        }
+        if !(self.fov_y > 0.0 && self.fov_y < 180.0) {
+            return Err(Error::InvalidParameter(format!("vertical fov must be in (0, 180) degrees, got {}", self.fov_y)));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<Cylindrical> {
+        self.validate()?;
+        Ok(Cylindrical {
+            rotation: rotation::euler_to_matrix(self.yaw, self.pitch, self.roll),
+            width: self.width,
+            height: self.height,
+            lon_range: self.fov_x.to_radians(),
+            half_extent: (0.5 * self.fov_y.to_radians()).tan(),
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+}
+
+/// A cylindrical view resolved from [`CylindricalParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cylindrical {
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+    lon_range: f64,
+    half_extent: f64,
+}
+
+impl Projection for Cylindrical {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let lon = ((u + 0.5) / self.width as f64 - 0.5) * self.lon_range;
+        let h = (2.0 * (v + 0.5) / self.height as f64 - 1.0) * self.half_extent;
+        Some(rotation::apply(&self.rotation, [lon.sin(), h, lon.cos()]))
+    }
+}
+
+/// Vertical mapping of a wide panorama from a stitching tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StripModel {
+    /// Rows follow `tan(latitude)`, as rendered by [`CylindricalParams`].
+    #[default]
+    Cylindrical,
+    /// Rows are evenly spaced in latitude: a partial equirectangular image.
+    Flat,
+}
+
+/// Declared geometry of a cylindrical or flat panorama, for importing it
+/// into equirectangular form.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PanoramaStrip {
+    pub(crate) model: StripModel,
+    pub(crate) fov_x: f64,
+    pub(crate) fov_y: Option<f64>,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+}
+
+impl Default for PanoramaStrip {
+    fn default() -> Self {
+        PanoramaStrip::new(StripModel::default())
+    }
+}
+
+impl PanoramaStrip {
+    pub fn new(model: StripModel) -> PanoramaStrip {
+        PanoramaStrip {
+            model,
+            fov_x: 360.0,
+            fov_y: None,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+        }
+    }
+
+    /// Horizontal extent, up to 360 degrees.
+    pub fn fov_x(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_x = fov.into().degrees();
+        self
+    }
+
+    /// Vertical extent; follows from square pixels when not set.
+    pub fn fov_y(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_y = Some(fov.into().degrees());
+        self
+    }
+
+    /// Longitude at the center column.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fov_x > 0.0 && self.fov_x <= 360.0) {
+            return Err(Error::InvalidParameter(format!("horizontal fov must be in (0, 360] degrees, got {}", self.fov_x)));
+        }
+        if let Some(fov_y) = self.fov_y {
+            if !(fov_y > 0.0 && fov_y < 180.0 || self.model == StripModel::Flat && fov_y == 180.0) {
+                return Err(Error::InvalidParameter(format!("vertical fov {fov_y} degrees is out of range")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Source geometry for a `width`x`height` image.
+    pub fn source(&self, width: u32, height: u32) -> Result<StripSource> {
+        self.validate()?;
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidParameter("panorama size must be non-zero".to_string()));
+        }
+        let lon_range = self.fov_x.to_radians();
+        let square = lon_range * height as f64 / (2.0 * width as f64);
+        let half_extent = match (self.model, self.fov_y) {
+            (StripModel::Cylindrical, Some(fov_y)) => (0.5 * fov_y.to_radians()).tan(),
+            (StripModel::Flat, Some(fov_y)) => 0.5 * fov_y.to_radians(),
+            (_, None) => square,
+        };
+        if self.model == StripModel::Flat && half_extent > std::f64::consts::FRAC_PI_2 + 1e-9 {
+            return Err(Error::InvalidParameter(format!("a {width}x{height} flat panorama would span more than 180 degrees vertically")));
+        }
+        Ok(StripSource {
+            model: self.model,
+            world_to_strip: rotation::transpose(&rotation::euler_to_matrix(self.yaw, self.pitch, self.roll)),
+            width,
+            height,
+            lon_range,
+            half_extent,
+        })
+    }
+
+    /// Reprojects an image onto an equirectangular canvas of `canvas_size`
+    /// `(width, height)`; directions outside it are masked out.
+    pub fn to_equirectangular(&self, image: &prelude::Mat, canvas_size: (u32, u32), options: &RenderOptions) -> Result<EquirectLayer> {
+        let source = self.source(image.cols() as u32, image.rows() as u32)?;
+        backproject::to_equirectangular(image, &source, canvas_size, options)
+    }
+}
+
+/// A cylindrical or flat panorama resolved from [`PanoramaStrip`], as a
+/// source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StripSource {
+    model: StripModel,
+    world_to_strip: Matrix3,
+    width: u32,
+    height: u32,
+    lon_range: f64,
+    half_extent: f64,
+}
+
+impl SourceProjection for StripSource {
+    fn source_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        let [x, y, z] = rotation::apply(&self.world_to_strip, dir);
+        let lon = x.atan2(z);
+        let horizontal = (x * x + z * z).sqrt();
+        let h = match self.model {
+            StripModel::Cylindrical => y / horizontal,
+            StripModel::Flat => y.atan2(horizontal),
+        };
+        let (width, height) = (self.width as f64, self.height as f64);
+        let u = (lon / self.lon_range + 0.5) * width - 0.5;
+        let v = (h / self.half_extent + 1.0) * height / 2.0 - 0.5;
+        let inside = (-0.5..=width - 0.5).contains(&u) && (-0.5..=height - 0.5).contains(&v);
+        inside.then_some((u as f32, v as f32))
+    }
+
+    fn wraps_horizontally(&self) -> bool {
+        self.lon_range >= 2.0 * std::f64::consts::PI
+    }
+}
+
+impl Equirectangular {
+    /// Full 360 degree cylindrical strip of `output_size` (`(width, height)`)
+    /// around the horizon, `vertical_fov` in degrees.
+    pub fn to_cylindrical(&self, vertical_fov: f64, output_size: (u32, u32)) -> Result<prelude::Mat> {
+        let (width, height) = output_size;
+        CylindricalParams::new()
+            .fov_y(Deg(vertical_fov))
+            .output_size(width, height)
+            .project(self)
+    }
+}
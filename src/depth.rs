@@ -0,0 +1,148 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::coords;
+use crate::distortion::Distortion;
+use crate::error::{Error, Result};
+use crate::maps;
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+
+/// Fixed-point iterations spent finding the scene point behind each pixel.
+const ITERATIONS: usize = 8;
+
+/// Distance from the panorama center to the scene along every direction,
+/// laid out like the panorama it belongs to (which may have a different
+/// resolution). Non-positive and non-finite values mean "infinitely far",
+/// e.g. sky.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthMap {
+    depth: ndarray::Array2<f32>,
+}
+
+impl DepthMap {
+    /// Reads a single-channel depth image of any depth type, in scene units.
+    pub fn new(depth: &prelude::Mat) -> Result<DepthMap> {
+        if depth.channels() != 1 {
+            return Err(Error::InvalidParameter(format!("depth maps have one channel, got {}", depth.channels())));
+        }
+        let mut depth_f = prelude::Mat::default();
+        depth.convert_to(&mut depth_f, opencv::core::CV_32F, 1.0, 0.0)?;
+        DepthMap::from_array(maps::to_array(&depth_f)?)
+    }
+
+    pub fn from_array(depth: ndarray::Array2<f32>) -> Result<DepthMap> {
+        let (rows, cols) = depth.dim();
+        if rows < 2 || cols < 2 {
+            return Err(Error::InvalidParameter(format!("depth map must be at least 2x2, got {cols}x{rows}")));
+        }
+        Ok(DepthMap { depth })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.depth.ncols() as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.depth.nrows() as u32
+    }
+
+    /// Bilinearly interpolated distance along `dir`, infinite where the map
+    /// has no valid depth.
+    pub fn distance(&self, dir: Vec3) -> f64 {
+        let (lon, lat) = coords::ray_to_lonlat(dir);
+        let (x, y) = coords::lonlat_to_equirect(lon, lat, self.width(), self.height());
+        let (last_x, last_y) = (self.width() as usize - 1, self.height() as usize - 1);
+        let (x, y) = (x.clamp(0.0, last_x as f64), y.clamp(0.0, last_y as f64));
+        let (x0, y0) = ((x.floor() as usize).min(last_x - 1), (y.floor() as usize).min(last_y - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let mut sum = 0.0;
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let d = self.depth[[y0 + dy, x0 + dx]] as f64;
+                if wx * wy > 0.0 {
+                    if !(d > 0.0 && d.is_finite()) {
+                        return f64::INFINITY;
+                    }
+                    sum += wx * wy * d;
+                }
+            }
+        }
+        sum
+    }
+
+    /// The view `params` seen from its [`translation`](PerspectiveParams::translation)
+    /// instead of the panorama center.
+    pub fn view(&self, params: &PerspectiveParams) -> Result<ParallaxView<'_>> {
+        params.validate()?;
+        Ok(ParallaxView {
+            depth: self,
+            k: params.intrinsics(),
+            distortion: params.distortion_coefficients(),
+            rotation: params.rotation()?,
+            translation: params.translation_vector(),
+            width: params.width,
+            height: params.height,
+        })
+    }
+}
+
+/// A pinhole camera displaced from the panorama center, resolved from
+/// [`DepthMap::view`]. Each pixel samples the panorama where the scene
+/// point it sees was recorded, which gives correct parallax for small
+/// translations; regions the panorama never saw (disocclusions) are
+/// stretched from their surroundings.
+#[derive(Debug, Clone)]
+pub struct ParallaxView<'a> {
+    depth: &'a DepthMap,
+    k: Matrix3,
+    distortion: Option<Distortion>,
+    rotation: Matrix3,
+    translation: [f64; 3],
+    width: u32,
+    height: u32,
+}
+
+impl Projection for ParallaxView<'_> {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let ray = rotation::apply(&self.rotation, coords::unproject_lens(&self.k, self.distortion.as_ref(), u, v));
+        let norm = (ray[0] * ray[0] + ray[1] * ray[1] + ray[2] * ray[2]).sqrt();
+        let ray = ray.map(|c| c / norm);
+        let t = self.translation;
+        let along = t[0] * ray[0] + t[1] * ray[1] + t[2] * ray[2];
+        let offset = t[0] * t[0] + t[1] * t[1] + t[2] * t[2];
+
+        // Find the distance s along the ray whose point t + s·ray lies at
+        // the recorded depth in its own direction from the center.
+        let mut dir = ray;
+        for _ in 0..ITERATIONS {
+            let distance = self.depth.distance(dir);
+            let discriminant = along * along - offset + distance * distance;
+            if !distance.is_finite() || discriminant < 0.0 {
+                break;
+            }
+            let s = -along + discriminant.sqrt();
+            let next: [f64; 3] = std::array::from_fn(|i| t[i] + s * ray[i]);
+            let moved = (0..3).map(|i| (next[i] / distance - dir[i]).abs()).fold(0.0, f64::max);
+            dir = next.map(|c| c / distance);
+            if moved < 1e-7 {
+                break;
+            }
+        }
+        Some(dir)
+    }
+}
+
+impl Equirectangular {
+    /// Renders `params` from its translated position using `depth`, which
+    /// must be aligned with this panorama.
+    pub fn render_translated(&self, params: &PerspectiveParams, depth: &DepthMap) -> Result<prelude::Mat> {
+        self.render_projection(&depth.view(params)?, &params.render_options())
+    }
+}
@@ -0,0 +1,270 @@
+//! Near-equal-area discretizations of the sphere: the 20 faces of an
+//! icosahedron and HEALPix pixels.
+
+use std::f64::consts::PI;
+
+use opencv::prelude;
+
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::sampling::RenderOptions;
+
+/// Number of faces of the icosahedron.
+pub const ICOSAHEDRON_FACES: usize = 20;
+
+fn icosahedron_vertices() -> [Vec3; 12] {
+    let phi = (1.0 + 5f64.sqrt()) / 2.0;
+    let mut vertices = [[0.0; 3]; 12];
+    let mut i = 0;
+    for a in [-1.0, 1.0] {
+        for b in [-phi, phi] {
+            vertices[i] = [0.0, a, b];
+            vertices[i + 1] = [a, b, 0.0];
+            vertices[i + 2] = [b, 0.0, a];
+            i += 3;
+        }
+    }
+    vertices
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Corners of every face, counter-clockwise seen from outside, in a fixed
+/// order.
+fn icosahedron_faces() -> Vec<[Vec3; 3]> {
+    let vertices = icosahedron_vertices();
+    let edge = |a: Vec3, b: Vec3| (dot(sub(a, b), sub(a, b)) - 4.0).abs() < 1e-9;
+    let mut faces = Vec::with_capacity(ICOSAHEDRON_FACES);
+    for i in 0..12 {
+        for j in i + 1..12 {
+            for k in j + 1..12 {
+                let (a, b, c) = (vertices[i], vertices[j], vertices[k]);
+                if !(edge(a, b) && edge(b, c) && edge(c, a)) {
+                    continue;
+                }
+                let (u, v) = (sub(b, a), sub(c, a));
+                let normal = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+                let center = [a[0] + b[0] + c[0], a[1] + b[1] + c[1], a[2] + b[2] + c[2]];
+                faces.push(if dot(normal, center) > 0.0 { [a, b, c] } else { [a, c, b] });
+            }
+        }
+    }
+    faces
+}
+
+/// One face of the icosahedron, gnomonically projected onto its plane and
+/// drawn as an upright equilateral triangle; pixels outside the triangle
+/// are filled with the border color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcosahedronFace {
+    corners: [Vec3; 3],
+    width: u32,
+    height: u32,
+}
+
+impl IcosahedronFace {
+    /// Face `index` (below [`ICOSAHEDRON_FACES`]) rendered `width` pixels
+    /// wide.
+    pub fn new(index: usize, width: u32) -> Result<IcosahedronFace> {
+        if width == 0 {
+            return Err(Error::InvalidParameter("face width must be non-zero".to_string()));
+        }
+        let faces = icosahedron_faces();
+        let corners = *faces
+            .get(index)
+            .ok_or_else(|| Error::InvalidParameter(format!("icosahedron face {index} does not exist")))?;
+        let height = ((width as f64 * 3f64.sqrt() / 2.0).round() as u32).max(1);
+        Ok(IcosahedronFace { corners, width, height })
+    }
+}
+
+impl Projection for IcosahedronFace {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        // The corners run clockwise seen from the sphere center, so the top
+        // corner `a` is followed by `b` at the bottom right and `c` at the
+        // bottom left. `s` grows toward the bottom left, `t` toward the
+        // bottom right.
+        let x = (u + 0.5) / self.width as f64 - 0.5;
+        let y = (v + 0.5) / self.height as f64;
+        let (s, t) = ((y - 2.0 * x) / 2.0, (y + 2.0 * x) / 2.0);
+        if s < 0.0 || t < 0.0 || s + t > 1.0 {
+            return None;
+        }
+        let [a, b, c] = self.corners;
+        Some(std::array::from_fn(|i| a[i] + s * (c[i] - a[i]) + t * (b[i] - a[i])))
+    }
+}
+
+/// HEALPix pixels of resolution `nside` in RING order, laid out row-major
+/// in a `4 nside` by `3 nside` image so pixel `p` sits at
+/// `(p % (4 nside), p / (4 nside))`. Every pixel covers the same solid
+/// angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Healpix {
+    nside: u32,
+}
+
+impl Healpix {
+    pub fn new(nside: u32) -> Result<Healpix> {
+        if nside == 0 || nside > 8192 {
+            return Err(Error::InvalidParameter(format!("HEALPix nside must be in 1..=8192, got {nside}")));
+        }
+        Ok(Healpix { nside })
+    }
+
+    pub fn pixel_count(&self) -> u64 {
+        12 * self.nside as u64 * self.nside as u64
+    }
+
+    /// Center `(lon, lat)` in degrees of RING pixel `p`.
+    pub fn pixel_center(&self, p: u64) -> (f64, f64) {
+        let n = self.nside as f64;
+        let npix = self.pixel_count();
+        let ncap = 2 * self.nside as u64 * (self.nside as u64 - 1);
+        let (z, phi) = if p < ncap {
+            let ring = ((1.0 + (1.0 + 2.0 * p as f64).sqrt()) / 2.0).floor();
+            let j = p as f64 + 1.0 - 2.0 * ring * (ring - 1.0);
+            (1.0 - ring * ring / (3.0 * n * n), (j - 0.5) * PI / (2.0 * ring))
+        } else if p < npix - ncap {
+            let q = p - ncap;
+            let ring = (q / (4 * self.nside as u64)) as f64 + n;
+            let j = (q % (4 * self.nside as u64)) as f64 + 1.0;
+            // Rings with `ring + nside` odd start on the prime meridian.
+            let fodd = if (ring + n) as u64 % 2 == 1 { 1.0 } else { 0.5 };
+            (4.0 / 3.0 - 2.0 * ring / (3.0 * n), (j - fodd) * PI / (2.0 * n))
+        } else {
+            let q = (npix - p) as f64;
+            let ring = ((1.0 + (2.0 * q - 1.0).sqrt()) / 2.0).floor();
+            let j = 4.0 * ring + 1.0 - (q - 2.0 * ring * (ring - 1.0));
+            (-1.0 + ring * ring / (3.0 * n * n), (j - 0.5) * PI / (2.0 * ring))
+        };
+        let lon = (phi.to_degrees() + 180.0).rem_euclid(360.0) - 180.0;
+        (lon, z.clamp(-1.0, 1.0).asin().to_degrees())
+    }
+}
+
+impl Projection for Healpix {
+    fn output_size(&self) -> (u32, u32) {
+        (4 * self.nside, 3 * self.nside)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let (u, v) = (u.round(), v.round());
+        if u < 0.0 || v < 0.0 || u >= 4.0 * self.nside as f64 || v >= 3.0 * self.nside as f64 {
+            return None;
+        }
+        let (lon, lat) = self.pixel_center(v as u64 * 4 * self.nside as u64 + u as u64);
+        Some(coords::lonlat_to_ray(lon, lat))
+    }
+}
+
+impl Equirectangular {
+    /// All 20 icosahedron faces, each `face_width` pixels wide.
+    pub fn to_icosahedron(&self, face_width: u32) -> Result<Vec<prelude::Mat>> {
+        (0..ICOSAHEDRON_FACES)
+            .map(|index| self.render_projection(&IcosahedronFace::new(index, face_width)?, &RenderOptions::default()))
+            .collect()
+    }
+
+    /// HEALPix samples of resolution `nside`; see [`Healpix`] for the layout.
+    pub fn to_healpix(&self, nside: u32) -> Result<prelude::Mat> {
+        self.render_projection(&Healpix::new(nside)?, &RenderOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Deg;
+    use crate::params::PerspectiveParams;
+    use crate::projection::PerspectiveCamera;
+
+    fn cross(a: Vec3, b: Vec3) -> Vec3 {
+        [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+    }
+
+    /// Whether moving right then down in the image turns clockwise around
+    /// the view direction, as seen from the sphere center.
+    fn handedness(projection: &impl Projection, u: f64, v: f64) -> f64 {
+        let center = projection.direction_for_pixel(u, v).unwrap();
+        let right = sub(projection.direction_for_pixel(u + 1.0, v).unwrap(), center);
+        let down = sub(projection.direction_for_pixel(u, v + 1.0).unwrap(), center);
+        dot(cross(right, down), center)
+    }
+
+    #[test]
+    fn icosahedron_faces_are_not_mirrored() {
+        for index in 0..ICOSAHEDRON_FACES {
+            let face = IcosahedronFace::new(index, 64).unwrap();
+            let center = face.direction_for_pixel(31.5, 36.0).unwrap();
+            let (lon, lat) = coords::ray_to_lonlat(center);
+            let params = PerspectiveParams::new().yaw(Deg(lon)).pitch(Deg(lat)).output_size(64, 64);
+            let camera = PerspectiveCamera::new(&params).unwrap();
+            assert!(handedness(&camera, 32.0, 32.0) > 0.0);
+            assert!(handedness(&face, 31.5, 36.0) > 0.0, "face {index} is mirrored");
+        }
+    }
+
+    fn assert_center(healpix: &Healpix, p: u64, lon: f64, z: f64) {
+        let (got_lon, got_lat) = healpix.pixel_center(p);
+        let lon_error = (got_lon - lon + 180.0).rem_euclid(360.0) - 180.0;
+        assert!(lon_error.abs() < 1e-9, "pixel {p}: lon {got_lon}, expected {lon}");
+        assert!((got_lat - z.asin().to_degrees()).abs() < 1e-9, "pixel {p}: lat {got_lat}");
+    }
+
+    #[test]
+    fn healpix_centers_match_pix2ang() {
+        // healpy.pix2ang(1, p) for every pixel, as (phi, cos(theta)).
+        let nside1 = Healpix::new(1).unwrap();
+        for (p, (lon, z)) in [
+            (45.0, 2.0 / 3.0),
+            (135.0, 2.0 / 3.0),
+            (225.0, 2.0 / 3.0),
+            (315.0, 2.0 / 3.0),
+            (0.0, 0.0),
+            (90.0, 0.0),
+            (180.0, 0.0),
+            (270.0, 0.0),
+            (45.0, -2.0 / 3.0),
+            (135.0, -2.0 / 3.0),
+            (225.0, -2.0 / 3.0),
+            (315.0, -2.0 / 3.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_center(&nside1, p as u64, lon, z);
+        }
+
+        // healpy.pix2ang(2, p) in the north cap, every equatorial ring
+        // parity and the south cap.
+        let nside2 = Healpix::new(2).unwrap();
+        for (p, lon, z) in [
+            (0, 45.0, 11.0 / 12.0),
+            (3, 315.0, 11.0 / 12.0),
+            (4, 22.5, 2.0 / 3.0),
+            (11, 337.5, 2.0 / 3.0),
+            (12, 0.0, 1.0 / 3.0),
+            (19, 315.0, 1.0 / 3.0),
+            (20, 22.5, 0.0),
+            (28, 0.0, -1.0 / 3.0),
+            (36, 22.5, -2.0 / 3.0),
+            (44, 45.0, -11.0 / 12.0),
+            (47, 315.0, -11.0 / 12.0),
+        ] {
+            assert_center(&nside2, p, lon, z);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::error::{Error, Result};
+
+/// Iterations used to invert the distortion; plenty for realistic lenses.
+const UNDISTORT_ITERATIONS: usize = 20;
+
+/// Brown–Conrady lens distortion with OpenCV's coefficient names: radial
+/// `k1`, `k2`, `k3` and tangential `p1`, `p2`, acting on normalized image
+/// coordinates `(x / z, y / z)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Distortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub p1: f64,
+    pub p2: f64,
+    pub k3: f64,
+}
+
+impl Distortion {
+    /// Coefficients in OpenCV's `distCoeffs` order.
+    pub fn new(k1: f64, k2: f64, p1: f64, p2: f64, k3: f64) -> Distortion {
+        Distortion { k1, k2, p1, p2, k3 }
+    }
+
+    /// Radial-only distortion.
+    pub fn radial(k1: f64, k2: f64, k3: f64) -> Distortion {
+        Distortion::new(k1, k2, 0.0, 0.0, k3)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if [self.k1, self.k2, self.p1, self.p2, self.k3].iter().any(|c| !c.is_finite()) {
+            return Err(Error::InvalidParameter(format!("{self:?} has non-finite coefficients")));
+        }
+        Ok(())
+    }
+
+    /// Where the lens images the ideal normalized point `(x, y)`.
+    pub fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        (
+            x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+            y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y,
+        )
+    }
+
+    /// Ideal normalized point imaged at `(xd, yd)`, by fixed-point iteration
+    /// like `cv::undistortPoints`.
+    pub fn undistort(&self, xd: f64, yd: f64) -> (f64, f64) {
+        let (mut x, mut y) = (xd, yd);
+        for _ in 0..UNDISTORT_ITERATIONS {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+            let dx = 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x);
+            let dy = self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y;
+            (x, y) = ((xd - dx) / radial, (yd - dy) / radial);
+        }
+        (x, y)
+    }
+}
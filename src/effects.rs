@@ -0,0 +1,195 @@
+use opencv::core::Vector;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::error::{Error, Result};
+use crate::interop;
+use crate::maps;
+use crate::output::{self, OutputFormat};
+use crate::params::PerspectiveParams;
+use crate::pool;
+use crate::sampling::{Border, RenderOptions};
+
+/// Capture artifacts added to rendered views so they look like real
+/// photos: vignetting and lateral chromatic aberration. Everything is off by
+/// default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CaptureEffects {
+    pub(crate) vignetting: f64,
+    pub(crate) falloff: f64,
+    pub(crate) chromatic_aberration: f64,
+}
+
+impl Default for CaptureEffects {
+    fn default() -> Self {
+        CaptureEffects::new()
+    }
+}
+
+impl CaptureEffects {
+    pub fn new() -> CaptureEffects {
+        CaptureEffects {
+            vignetting: 0.0,
+            falloff: 2.0,
+            chromatic_aberration: 0.0,
+        }
+    }
+
+    /// Brightness lost in the image corners, from 0 (none) to 1 (black).
+    pub fn vignetting(mut self, strength: f64) -> Self {
+        self.vignetting = strength;
+        self
+    }
+
+    /// Exponent of the vignetting falloff over the distance from the
+    /// principal point; larger values keep more of the image bright.
+    pub fn vignetting_falloff(mut self, falloff: f64) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// Lateral chromatic aberration: how many pixels red is pushed outward
+    /// and blue inward at the image corners, relative to green.
+    pub fn chromatic_aberration(mut self, shift: f64) -> Self {
+        self.chromatic_aberration = shift;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.vignetting) {
+            return Err(Error::InvalidParameter(format!("vignetting must be in [0, 1], got {}", self.vignetting)));
+        }
+        if !(self.falloff > 0.0 && self.falloff.is_finite()) {
+            return Err(Error::InvalidParameter(format!("vignetting falloff must be positive, got {}", self.falloff)));
+        }
+        if !self.chromatic_aberration.is_finite() {
+            return Err(Error::InvalidParameter(format!("chromatic aberration must be finite, got {}", self.chromatic_aberration)));
+        }
+        Ok(())
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.vignetting == 0.0 && self.chromatic_aberration == 0.0
+    }
+
+    /// Applies the effects to a BGR or BGRA image (gray images only get
+    /// vignetting) around the principal point `center`. Alpha is left alone.
+    pub fn apply(&self, image: &prelude::Mat, center: (f64, f64)) -> Result<prelude::Mat> {
+        let mut out = prelude::Mat::default();
+        self.apply_into(image, center, &mut out)?;
+        Ok(out)
+    }
+
+    /// [`apply`](Self::apply) into `dst`, reusing its allocation when it
+    /// already has the image's size and type. `dst` must not be `image`.
+    pub(crate) fn apply_into(&self, image: &prelude::Mat, center: (f64, f64), dst: &mut prelude::Mat) -> Result<()> {
+        self.validate()?;
+        let (rows, cols, channels) = (image.rows(), image.cols(), image.channels());
+        let corner = [(0.0, 0.0), (cols as f64 - 1.0, 0.0), (0.0, rows as f64 - 1.0), (cols as f64 - 1.0, rows as f64 - 1.0)]
+            .iter()
+            .map(|&(x, y)| (x - center.0).hypot(y - center.1))
+            .fold(0.0, f64::max)
+            .max(1.0);
+
+        let mut planes = Vector::<prelude::Mat>::new();
+        opencv::core::split(image, &mut planes)?;
+        if channels >= 3 && self.chromatic_aberration != 0.0 {
+            let shift = self.chromatic_aberration / corner;
+            // Red is plane 2 and blue plane 0 in OpenCV's BGR order.
+            for (plane, scale) in [(2, 1.0 + shift), (0, 1.0 - shift)] {
+                let magnified = magnify(&planes.get(plane)?, center, scale)?;
+                planes.set(plane, magnified)?;
+            }
+        }
+        if self.vignetting > 0.0 {
+            let gain = pool::from_shape_fn((rows as usize, cols as usize), |(v, u)| {
+                let rho = (u as f64 - center.0).hypot(v as f64 - center.1) / corner;
+                (1.0 - self.vignetting * rho.powf(self.falloff)) as f32
+            })?;
+            let gain_mat = interop::array_mat(&gain)?;
+            let color_planes = if channels == 2 || channels == 4 { channels - 1 } else { channels };
+            for plane in 0..color_planes as usize {
+                let mut darkened = prelude::Mat::default();
+                opencv::core::multiply(&planes.get(plane)?, &gain_mat, &mut darkened, 1.0, image.depth())?;
+                planes.set(plane, darkened)?;
+            }
+            drop(gain_mat);
+            pool::recycle_array(gain);
+        }
+        opencv::core::merge(&planes, dst)?;
+        Ok(())
+    }
+}
+
+/// Renders a view through `maps` like [`maps::RemapMaps::render_into`],
+/// adding the view's capture effects before the output format conversion.
+pub(crate) fn render_into(
+    maps: &maps::RemapMaps,
+    src: &prelude::Mat,
+    params: &PerspectiveParams,
+    options: &RenderOptions,
+    dst: &mut prelude::Mat,
+) -> Result<()> {
+    let effects = params.capture_effects();
+    if effects.is_identity() {
+        return maps.render_into(src, options, dst);
+    }
+    let k = params.intrinsics();
+    let center = (k[0][2], k[1][2]);
+    let mut view = pool::take_output(maps.map_x.rows(), maps.map_x.cols(), src.typ(), options.border)?;
+    if options.border == Border::Transparent && options.output_format == OutputFormat::Native && !dst.empty() {
+        // Uncovered pixels keep what `dst` held, as without effects.
+        dst.copy_to(&mut view)?;
+    }
+    maps.remap_into(src, options, &mut view)?;
+    if options.output_format == OutputFormat::Native {
+        effects.apply_into(&view, center, dst)?;
+    } else {
+        output::convert_into(&effects.apply(&view, center)?, options.output_format, dst)?;
+    }
+    pool::recycle(view);
+    Ok(())
+}
+
+/// Scales one plane by `scale` about `center`, replicating the edges.
+fn magnify(plane: &prelude::Mat, center: (f64, f64), scale: f64) -> Result<prelude::Mat> {
+    let shape = (plane.rows() as usize, plane.cols() as usize);
+    let map_x = pool::from_shape_fn(shape, |(_, u)| (center.0 + (u as f64 - center.0) / scale) as f32)?;
+    let map_y = pool::from_shape_fn(shape, |(v, _)| (center.1 + (v as f64 - center.1) / scale) as f32)?;
+    let mut out = prelude::Mat::default();
+    opencv::imgproc::remap(
+        plane,
+        &mut out,
+        &interop::array_mat(&map_x)?,
+        &interop::array_mat(&map_y)?,
+        opencv::imgproc::INTER_LINEAR,
+        opencv::core::BORDER_REPLICATE,
+        opencv::core::Scalar::all(0.0),
+    )?;
+    pool::recycle_array(map_x);
+    pool::recycle_array(map_y);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projector::Projector;
+
+    #[test]
+    fn effects_render_into_the_callers_buffer() {
+        let params = PerspectiveParams::new().output_size(32, 24).effects(CaptureEffects::new().vignetting(0.5));
+        let projector = Projector::new(&params, (128, 64)).unwrap();
+        let src = prelude::Mat::new_rows_cols_with_default(64, 128, opencv::core::CV_8UC3, opencv::core::Scalar::all(200.0)).unwrap();
+        let mut dst = prelude::Mat::default();
+        projector.apply_into(&src, &mut dst).unwrap();
+        let buffer = dst.data();
+        projector.apply_into(&src, &mut dst).unwrap();
+        assert_eq!(dst.data(), buffer);
+        // Darker toward the corners.
+        let (corner, center) = (dst.at_2d::<opencv::core::Vec3b>(0, 0).unwrap()[0], dst.at_2d::<opencv::core::Vec3b>(12, 16).unwrap()[0]);
+        assert!(corner < center);
+    }
+}
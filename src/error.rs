@@ -0,0 +1,59 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Decode(String),
+    InvalidParameter(String),
+    /// The panorama does not have the 2:1 aspect ratio of a full
+    /// equirectangular image.
+    AspectRatio { width: u32, height: u32 },
+    OpenCv(opencv::Error),
+    /// The GPU backend could not be set up or failed to render.
+    Gpu(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Decode(msg) => write!(f, "could not decode image: {msg}"),
+            Error::InvalidParameter(msg) => write!(f, "invalid parameter: {msg}"),
+            Error::AspectRatio { width, height } => {
+                write!(f, "panorama is {width}x{height}, expected a 2:1 equirectangular image")
+            }
+            Error::OpenCv(e) => write!(f, "opencv error: {e}"),
+            Error::Gpu(msg) => write!(f, "gpu error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::OpenCv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<opencv::Error> for Error {
+    fn from(e: opencv::Error) -> Self {
+        Error::OpenCv(e)
+    }
+}
+
+impl From<ndarray::ShapeError> for Error {
+    fn from(e: ndarray::ShapeError) -> Self {
+        Error::InvalidParameter(format!("array shape mismatch: {e}"))
+    }
+}
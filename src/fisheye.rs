@@ -0,0 +1,386 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::{Angle, Deg};
+use crate::backproject::{self, EquirectLayer};
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+use crate::source::SourceProjection;
+
+/// Mapping from the angle θ off the optical axis to the image radius r.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyeModel {
+    /// `r = f·θ`
+    #[default]
+    Equidistant,
+    /// `r = 2f·sin(θ/2)`, equal-area.
+    Equisolid,
+    /// `r = f·sin(θ)`, limited to 180 degrees.
+    Orthographic,
+    /// `r = 2f·tan(θ/2)`, conformal, below 360 degrees.
+    Stereographic,
+}
+
+impl FisheyeModel {
+    /// `r / f` for an off-axis angle `theta`.
+    pub fn radius(self, theta: f64) -> f64 {
+        match self {
+            FisheyeModel::Equidistant => theta,
+            FisheyeModel::Equisolid => 2.0 * (theta / 2.0).sin(),
+            FisheyeModel::Orthographic => theta.sin(),
+            FisheyeModel::Stereographic => 2.0 * (theta / 2.0).tan(),
+        }
+    }
+
+    /// Off-axis angle for a normalized radius `r / f`, or `None` past the
+    /// model's range.
+    pub fn theta(self, radius: f64) -> Option<f64> {
+        match self {
+            FisheyeModel::Equidistant => Some(radius),
+            FisheyeModel::Equisolid => (radius <= 2.0).then(|| 2.0 * (radius / 2.0).asin()),
+            FisheyeModel::Orthographic => (radius <= 1.0).then(|| radius.asin()),
+            FisheyeModel::Stereographic => Some(2.0 * (radius / 2.0).atan()),
+        }
+    }
+
+    /// Largest field of view (degrees) the model can represent.
+    pub fn max_fov(self) -> f64 {
+        match self {
+            FisheyeModel::Orthographic => 180.0,
+            _ => 360.0,
+        }
+    }
+}
+
+/// A circular fisheye view. The image circle touches the edges of the
+/// square output; pixels outside it are filled with the border color.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FisheyeParams {
+    pub(crate) model: FisheyeModel,
+    pub(crate) fov: f64,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    pub(crate) size: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for FisheyeParams {
+    fn default() -> Self {
+        FisheyeParams::new()
+    }
+}
+
+impl FisheyeParams {
+    pub fn new() -> FisheyeParams {
+        FisheyeParams {
+            model: FisheyeModel::default(),
+            fov: 180.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            size: 1024,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn model(mut self, model: FisheyeModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Field of view across the image circle, up to 360 degrees.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    /// Width and height of the square output.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let max_fov = self.model.max_fov();
+        let in_range = self.fov > 0.0 && self.fov <= max_fov && !(self.model == FisheyeModel::Stereographic && self.fov == max_fov);
+        if !in_range {
+            return Err(Error::InvalidParameter(format!(
+                "{:?} fisheye fov must be in (0, {max_fov}] degrees, got {}",
+                self.model, self.fov
+            )));
+        }
+        if self.size == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<FisheyeCamera> {
+        self.validate()?;
+        let radius = self.size as f64 / 2.0;
+        let theta_max = 0.5 * self.fov.to_radians();
+        Ok(FisheyeCamera {
+            model: self.model,
+            rotation: rotation::euler_to_matrix(self.yaw, self.pitch, self.roll),
+            size: self.size,
+            radius,
+            theta_max,
+            focal: radius / self.model.radius(theta_max),
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+
+    /// Like [`project`](Self::project), also returning the validity mask of
+    /// [`Equirectangular::render_projection_with_mask`].
+    pub fn project_with_mask(&self, pano: &Equirectangular) -> Result<(prelude::Mat, prelude::Mat)> {
+        pano.render_projection_with_mask(&self.camera()?, &self.options)
+    }
+}
+
+/// A fisheye lens resolved from [`FisheyeParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FisheyeCamera {
+    model: FisheyeModel,
+    rotation: Matrix3,
+    size: u32,
+    radius: f64,
+    theta_max: f64,
+    focal: f64,
+}
+
+impl Projection for FisheyeCamera {
+    fn output_size(&self) -> (u32, u32) {
+        (self.size, self.size)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let center = self.radius - 0.5;
+        let (dx, dy) = (u - center, v - center);
+        let theta = self.model.theta((dx * dx + dy * dy).sqrt() / self.focal)?;
+        if theta > self.theta_max {
+            return None;
+        }
+        let phi = dy.atan2(dx);
+        let ray = [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+        Some(rotation::apply(&self.rotation, ray))
+    }
+}
+
+/// Calibration of a real fisheye capture: lens model, field of view, image
+/// circle and the lens orientation in the world.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FisheyeLens {
+    pub(crate) model: FisheyeModel,
+    pub(crate) fov: f64,
+    pub(crate) center: Option<(f64, f64)>,
+    pub(crate) radius: Option<f64>,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+}
+
+impl Default for FisheyeLens {
+    fn default() -> Self {
+        FisheyeLens::new()
+    }
+}
+
+impl FisheyeLens {
+    pub fn new() -> FisheyeLens {
+        FisheyeLens {
+            model: FisheyeModel::default(),
+            fov: 180.0,
+            center: None,
+            radius: None,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+        }
+    }
+
+    pub fn model(mut self, model: FisheyeModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Field of view across the image circle.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    /// Center of the image circle in pixels; defaults to the image center.
+    pub fn center(mut self, cx: f64, cy: f64) -> Self {
+        self.center = Some((cx, cy));
+        self
+    }
+
+    /// Radius of the image circle in pixels; defaults to half the shorter
+    /// image side.
+    pub fn radius(mut self, radius: f64) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+
+    /// Direction of the optical axis.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let max_fov = self.model.max_fov();
+        // A stereographic lens at its maximum has a zero focal length.
+        let in_range = self.fov > 0.0 && self.fov <= max_fov && !(self.model == FisheyeModel::Stereographic && self.fov == max_fov);
+        if !in_range {
+            return Err(Error::InvalidParameter(format!(
+                "{:?} fisheye fov must be in (0, {max_fov}] degrees, got {}",
+                self.model, self.fov
+            )));
+        }
+        if let Some((x, y)) = self.center {
+            if !(x.is_finite() && y.is_finite()) {
+                return Err(Error::InvalidParameter(format!("image circle center must be finite, got ({x}, {y})")));
+            }
+        }
+        if let Some(radius) = self.radius {
+            if !(radius > 0.0 && radius.is_finite()) {
+                return Err(Error::InvalidParameter(format!("image circle radius must be positive, got {radius}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Source geometry for a `width`x`height` capture with this lens.
+    pub fn source(&self, width: u32, height: u32) -> Result<FisheyeSource> {
+        self.validate()?;
+        let (cx, cy) = self.center.unwrap_or(((width as f64 - 1.0) / 2.0, (height as f64 - 1.0) / 2.0));
+        let radius = self.radius.unwrap_or(width.min(height) as f64 / 2.0);
+        let theta_max = 0.5 * self.fov.to_radians();
+        Ok(FisheyeSource {
+            model: self.model,
+            world_to_lens: rotation::transpose(&rotation::euler_to_matrix(self.yaw, self.pitch, self.roll)),
+            width,
+            height,
+            center: (cx, cy),
+            theta_max,
+            focal: radius / self.model.radius(theta_max),
+        })
+    }
+
+    /// Unwarps a capture onto an equirectangular canvas of `canvas_size`
+    /// `(width, height)`. Directions the lens did not see are filled with
+    /// the border color ([`Border::Transparent`](crate::Border::Transparent)
+    /// leaves them zero) and are 0 in the mask.
+    pub fn to_equirectangular(&self, image: &prelude::Mat, canvas_size: (u32, u32), options: &RenderOptions) -> Result<EquirectLayer> {
+        let source = self.source(image.cols() as u32, image.rows() as u32)?;
+        backproject::to_equirectangular(image, &source, canvas_size, options)
+    }
+}
+
+/// A fisheye capture resolved from [`FisheyeLens`], as a source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FisheyeSource {
+    model: FisheyeModel,
+    world_to_lens: Matrix3,
+    width: u32,
+    height: u32,
+    center: (f64, f64),
+    theta_max: f64,
+    focal: f64,
+}
+
+impl FisheyeSource {
+    /// Angle in radians between `dir` and the edge of the lens' field of
+    /// view, negative outside it.
+    pub(crate) fn edge_distance(&self, dir: Vec3) -> f64 {
+        let [x, y, z] = rotation::apply(&self.world_to_lens, dir);
+        self.theta_max - (x * x + y * y).sqrt().atan2(z)
+    }
+}
+
+impl SourceProjection for FisheyeSource {
+    fn source_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        let [x, y, z] = rotation::apply(&self.world_to_lens, dir);
+        let theta = (x * x + y * y).sqrt().atan2(z);
+        if theta > self.theta_max {
+            return None;
+        }
+        let r = self.focal * self.model.radius(theta);
+        let phi = y.atan2(x);
+        let (cx, cy) = self.center;
+        Some(((cx + r * phi.cos()) as f32, (cy + r * phi.sin()) as f32))
+    }
+}
+
+impl Equirectangular {
+    /// Positional shorthand for [`FisheyeParams::project`], angles in degrees.
+    pub fn get_fisheye(&self, fov: f64, theta: f64, phi: f64, size: u32) -> Result<prelude::Mat> {
+        FisheyeParams::new()
+            .fov(Deg(fov))
+            .yaw(Deg(theta))
+            .pitch(Deg(phi))
+            .size(size)
+            .project(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenses_reject_degenerate_calibrations() {
+        let lens = FisheyeLens::new().model(FisheyeModel::Stereographic);
+        assert!(lens.clone().fov(Deg(359.0)).validate().is_ok());
+        assert!(lens.fov(Deg(360.0)).validate().is_err());
+        assert!(FisheyeLens::new().center(f64::NAN, 10.0).validate().is_err());
+        assert!(FisheyeLens::new().center(512.0, f64::INFINITY).validate().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::interop;
+use crate::perspective::Equirectangular;
+use crate::projection::{EquirectProjection, Projection, Vec3};
+use crate::sampling::RenderOptions;
+use crate::source::{self, SourceProjection};
+
+/// A per-direction displacement in longitude and latitude (degrees), laid
+/// out equirectangularly at any resolution. The content that belongs at
+/// `(lon, lat)` is sampled from `(lon + dlon, lat + dlat)` of the source, so
+/// a field measured as "where each point ended up" corrects it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+    dlon: ndarray::Array2<f32>,
+    dlat: ndarray::Array2<f32>,
+}
+
+impl FlowField {
+    pub fn new(dlon: ndarray::Array2<f32>, dlat: ndarray::Array2<f32>) -> Result<FlowField> {
+        let (rows, cols) = dlon.dim();
+        if dlat.dim() != (rows, cols) {
+            return Err(Error::InvalidParameter("longitude and latitude displacements must have the same shape".to_string()));
+        }
+        if rows < 2 || cols < 2 {
+            return Err(Error::InvalidParameter(format!("flow field must be at least 2x2, got {cols}x{rows}")));
+        }
+        Ok(FlowField { dlon, dlat })
+    }
+
+    /// Converts a dense two-channel pixel flow computed on an equirectangular
+    /// image (e.g. by `calcOpticalFlowFarneback`), `(dx, dy)` per pixel with
+    /// y pointing down.
+    pub fn from_pixel_flow(flow: &prelude::Mat) -> Result<FlowField> {
+        if flow.channels() != 2 {
+            return Err(Error::InvalidParameter(format!("pixel flow has two channels, got {}", flow.channels())));
+        }
+        let (rows, cols) = (flow.rows(), flow.cols());
+        if rows < 2 || cols < 2 {
+            return Err(Error::InvalidParameter(format!("flow field must be at least 2x2, got {cols}x{rows}")));
+        }
+        let mut flow_f = prelude::Mat::default();
+        flow.convert_to(&mut flow_f, opencv::core::CV_32F, 1.0, 0.0)?;
+        let data = interop::mat_view3::<f32>(&flow_f)?;
+        let (lon_step, lat_step) = (360.0 / (cols as f32 - 1.0), 180.0 / (rows as f32 - 1.0));
+        let dlon = data.index_axis(ndarray::Axis(2), 0).mapv(|dx| dx * lon_step);
+        let dlat = data.index_axis(ndarray::Axis(2), 1).mapv(|dy| -dy * lat_step);
+        FlowField::new(dlon, dlat)
+    }
+
+    /// Bilinearly interpolated `(dlon, dlat)` at a point.
+    pub fn displacement(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let (rows, cols) = self.dlon.dim();
+        let (x, y) = coords::lonlat_to_equirect(lon, lat, cols as u32, rows as u32);
+        let (x, y) = (x.clamp(0.0, cols as f64 - 1.0), y.clamp(0.0, rows as f64 - 1.0));
+        let (x0, y0) = ((x.floor() as usize).min(cols - 2), (y.floor() as usize).min(rows - 2));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let mut sum = (0.0, 0.0);
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let at = [y0 + dy, x0 + dx];
+                sum.0 += wx * wy * self.dlon[at] as f64;
+                sum.1 += wx * wy * self.dlat[at] as f64;
+            }
+        }
+        sum
+    }
+
+    pub fn warp_direction(&self, dir: Vec3) -> Vec3 {
+        let (lon, lat) = coords::ray_to_lonlat(dir);
+        let (dlon, dlat) = self.displacement(lon, lat);
+        coords::lonlat_to_ray(lon + dlon, lat + dlat)
+    }
+
+    /// `source` sampled through this field.
+    pub fn warp_source<'a, S: SourceProjection + ?Sized>(&'a self, source: &'a S) -> WarpedSource<'a, S> {
+        WarpedSource { source, flow: self }
+    }
+}
+
+/// A source whose directions are displaced by a [`FlowField`] before
+/// lookup; from [`FlowField::warp_source`].
+#[derive(Debug)]
+pub struct WarpedSource<'a, S: ?Sized> {
+    source: &'a S,
+    flow: &'a FlowField,
+}
+
+impl<S: SourceProjection + ?Sized> SourceProjection for WarpedSource<'_, S> {
+    fn source_size(&self) -> (u32, u32) {
+        self.source.source_size()
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        self.source.pixel_for_direction(self.flow.warp_direction(dir))
+    }
+
+    fn wraps_horizontally(&self) -> bool {
+        self.source.wraps_horizontally()
+    }
+}
+
+impl Equirectangular {
+    /// Renders `projection` with every lookup displaced by `flow`.
+    pub fn render_warped<P: Projection + ?Sized>(&self, projection: &P, flow: &FlowField, options: &RenderOptions) -> Result<prelude::Mat> {
+        source::render(self.as_mat(), &flow.warp_source(&self.source()), projection, options)
+    }
+
+    /// The whole panorama corrected by `flow`, as a full frame.
+    pub fn warp(&self, flow: &FlowField) -> Result<Equirectangular> {
+        let (width, height) = match self.crop() {
+            Some(crop) => (crop.full_width, crop.full_height),
+            None => (self.width(), self.height()),
+        };
+        let frame = EquirectProjection::new(width, height);
+        Equirectangular::from_mat(self.render_warped(&frame, flow, &RenderOptions::default())?)
+    }
+}
@@ -0,0 +1,357 @@
+//! Perspective rendering in a wgpu compute shader, for GPU acceleration
+//! without an OpenCV build that has OpenCL or CUDA.
+
+use opencv::imgproc;
+use opencv::prelude;
+use opencv::prelude::{MatTraitConst, MatTraitConstManual, MatTraitManual};
+use wgpu::util::DeviceExt;
+
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::output::{self, OutputFormat};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::rotation;
+use crate::sampling::Interpolation;
+
+const SHADER: &str = r#"
+struct View {
+    rows: array<vec4<f32>, 3>,
+    out_size: vec2<u32>,
+    pano_size: vec2<u32>,
+}
+
+@group(0) @binding(0) var<uniform> view: View;
+@group(0) @binding(1) var pano: texture_2d<f32>;
+@group(0) @binding(2) var pano_sampler: sampler;
+@group(0) @binding(3) var output: texture_storage_2d<rgba8unorm, write>;
+
+const PI: f32 = 3.14159265358979;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= view.out_size.x || id.y >= view.out_size.y) {
+        return;
+    }
+    let pixel = vec3<f32>(f32(id.x), f32(id.y), 1.0);
+    let ray = normalize(vec3<f32>(dot(view.rows[0].xyz, pixel), dot(view.rows[1].xyz, pixel), dot(view.rows[2].xyz, pixel)));
+    let lon = atan2(ray.x, ray.z);
+    let lat = -asin(clamp(ray.y, -1.0, 1.0));
+    // Same pixel grid as coords::lonlat_to_equirect, then texel centers.
+    let size = vec2<f32>(view.pano_size);
+    let x = (lon / (2.0 * PI) + 0.5) * (size.x - 1.0);
+    let y = (-lat / PI + 0.5) * (size.y - 1.0);
+    let color = textureSampleLevel(pano, pano_sampler, (vec2<f32>(x, y) + 0.5) / size, 0.0);
+    textureStore(output, vec2<i32>(id.xy), color);
+}
+"#;
+
+/// Uniforms of [`SHADER`]: rows of the pixel-to-world matrix `R K⁻¹`, then
+/// the output and panorama sizes.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewUniform {
+    rows: [[f32; 4]; 3],
+    out_size: [u32; 2],
+    pano_size: [u32; 2],
+}
+
+/// A GPU device with the projection shader compiled, reused across frames.
+///
+/// Renders 8-bit panoramas with 1, 3 or 4 channels. Nearest interpolation
+/// samples without filtering and every other mode is bilinear; the view is
+/// computed on the device, so lens distortion, capture effects and
+/// partial panoramas are not supported.
+pub struct WgpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+fn gpu_error(e: impl std::fmt::Display) -> Error {
+    Error::Gpu(e.to_string())
+}
+
+impl WgpuRenderer {
+    /// Opens the default high-performance adapter.
+    pub fn new() -> Result<WgpuRenderer> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or_else(|| Error::Gpu("no GPU adapter available".to_string()))?;
+        // The default limits cap textures at 8192 pixels, below common
+        // panorama widths; ask for everything the adapter offers.
+        let descriptor = wgpu::DeviceDescriptor {
+            required_limits: adapter.limits(),
+            ..Default::default()
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).map_err(gpu_error)?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("e2p projection"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("e2p projection"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Ok(WgpuRenderer { device, queue, pipeline })
+    }
+
+    /// Renders `params` from `pano`.
+    pub fn render(&self, pano: &Equirectangular, params: &PerspectiveParams) -> Result<prelude::Mat> {
+        params.validate()?;
+        if pano.crop().is_some() {
+            return Err(Error::InvalidParameter("the wgpu renderer needs a full panorama".to_string()));
+        }
+        if params.deterministic {
+            return Err(Error::InvalidParameter("deterministic views must be rendered on the CPU".to_string()));
+        }
+        if params.distortion_coefficients().is_some() || !params.capture_effects().is_identity() {
+            return Err(Error::InvalidParameter("the wgpu renderer does not support lens distortion or capture effects".to_string()));
+        }
+        let src = pano.as_mat();
+        let channels = src.channels();
+        let to_bgra = match channels {
+            1 => imgproc::COLOR_GRAY2BGRA,
+            3 => imgproc::COLOR_BGR2BGRA,
+            4 => -1,
+            _ => return Err(Error::InvalidParameter(format!("cannot render a {channels}-channel panorama on the GPU"))),
+        };
+        if src.depth() != opencv::core::CV_8U {
+            return Err(Error::InvalidParameter("the wgpu renderer needs an 8-bit panorama".to_string()));
+        }
+        // Oversized textures are validation errors, which wgpu reports by
+        // panicking.
+        let limits = self.device.limits();
+        let max = limits.max_texture_dimension_2d;
+        for (what, width, height) in [("panorama", src.cols() as u32, src.rows() as u32), ("view", params.width, params.height)] {
+            if width > max || height > max {
+                return Err(Error::InvalidParameter(format!("{what} is {width}x{height}, the GPU supports at most {max}x{max}")));
+            }
+        }
+        let readback = params.width.div_ceil(64) as u64 * 256 * params.height as u64;
+        if readback > limits.max_buffer_size {
+            return Err(Error::InvalidParameter(format!(
+                "a {}x{} view needs more than the GPU's {} byte buffer limit",
+                params.width, params.height, limits.max_buffer_size
+            )));
+        }
+        let bgra = if to_bgra < 0 {
+            src.try_clone()?
+        } else {
+            let mut bgra = prelude::Mat::default();
+            imgproc::cvt_color_def(src, &mut bgra, to_bgra)?;
+            bgra
+        };
+
+        let rendered = self.dispatch(&bgra, params, pano.view_rotation(params)?)?;
+        let native = match channels {
+            1 => {
+                let mut gray = prelude::Mat::default();
+                imgproc::cvt_color_def(&rendered, &mut gray, imgproc::COLOR_BGRA2GRAY)?;
+                gray
+            }
+            3 => {
+                let mut bgr = prelude::Mat::default();
+                imgproc::cvt_color_def(&rendered, &mut bgr, imgproc::COLOR_BGRA2BGR)?;
+                bgr
+            }
+            _ => rendered,
+        };
+        if params.output_format == OutputFormat::Native {
+            return Ok(native);
+        }
+        let mut dst = prelude::Mat::default();
+        output::convert_into(&native, params.output_format, &mut dst)?;
+        Ok(dst)
+    }
+
+    fn dispatch(&self, bgra: &prelude::Mat, params: &PerspectiveParams, to_pano: rotation::Matrix3) -> Result<prelude::Mat> {
+        let (pano_width, pano_height) = (bgra.cols() as u32, bgra.rows() as u32);
+        let (width, height) = (params.width, params.height);
+
+        // R K⁻¹ applied to (u, v, 1), one column per component.
+        let k = params.intrinsics();
+        let origin = rotation::apply(&to_pano, coords::unproject_lens(&k, None, 0.0, 0.0));
+        let along_u = rotation::apply(&to_pano, coords::unproject_lens(&k, None, 1.0, 0.0));
+        let along_v = rotation::apply(&to_pano, coords::unproject_lens(&k, None, 0.0, 1.0));
+        let rows = std::array::from_fn(|i| [(along_u[i] - origin[i]) as f32, (along_v[i] - origin[i]) as f32, origin[i] as f32, 0.0]);
+        let uniform = ViewUniform { rows, out_size: [width, height], pano_size: [pano_width, pano_height] };
+        let uniform = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("e2p view"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let pano_extent = wgpu::Extent3d { width: pano_width, height: pano_height, depth_or_array_layers: 1 };
+        let pano_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("e2p panorama"),
+            size: pano_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &pano_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bgra.data_bytes()?,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * pano_width),
+                rows_per_image: Some(pano_height),
+            },
+            pano_extent,
+        );
+
+        let filter = match params.interpolation {
+            Interpolation::Nearest => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        };
+        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("e2p panorama"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let out_extent = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let out_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("e2p view"),
+            size: out_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("e2p projection"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform.as_entire_binding() },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&pano_texture.create_view(&Default::default())),
+                },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&out_texture.create_view(&Default::default())),
+                },
+            ],
+        });
+
+        // Texture-to-buffer copies need rows padded to 256 bytes.
+        let row_bytes = 4 * width;
+        let padded_row = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("e2p readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("e2p projection") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("e2p projection"), timestamp_writes: None });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(width.div_ceil(8), height.div_ceil(8), 1);
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &out_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            out_extent,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().map_err(gpu_error)?.map_err(gpu_error)?;
+
+        let mut view = prelude::Mat::new_rows_cols_with_default(height as i32, width as i32, opencv::core::CV_8UC4, opencv::core::Scalar::all(0.0))?;
+        {
+            let mapped = slice.get_mapped_range();
+            let dst = view.data_bytes_mut()?;
+            for (row, padded) in dst.chunks_exact_mut(row_bytes as usize).zip(mapped.chunks_exact(padded_row as usize)) {
+                row.copy_from_slice(&padded[..row_bytes as usize]);
+            }
+        }
+        readback.unmap();
+        Ok(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Deg;
+
+    /// Smooth colors, so bilinear sampling on either side agrees closely.
+    fn gradient(width: u32, height: u32) -> Equirectangular {
+        let data: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| [(x * 255 / width) as u8, (y * 255 / height) as u8, 128]))
+            .collect();
+        Equirectangular::from_bytes(&data, width, height).unwrap()
+    }
+
+    #[test]
+    fn matches_the_cpu() {
+        // Machines without an adapter have nothing to compare.
+        let Ok(renderer) = WgpuRenderer::new() else { return };
+        let pano = gradient(512, 256);
+        let params = PerspectiveParams::new()
+            .fov(Deg(80.0))
+            .yaw(Deg(30.0))
+            .pitch(Deg(20.0))
+            .output_size(96, 64)
+            .interpolation(Interpolation::Linear);
+        let gpu = renderer.render(&pano, &params).unwrap();
+        let cpu = params.project(&pano).unwrap();
+        let (gpu, cpu) = (gpu.data_bytes().unwrap(), cpu.data_bytes().unwrap());
+        assert_eq!(gpu.len(), cpu.len());
+        let error = gpu.iter().zip(cpu).map(|(&a, &b)| a.abs_diff(b) as f64).sum::<f64>() / gpu.len() as f64;
+        assert!(error < 2.0, "mean difference {error}");
+    }
+
+    #[test]
+    fn rejects_views_beyond_the_device_limits() {
+        let Ok(renderer) = WgpuRenderer::new() else { return };
+        let max = renderer.device.limits().max_texture_dimension_2d;
+        let params = PerspectiveParams::new().output_size(max + 1, 16);
+        assert!(matches!(renderer.render(&gradient(64, 32), &params), Err(Error::InvalidParameter(_))));
+    }
+}
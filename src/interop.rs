@@ -0,0 +1,93 @@
+//! Zero-copy views between OpenCV mats and ndarray arrays, so changing
+//! library types never duplicates a frame.
+
+use opencv::boxed_ref::{BoxedRef, BoxedRefMut};
+use opencv::core::DataType;
+use opencv::prelude;
+use opencv::prelude::{MatTrait, MatTraitConst};
+
+use crate::error::{Error, Result};
+
+fn check_depth<T: DataType>(mat: &prelude::Mat) -> Result<()> {
+    if mat.depth() != T::opencv_depth() || T::opencv_channels() != 1 {
+        return Err(Error::InvalidParameter(format!(
+            "mat of type {} cannot be viewed as {}",
+            mat.typ(),
+            std::any::type_name::<T>()
+        )));
+    }
+    if mat.dims() != 2 {
+        return Err(Error::InvalidParameter(format!("only 2-dimensional mats can be viewed, got {} dimensions", mat.dims())));
+    }
+    Ok(())
+}
+
+/// Shape and strides in elements of `mat` as `(rows, cols, channels)`.
+fn layout(mat: &prelude::Mat) -> Result<((usize, usize, usize), (usize, usize, usize))> {
+    let channels = mat.channels() as usize;
+    let shape = (mat.rows() as usize, mat.cols() as usize, channels);
+    Ok((shape, (mat.step1(0)?, channels, 1)))
+}
+
+/// Borrows a mat as a `(rows, cols, channels)` array of its element type,
+/// following the mat's row stride so ROIs need no copy.
+pub fn mat_view3<T: DataType>(mat: &prelude::Mat) -> Result<ndarray::ArrayView3<'_, T>> {
+    check_depth::<T>(mat)?;
+    let (shape, strides) = layout(mat)?;
+    if mat.empty() {
+        return Ok(ndarray::ArrayView3::from_shape(shape, &[])?);
+    }
+    // SAFETY: the depth matches `T`, and rows, columns and channels stay
+    // within the mat's data, which outlives the returned view.
+    Ok(unsafe { ndarray::ArrayView3::from_shape_ptr(ndarray::ShapeBuilder::strides(shape, strides), mat.data().cast::<T>()) })
+}
+
+/// Mutable counterpart of [`mat_view3`].
+pub fn mat_view3_mut<T: DataType>(mat: &mut prelude::Mat) -> Result<ndarray::ArrayViewMut3<'_, T>> {
+    check_depth::<T>(mat)?;
+    let (shape, strides) = layout(mat)?;
+    if mat.empty() {
+        return Ok(ndarray::ArrayViewMut3::from_shape(shape, &mut [])?);
+    }
+    // SAFETY: as in `mat_view3`, with the mat borrowed mutably.
+    Ok(unsafe { ndarray::ArrayViewMut3::from_shape_ptr(ndarray::ShapeBuilder::strides(shape, strides), mat.data_mut().cast::<T>()) })
+}
+
+/// Borrows a single-channel mat as a `(rows, cols)` array.
+pub fn mat_view<T: DataType>(mat: &prelude::Mat) -> Result<ndarray::ArrayView2<'_, T>> {
+    if mat.channels() != 1 {
+        return Err(Error::InvalidParameter(format!("expected a single-channel mat, got {} channels", mat.channels())));
+    }
+    Ok(mat_view3(mat)?.remove_axis(ndarray::Axis(2)))
+}
+
+/// Mutable counterpart of [`mat_view`].
+pub fn mat_view_mut<T: DataType>(mat: &mut prelude::Mat) -> Result<ndarray::ArrayViewMut2<'_, T>> {
+    if mat.channels() != 1 {
+        return Err(Error::InvalidParameter(format!("expected a single-channel mat, got {} channels", mat.channels())));
+    }
+    Ok(mat_view3_mut(mat)?.remove_axis(ndarray::Axis(2)))
+}
+
+/// Wraps a standard-layout array as a single-channel mat sharing its data.
+pub fn array_mat<T, S>(array: &ndarray::ArrayBase<S, ndarray::Ix2>) -> Result<BoxedRef<'_, prelude::Mat>>
+where
+    T: DataType,
+    S: ndarray::Data<Elem = T>,
+{
+    let (rows, cols) = array.dim();
+    let data = array.as_slice().ok_or_else(|| Error::InvalidParameter("array is not contiguous".to_string()))?;
+    Ok(prelude::Mat::new_rows_cols_with_data(rows as i32, cols as i32, data)?)
+}
+
+/// Mutable counterpart of [`array_mat`], for OpenCV calls that write into
+/// the array in place.
+pub fn array_mat_mut<T, S>(array: &mut ndarray::ArrayBase<S, ndarray::Ix2>) -> Result<BoxedRefMut<'_, prelude::Mat>>
+where
+    T: DataType,
+    S: ndarray::DataMut<Elem = T>,
+{
+    let (rows, cols) = array.dim();
+    let data = array.as_slice_mut().ok_or_else(|| Error::InvalidParameter("array is not contiguous".to_string()))?;
+    Ok(prelude::Mat::new_rows_cols_with_data_mut(rows as i32, cols as i32, data)?)
+}
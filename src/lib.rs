@@ -0,0 +1,105 @@
+pub mod analysis;
+pub mod angle;
+pub mod annotate;
+pub mod backproject;
+pub mod batch;
+mod blend;
+pub mod cache;
+pub mod coords;
+pub mod cubemap;
+pub mod cylindrical;
+pub mod depth;
+pub mod discretize;
+pub mod distortion;
+pub mod effects;
+pub mod error;
+#[cfg(feature = "simd")]
+mod fastmath;
+pub mod fisheye;
+pub mod flow;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+pub mod interop;
+mod maps;
+pub mod mercator;
+pub mod output;
+pub mod overlay;
+mod overview;
+pub mod pannini;
+pub mod params;
+pub mod perspective;
+pub mod planet;
+pub mod pole;
+pub mod pool;
+pub mod projection;
+pub mod projector;
+pub mod region;
+pub mod reproject;
+pub mod rig;
+pub mod rotation;
+#[cfg(feature = "tokio")]
+mod runtime;
+pub mod sampling;
+#[cfg(feature = "scene")]
+pub mod scene;
+pub mod sequence;
+pub mod source;
+pub mod stats;
+pub mod stereo;
+pub mod stitch;
+pub mod table;
+pub mod tiles;
+pub mod vr180;
+pub mod world;
+
+pub use analysis::{TissotMaps, solid_angle_map, tissot_maps};
+pub use angle::{Angle, Deg, Rad};
+pub use annotate::{LiftedPoint, LonLatBox, ProjectedBox, ProjectedPoint};
+pub use backproject::{EquirectLayer, Perspective};
+pub use batch::{Batch, BatchReport};
+pub use cubemap::{CubeFace, CubeLayout, CubeMap, CubeSource, CubemapParams, CubemapStyle, FaceOrder};
+pub use cylindrical::{Cylindrical, CylindricalParams, PanoramaStrip, StripModel, StripSource};
+pub use depth::{DepthMap, ParallaxView};
+pub use discretize::{Healpix, ICOSAHEDRON_FACES, IcosahedronFace};
+pub use distortion::Distortion;
+pub use effects::CaptureEffects;
+pub use error::{Error, Result};
+pub use fisheye::{FisheyeCamera, FisheyeLens, FisheyeModel, FisheyeParams, FisheyeSource};
+pub use flow::{FlowField, WarpedSource};
+#[cfg(feature = "wgpu")]
+pub use gpu::WgpuRenderer;
+pub use mercator::{Mercator, MercatorAspect, MercatorParams};
+pub use output::{EncodeOptions, OutputFormat};
+pub use overlay::Graticule;
+pub use pannini::{Pannini, PanniniParams};
+pub use params::{Eye, PerspectiveParams};
+pub use perspective::{CroppedPanoParams, Equirectangular, lonlat_to_xy, xyz_to_lonlat};
+pub use planet::{LittlePlanet, LittlePlanetParams};
+pub use pole::{Pole, PolePatch};
+pub use projection::{EquirectProjection, PerspectiveCamera, Projection, Vec3};
+pub use projector::Projector;
+pub use region::{LonLatWindow, RegionCrop, RegionStyle};
+pub use reproject::{reproject, reproject_to_cubemap, reproject_with_mask};
+pub use rig::{Rig, RigCamera};
+pub use rotation::{EulerFrame, EulerOrder};
+pub use sampling::{Backend, Border, Interpolation, Precision, RenderOptions};
+#[cfg(feature = "simd")]
+pub use sampling::SimdAccuracy;
+#[cfg(feature = "scene")]
+pub use scene::{Scene, SceneView};
+pub use sequence::{Easing, Pose, Sweep};
+pub use source::{EquirectSource, PerspectiveSource, SourceProjection};
+pub use stats::Stats;
+pub use stereo::{StereoLayout, StereoPair};
+pub use stitch::{BlendMode, DualFisheye, Mosaic};
+pub use table::RayTable;
+pub use tiles::GnomonicTiling;
+pub use vr180::{HalfEquirect, Vr180Params};
+pub use world::{WorldMap, WorldMapParams, WorldProjection};
+
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Equirectangular>();
+    assert_send_sync::<Projector>();
+    assert_send_sync::<PerspectiveParams>();
+};
@@ -1,10 +1,489 @@
-use crate::perspective::Equirectangular;
-use opencv;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-mod perspective;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use e2p::{
+    Backend, Batch, BatchReport, CubeLayout, CubeMap, Deg, EncodeOptions, Equirectangular, FaceOrder, FisheyeLens, FisheyeModel, FisheyeParams,
+    LittlePlanetParams, PerspectiveParams, Projector, RenderOptions,
+};
+use opencv::imgcodecs;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
 
-fn main() {
-    let image = Equirectangular::new("image.jpg");
-    let perspective_image = image.get_perspective(60.0, 80.0, 33.0, 720, 1080);
-    opencv::imgcodecs::imwrite("final_image.jpg", &perspective_image, &opencv::core::Vector::<i32>::new()).expect("Could not write image!");
-}
\ No newline at end of file
+/// Renders views and reprojections of equirectangular panoramas.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a perspective view.
+    Persp(PerspArgs),
+    /// Render the six cube faces as one atlas image.
+    Cubemap(CubemapArgs),
+    /// Render a fisheye view.
+    Fisheye(FisheyeArgs),
+    /// Render a little planet (stereographic) view.
+    Littleplanet(LittlePlanetArgs),
+    /// Convert a cube map atlas or a fisheye photo into a panorama.
+    Reproject(ReprojectArgs),
+    /// Render every view listed in a scene file.
+    #[cfg(feature = "scene")]
+    Run {
+        /// TOML file naming the panoramas and the views to cut from them.
+        scene: PathBuf,
+    },
+    /// Print the size and format of an image.
+    Info {
+        /// Image to inspect.
+        input: PathBuf,
+    },
+    /// Report map build and remap times for every available backend.
+    Bench,
+}
+
+#[derive(Args)]
+struct Io {
+    /// Image to read.
+    #[arg(long, short)]
+    input: PathBuf,
+    /// Image to write; the extension picks the format.
+    #[arg(long, short)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct Orientation {
+    /// Longitude of the view center in degrees, increasing to the right.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    yaw: f64,
+    /// Latitude of the view center in degrees, positive above the horizon.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pitch: f64,
+    /// Rotation about the viewing direction in degrees.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    roll: f64,
+}
+
+#[derive(Args)]
+struct PerspArgs {
+    /// Panorama to read, a directory of panoramas, or a glob pattern such
+    /// as "panos/**/*.jpg".
+    #[arg(long, short)]
+    input: String,
+    /// Image to write; the extension picks the format.
+    #[arg(long, short, required_unless_present = "out_dir", conflicts_with = "out_dir")]
+    output: Option<PathBuf>,
+    /// Directory to write one view per input into, keeping the inputs'
+    /// relative paths and formats.
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+    /// Panoramas rendered at once in batch mode; defaults to one per core.
+    #[arg(long, short)]
+    jobs: Option<usize>,
+    #[command(flatten)]
+    orientation: Orientation,
+    /// Horizontal field of view in degrees, in (0, 180).
+    #[arg(long, default_value_t = 90.0, value_parser = parse_fov)]
+    fov: f64,
+    /// Output size as WIDTHxHEIGHT.
+    #[arg(long, default_value = "1920x1080", value_parser = parse_size)]
+    size: (u32, u32),
+    /// JPEG and WebP quality from 1 to 100.
+    #[arg(long, default_value_t = 95, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+}
+
+#[derive(Args)]
+struct CubemapArgs {
+    #[command(flatten)]
+    io: Io,
+    /// Edge length of each face in pixels.
+    #[arg(long, default_value_t = 1024)]
+    face_size: u32,
+    #[arg(long, value_enum, default_value_t = Layout::HorizontalCross)]
+    layout: Layout,
+}
+
+#[derive(Args)]
+struct FisheyeArgs {
+    #[command(flatten)]
+    io: Io,
+    #[command(flatten)]
+    orientation: Orientation,
+    #[arg(long, value_enum, default_value_t = Model::Equidistant)]
+    model: Model,
+    /// Field of view across the image circle in degrees.
+    #[arg(long, default_value_t = 180.0)]
+    fov: f64,
+    /// Edge length of the square output in pixels.
+    #[arg(long, default_value_t = 1024)]
+    size: u32,
+}
+
+#[derive(Args)]
+struct LittlePlanetArgs {
+    #[command(flatten)]
+    io: Io,
+    /// Field of view across the inscribed circle in degrees, below 360.
+    #[arg(long, default_value_t = 300.0)]
+    fov: f64,
+    /// Longitude of the point in the middle of the planet, in degrees.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    lon: f64,
+    /// Latitude of the point in the middle of the planet: -90 for a
+    /// planet, 90 for a tunnel.
+    #[arg(long, default_value_t = -90.0, allow_negative_numbers = true)]
+    lat: f64,
+    /// Turn of the output about its center in degrees.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    rotation: f64,
+    /// Edge length of the square output in pixels.
+    #[arg(long, default_value_t = 1024)]
+    size: u32,
+}
+
+#[derive(Args)]
+struct ReprojectArgs {
+    #[command(flatten)]
+    io: Io,
+    /// What the input image is.
+    #[arg(long, value_enum)]
+    from: Source,
+    /// Width of the panorama; its height is half of it.
+    #[arg(long, default_value_t = 4096)]
+    width: u32,
+    /// Atlas layout of a cube map input.
+    #[arg(long, value_enum, default_value_t = Layout::HorizontalCross)]
+    layout: Layout,
+    /// Lens model of a fisheye input.
+    #[arg(long, value_enum, default_value_t = Model::Equidistant)]
+    model: Model,
+    /// Field of view of a fisheye input in degrees.
+    #[arg(long, default_value_t = 180.0)]
+    fov: f64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Source {
+    Cubemap,
+    Fisheye,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Layout {
+    HorizontalCross,
+    VerticalCross,
+    Grid3x2,
+    Strip6x1,
+    Strip1x6,
+}
+
+impl From<Layout> for CubeLayout {
+    fn from(layout: Layout) -> Self {
+        match layout {
+            Layout::HorizontalCross => CubeLayout::HorizontalCross,
+            Layout::VerticalCross => CubeLayout::VerticalCross,
+            Layout::Grid3x2 => CubeLayout::Grid3x2,
+            Layout::Strip6x1 => CubeLayout::Strip6x1,
+            Layout::Strip1x6 => CubeLayout::Strip1x6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Model {
+    Equidistant,
+    Equisolid,
+    Orthographic,
+    Stereographic,
+}
+
+impl From<Model> for FisheyeModel {
+    fn from(model: Model) -> Self {
+        match model {
+            Model::Equidistant => FisheyeModel::Equidistant,
+            Model::Equisolid => FisheyeModel::Equisolid,
+            Model::Orthographic => FisheyeModel::Orthographic,
+            Model::Stereographic => FisheyeModel::Stereographic,
+        }
+    }
+}
+
+fn parse_fov(value: &str) -> Result<f64, String> {
+    let fov: f64 = value.parse().map_err(|_| format!("`{value}` is not a number"))?;
+    if !(fov > 0.0 && fov < 180.0) {
+        return Err(format!("field of view must be between 0 and 180 degrees, got {fov}"));
+    }
+    Ok(fov)
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("`{value}` is not a size like 1920x1080");
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let (width, height): (u32, u32) = (width.parse().map_err(|_| invalid())?, height.parse().map_err(|_| invalid())?);
+    if width == 0 || height == 0 {
+        return Err(format!("output size must be non-zero, got {width}x{height}"));
+    }
+    Ok((width, height))
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Persp(args) => persp(&args),
+        Command::Cubemap(args) => cubemap(&args),
+        Command::Fisheye(args) => fisheye(&args),
+        Command::Littleplanet(args) => little_planet(&args),
+        Command::Reproject(args) => reproject(&args),
+        #[cfg(feature = "scene")]
+        Command::Run { scene } => run(&scene),
+        Command::Info { input } => info(&input),
+        Command::Bench => bench(),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn path_str(path: &Path) -> e2p::Result<&str> {
+    path.to_str().ok_or_else(|| e2p::Error::InvalidParameter(format!("{} is not valid UTF-8", path.display())))
+}
+
+fn open(path: &Path) -> e2p::Result<Equirectangular> {
+    Equirectangular::new(path_str(path)?)
+}
+
+fn read(path: &Path) -> e2p::Result<prelude::Mat> {
+    std::fs::metadata(path)?;
+    let image = imgcodecs::imread(path_str(path)?, imgcodecs::IMREAD_UNCHANGED)?;
+    if image.empty() {
+        return Err(e2p::Error::Decode(format!("{} is not a supported image", path.display())));
+    }
+    Ok(image)
+}
+
+fn write(path: &Path, image: &prelude::Mat) -> e2p::Result<()> {
+    if !imgcodecs::imwrite(path_str(path)?, image, &opencv::core::Vector::new())? {
+        return Err(e2p::Error::Io(std::io::Error::other(format!("could not encode {}", path.display()))));
+    }
+    Ok(())
+}
+
+fn persp(args: &PerspArgs) -> e2p::Result<()> {
+    let params = PerspectiveParams::new()
+        .fov(Deg(args.fov))
+        .yaw(Deg(args.orientation.yaw))
+        .pitch(Deg(args.orientation.pitch))
+        .roll(Deg(args.orientation.roll))
+        .output_size(args.size.0, args.size.1);
+    let options = EncodeOptions::new().jpeg_quality(args.quality);
+    match (&args.output, &args.out_dir) {
+        (Some(output), _) if !is_pattern(&args.input) && !Path::new(&args.input).is_dir() => {
+            open(Path::new(&args.input))?.project_to_file(output, &params, &options)
+        }
+        (_, Some(out_dir)) => persp_batch(args, params, options, out_dir),
+        _ => Err(e2p::Error::InvalidParameter("several inputs need --out-dir instead of --output".to_string())),
+    }
+}
+
+/// Extensions of the files picked up from an input directory.
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "tif", "tiff", "webp", "exr", "hdr"];
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// The files matching `input` with the directory their relative paths are
+/// kept from: the pattern's leading components without wildcards, or the
+/// directory itself.
+fn batch_inputs(input: &str) -> e2p::Result<(PathBuf, Vec<PathBuf>)> {
+    let path = Path::new(input);
+    if !is_pattern(input) && !path.is_dir() {
+        return Ok((path.parent().unwrap_or(Path::new("")).to_path_buf(), vec![path.to_path_buf()]));
+    }
+    let invalid = |e: &dyn std::fmt::Display| e2p::Error::InvalidParameter(format!("bad input pattern `{input}`: {e}"));
+    let (base, pattern) = if is_pattern(input) {
+        let base: PathBuf = Path::new(input).components().take_while(|c| !is_pattern(&c.as_os_str().to_string_lossy())).collect();
+        (base, input.to_string())
+    } else {
+        (PathBuf::from(input), format!("{}/**/*", glob::Pattern::escape(input)))
+    };
+    let mut files = Vec::new();
+    for entry in glob::glob(&pattern).map_err(|e| invalid(&e))? {
+        let path = entry.map_err(|e| invalid(&e))?;
+        let image = path
+            .extension()
+            .is_some_and(|ext| IMAGE_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)));
+        // Patterns pick their files themselves; directories only yield images.
+        if path.is_file() && (is_pattern(input) || image) {
+            files.push(path);
+        }
+    }
+    Ok((base, files))
+}
+
+fn persp_batch(args: &PerspArgs, params: PerspectiveParams, options: EncodeOptions, out_dir: &Path) -> e2p::Result<()> {
+    let (base, inputs) = batch_inputs(&args.input)?;
+    if inputs.is_empty() {
+        return Err(e2p::Error::InvalidParameter(format!("no images match `{}`", args.input)));
+    }
+    let output = |input: &Path| out_dir.join(input.strip_prefix(&base).unwrap_or(input));
+    // Outputs inside the input tree could replace panoramas not yet read.
+    let sources: std::collections::HashSet<PathBuf> = inputs.iter().map(|input| input.canonicalize()).collect::<Result<_, _>>()?;
+    for input in &inputs {
+        let output = output(input);
+        let resolved = match (output.parent().map(Path::canonicalize), output.file_name()) {
+            (Some(Ok(parent)), Some(name)) => parent.join(name),
+            _ => continue,
+        };
+        if sources.contains(&resolved) {
+            return Err(e2p::Error::InvalidParameter(format!(
+                "writing {} would replace an input; pick another --out-dir",
+                output.display()
+            )));
+        }
+    }
+    for input in &inputs {
+        if let Some(parent) = output(input).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let mut batch = Batch::new(vec![params]).encode_options(options);
+    if let Some(jobs) = args.jobs {
+        batch = batch.workers(jobs).encoders(jobs.div_ceil(2));
+    }
+    let count = inputs.len();
+    summarize(&batch.run(inputs, |input, _| output(input))?, count)
+}
+
+#[cfg(feature = "scene")]
+fn run(path: &Path) -> e2p::Result<()> {
+    let scene = e2p::Scene::load(path)?;
+    summarize(&scene.run()?, scene.inputs().len() * scene.views().len())
+}
+
+/// Prints each failure of a batch of `count` views, failing if any did.
+fn summarize(report: &BatchReport, count: usize) -> e2p::Result<()> {
+    for (path, error) in &report.failures {
+        eprintln!("error: {}: {error}", path.display());
+    }
+    println!("{} of {count} views written", report.written);
+    if !report.failures.is_empty() {
+        return Err(e2p::Error::InvalidParameter(format!("{} of {count} views failed", report.failures.len())));
+    }
+    Ok(())
+}
+
+fn cubemap(args: &CubemapArgs) -> e2p::Result<()> {
+    let atlas = open(&args.io.input)?.to_cubemap(args.face_size)?.to_atlas(args.layout.into())?;
+    write(&args.io.output, &atlas)
+}
+
+fn fisheye(args: &FisheyeArgs) -> e2p::Result<()> {
+    let view = FisheyeParams::new()
+        .model(args.model.into())
+        .fov(Deg(args.fov))
+        .yaw(Deg(args.orientation.yaw))
+        .pitch(Deg(args.orientation.pitch))
+        .roll(Deg(args.orientation.roll))
+        .size(args.size)
+        .project(&open(&args.io.input)?)?;
+    write(&args.io.output, &view)
+}
+
+fn little_planet(args: &LittlePlanetArgs) -> e2p::Result<()> {
+    let view = LittlePlanetParams::new()
+        .fov(Deg(args.fov))
+        .center(Deg(args.lon), Deg(args.lat))
+        .rotation(Deg(args.rotation))
+        .size(args.size)
+        .project(&open(&args.io.input)?)?;
+    write(&args.io.output, &view)
+}
+
+fn reproject(args: &ReprojectArgs) -> e2p::Result<()> {
+    let image = read(&args.io.input)?;
+    let pano = match args.from {
+        Source::Cubemap => {
+            let cube = CubeMap::from_atlas(&image, args.layout.into(), FaceOrder::default())?;
+            Equirectangular::from_cubemap(&cube, args.width)?.as_mat().try_clone()?
+        }
+        Source::Fisheye => {
+            let lens = FisheyeLens::new().model(args.model.into()).fov(Deg(args.fov));
+            lens.to_equirectangular(&image, (args.width, args.width / 2), &RenderOptions::default())?.image
+        }
+    };
+    write(&args.io.output, &pano)
+}
+
+fn info(input: &Path) -> e2p::Result<()> {
+    let image = read(input)?;
+    let (width, height) = (image.cols(), image.rows());
+    let depth = match image.depth() {
+        opencv::core::CV_8U => "8-bit",
+        opencv::core::CV_16U => "16-bit",
+        opencv::core::CV_32F => "32-bit float",
+        _ => "other",
+    };
+    println!("{}", input.display());
+    println!("  size:     {width}x{height}");
+    println!("  channels: {} ({depth})", image.channels());
+    let equirect = if width == 2 * height { "yes" } else { "no (not 2:1)" };
+    println!("  full equirectangular: {equirect}");
+    Ok(())
+}
+
+/// Mean time of `f` over enough runs to fill about half a second.
+fn time(mut f: impl FnMut() -> e2p::Result<()>) -> e2p::Result<Duration> {
+    f()?;
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs == 0 || start.elapsed() < Duration::from_millis(500) {
+        f()?;
+        runs += 1;
+    }
+    Ok(start.elapsed() / runs)
+}
+
+/// Reports map build and per-frame remap times of a 1080p view for every
+/// backend available here, to pick one for this machine.
+fn bench() -> e2p::Result<()> {
+    let mut backends = vec![Backend::Cpu, Backend::OpenCl];
+    #[cfg(feature = "cuda")]
+    backends.push(Backend::Cuda);
+    let params = PerspectiveParams::new().fov(Deg(90.0)).yaw(Deg(30.0)).pitch(Deg(10.0)).output_size(1920, 1080);
+
+    println!("{:<8} {:>10} {:>12} {:>12} {:>10}", "backend", "panorama", "maps (ms)", "remap (ms)", "frames/s");
+    for backend in backends {
+        if !backend.is_available() {
+            println!("{:<8} unavailable", format!("{backend:?}"));
+            continue;
+        }
+        let params = params.clone().backend(backend);
+        for width in [4096u32, 8192, 16384] {
+            let size = (width, width / 2);
+            let pano = prelude::Mat::new_rows_cols_with_default(size.1 as i32, size.0 as i32, opencv::core::CV_8UC3, opencv::core::Scalar::all(128.0))?;
+            let build = time(|| Projector::new(&params, size).map(drop))?;
+            let projector = Projector::new(&params, size)?;
+            let mut dst = prelude::Mat::default();
+            let remap = time(|| projector.apply_into(&pano, &mut dst))?;
+            println!(
+                "{:<8} {:>10} {:>12.2} {:>12.2} {:>10.1}",
+                format!("{backend:?}"),
+                format!("{}x{}", size.0, size.1),
+                build.as_secs_f64() * 1e3,
+                remap.as_secs_f64() * 1e3,
+                1.0 / remap.as_secs_f64()
+            );
+        }
+    }
+    Ok(())
+}
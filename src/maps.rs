@@ -0,0 +1,585 @@
+use std::sync::OnceLock;
+
+use ndarray::Zip;
+use opencv::prelude;
+#[cfg(feature = "cuda")]
+use opencv::core::{GpuMat, Stream};
+use opencv::core::{UMat, UMatUsageFlags};
+#[cfg(feature = "cuda")]
+use opencv::prelude::{GpuMatTrait, GpuMatTraitConst, StreamTrait};
+use opencv::prelude::{MatTrait, MatTraitConst, MatTraitConstManual, UMatTraitConst};
+
+use crate::coords;
+use crate::error::{Error, Result};
+#[cfg(feature = "simd")]
+use crate::fastmath;
+use crate::interop;
+use crate::output::{self, OutputFormat};
+use crate::params::PerspectiveParams;
+use crate::perspective::CroppedPanoParams;
+use crate::pool;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::source::SourceProjection;
+use crate::stats::{self, Stage};
+use crate::table::RayTable;
+#[cfg(feature = "cuda")]
+use crate::sampling::Interpolation;
+use crate::sampling::{Backend, Border, Precision, RenderOptions};
+
+/// Sampling maps for one output: the source x and y coordinate of every
+/// output pixel, plus a mask of pixels with no source direction.
+pub(crate) struct Maps {
+    pub(crate) map_x: ndarray::Array2<f32>,
+    pub(crate) map_y: ndarray::Array2<f32>,
+    /// 255 where the projection has no direction for the pixel.
+    pub(crate) invalid: Option<ndarray::Array2<u8>>,
+}
+
+impl Maps {
+    /// 255 where the output pixel samples inside a source of `source_size`
+    /// `(width, height)`, 0 where it has no source position or falls off the
+    /// source's edges. Sources that wrap horizontally cover every column.
+    pub(crate) fn coverage(&self, source_size: (u32, u32), wraps: bool) -> ndarray::Array2<u8> {
+        let (width, height) = (source_size.0 as f32, source_size.1 as f32);
+        let mut mask = ndarray::Array2::<u8>::zeros(self.map_x.dim());
+        Zip::from(&mut mask).and(&self.map_x).and(&self.map_y).for_each(|m, &x, &y| {
+            let inside_x = wraps || (-0.5..=width - 0.5).contains(&x);
+            if inside_x && (-0.5..=height - 0.5).contains(&y) {
+                *m = 255;
+            }
+        });
+        if let Some(invalid) = &self.invalid {
+            Zip::from(&mut mask).and(invalid).for_each(|m, &bad| *m &= !bad);
+        }
+        mask
+    }
+}
+
+fn full_size(pano_size: (usize, usize), crop: Option<&CroppedPanoParams>) -> (usize, usize) {
+    match crop {
+        Some(crop) => (crop.full_height as usize, crop.full_width as usize),
+        None => pano_size,
+    }
+}
+
+/// Moves full-frame coordinates into the frame of a cropped panorama.
+fn apply_crop(x: f32, y: f32, crop: &CroppedPanoParams) -> (f32, f32) {
+    ((x - crop.left as f32).rem_euclid(crop.full_width as f32), y - crop.top as f32)
+}
+
+/// Equirectangular pixel sampled for world direction `dir`, matching
+/// [`xyz_to_lonlat`](crate::xyz_to_lonlat) followed by
+/// [`lonlat_to_xy`](crate::lonlat_to_xy).
+pub(crate) fn sample_point(dir: Vec3, pano_size: (usize, usize), crop: Option<&CroppedPanoParams>) -> (f32, f32) {
+    let norm = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+    let lon = dir[0].atan2(dir[2]);
+    let lat = (dir[1] / norm).asin();
+    let (h, w) = full_size(pano_size, crop);
+    let x = ((lon / (2.0 * std::f64::consts::PI) + 0.5) * ((w as f64) - 1.0)) as f32;
+    let y = ((lat / std::f64::consts::PI + 0.5) * ((h as f64) - 1.0)) as f32;
+    match crop {
+        Some(crop) => apply_crop(x, y, crop),
+        None => (x, y),
+    }
+}
+
+/// [`sample_point`] in single precision.
+fn sample_point_f32(dir: [f32; 3], pano_size: (usize, usize), crop: Option<&CroppedPanoParams>) -> (f32, f32) {
+    let norm = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+    let lon = dir[0].atan2(dir[2]);
+    let lat = (dir[1] / norm).clamp(-1.0, 1.0).asin();
+    let (h, w) = full_size(pano_size, crop);
+    let x = (lon / (2.0 * std::f32::consts::PI) + 0.5) * (w as f32 - 1.0);
+    let y = (lat / std::f32::consts::PI + 0.5) * (h as f32 - 1.0);
+    match crop {
+        Some(crop) => apply_crop(x, y, crop),
+        None => (x, y),
+    }
+}
+
+/// Builds the sampling maps of any output projection over any source, one
+/// pixel at a time.
+pub(crate) fn build_maps<P, S>(projection: &P, source: &S) -> Maps
+where
+    P: Projection + ?Sized,
+    S: SourceProjection + ?Sized,
+{
+    let _timer = stats::time(Stage::MapBuild);
+    let (width, height) = projection.output_size();
+    let shape = (height as usize, width as usize);
+    let mut map_x = ndarray::Array2::<f32>::zeros(shape);
+    let mut map_y = ndarray::Array2::<f32>::zeros(shape);
+    let mut invalid = ndarray::Array2::<u8>::zeros(shape);
+
+    Zip::indexed(&mut map_x)
+        .and(&mut map_y)
+        .and(&mut invalid)
+        .for_each(|(v, u), x, y, bad| {
+            match projection.direction_for_pixel(u as f64, v as f64).and_then(|dir| source.pixel_for_direction(dir)) {
+                Some(xy) => (*x, *y) = xy,
+                None => *bad = 255,
+            }
+        });
+
+    let any_invalid = invalid.iter().any(|&b| b != 0);
+    Maps {
+        map_x,
+        map_y,
+        invalid: any_invalid.then_some(invalid),
+    }
+}
+
+/// Equirectangular sampling maps of a perspective view for a panorama of
+/// `pano_size` `(height, width)`, or for the crop described by `crop`. Each
+/// pixel goes straight from `R · K⁻¹ · [u, v, 1]`, undistorted in between
+/// when the view has lens distortion, to its source coordinates.
+pub(crate) fn perspective_maps(
+    params: &PerspectiveParams,
+    rotation: &Matrix3,
+    pano_size: (usize, usize),
+    crop: Option<&CroppedPanoParams>,
+) -> Result<Maps> {
+    perspective_band_maps(params, rotation, pano_size, crop, 0..params.height as usize)
+}
+
+/// [`perspective_maps`] for output rows `rows` only.
+pub(crate) fn perspective_band_maps(
+    params: &PerspectiveParams,
+    rotation: &Matrix3,
+    pano_size: (usize, usize),
+    crop: Option<&CroppedPanoParams>,
+    rows: std::ops::Range<usize>,
+) -> Result<Maps> {
+    let _timer = stats::time(Stage::MapBuild);
+    let k = params.intrinsics();
+    if k[0][0] == 0.0 || k[1][1] == 0.0 {
+        return Err(Error::InvalidParameter(format!("intrinsics {k:?} are not invertible")));
+    }
+    let distortion = params.distortion_coefficients();
+    let shape = (rows.len(), params.width as usize);
+    let mut map_x = ndarray::Array2::<f32>::zeros(shape);
+    let mut map_y = ndarray::Array2::<f32>::zeros(shape);
+    // Without distortion R · K⁻¹ is affine in (u, v): a direction at the
+    // origin plus one step per column and per row.
+    let world = |u: f64, v: f64| rotation::apply(rotation, coords::unproject_lens(&k, None, u, v));
+    let origin = world(0.0, 0.0);
+    let (along_u, along_v) = (world(1.0, 0.0), world(0.0, 1.0));
+    let steps: [[f32; 3]; 3] = std::array::from_fn(|i| [origin[i] as f32, (along_u[i] - origin[i]) as f32, (along_v[i] - origin[i]) as f32]);
+    #[cfg(feature = "simd")]
+    if let (Precision::Simd(accuracy), None) = (params.precision, distortion) {
+        let frame_size = full_size(pano_size, crop);
+        let row = |row: usize, mut xs: ndarray::ArrayViewMut1<f32>, mut ys: ndarray::ArrayViewMut1<f32>| {
+            let v = (rows.start + row) as f32;
+            for start in (0..xs.len()).step_by(8) {
+                let u = wide::f32x8::new(std::array::from_fn(|lane| (start + lane) as f32));
+                let dir = steps.map(|[o, du, dv]| wide::f32x8::splat(o + v * dv) + u * wide::f32x8::splat(du));
+                let (px, py) = fastmath::sample_points(dir, frame_size, accuracy);
+                let (px, py) = (px.to_array(), py.to_array());
+                for lane in 0..8.min(xs.len() - start) {
+                    (xs[start + lane], ys[start + lane]) = match crop {
+                        Some(crop) => apply_crop(px[lane], py[lane], crop),
+                        None => (px[lane], py[lane]),
+                    };
+                }
+            }
+        };
+        #[cfg(feature = "parallel")]
+        Zip::indexed(map_x.rows_mut()).and(map_y.rows_mut()).par_for_each(row);
+        #[cfg(not(feature = "parallel"))]
+        Zip::indexed(map_x.rows_mut()).and(map_y.rows_mut()).for_each(row);
+        return Ok(Maps {
+            map_x,
+            map_y,
+            invalid: None,
+        });
+    }
+    let single = params.precision != Precision::F64 && distortion.is_none();
+    let pixel = |(row, u): (usize, usize), x: &mut f32, y: &mut f32| {
+        let v = rows.start + row;
+        (*x, *y) = if single {
+            let dir = steps.map(|[o, du, dv]| o + u as f32 * du + v as f32 * dv);
+            sample_point_f32(dir, pano_size, crop)
+        } else {
+            let ray = coords::unproject_lens(&k, distortion.as_ref(), u as f64, v as f64);
+            sample_point(rotation::apply(rotation, ray), pano_size, crop)
+        };
+    };
+    #[cfg(feature = "parallel")]
+    Zip::indexed(&mut map_x).and(&mut map_y).par_for_each(pixel);
+    #[cfg(not(feature = "parallel"))]
+    Zip::indexed(&mut map_x).and(&mut map_y).for_each(pixel);
+    Ok(Maps {
+        map_x,
+        map_y,
+        invalid: None,
+    })
+}
+
+/// [`perspective_maps`] from the camera rays of `table`.
+pub(crate) fn table_maps(table: &RayTable, rotation: &Matrix3, pano_size: (usize, usize), crop: Option<&CroppedPanoParams>) -> Maps {
+    let _timer = stats::time(Stage::MapBuild);
+    let (width, height) = table.output_size();
+    let shape = (height as usize, width as usize);
+    let mut map_x = ndarray::Array2::<f32>::zeros(shape);
+    let mut map_y = ndarray::Array2::<f32>::zeros(shape);
+    let pixel = |(v, u): (usize, usize), x: &mut f32, y: &mut f32| {
+        (*x, *y) = sample_point(rotation::apply(rotation, table.ray(u, v)), pano_size, crop);
+    };
+    #[cfg(feature = "parallel")]
+    Zip::indexed(&mut map_x).and(&mut map_y).par_for_each(pixel);
+    #[cfg(not(feature = "parallel"))]
+    Zip::indexed(&mut map_x).and(&mut map_y).for_each(pixel);
+    Maps {
+        map_x,
+        map_y,
+        invalid: None,
+    }
+}
+
+pub(crate) fn to_mat<T: opencv::core::DataType>(map: &ndarray::Array2<T>) -> Result<prelude::Mat> {
+    Ok(interop::array_mat(map)?.try_clone()?)
+}
+
+pub(crate) fn to_array(mat: &prelude::Mat) -> Result<ndarray::Array2<f32>> {
+    Ok(interop::mat_view::<f32>(mat)?.to_owned())
+}
+
+/// Sampling maps uploaded to OpenCV, ready to be applied to frames.
+pub(crate) struct RemapMaps {
+    pub(crate) map_x: prelude::Mat,
+    pub(crate) map_y: prelude::Mat,
+    pub(crate) invalid: Option<prelude::Mat>,
+    /// `CV_16SC2` and `CV_16UC1` copies of the maps from `convertMaps`,
+    /// used by CPU remaps when set.
+    fixed: Option<(prelude::Mat, prelude::Mat)>,
+    /// The part of the source the maps read, on first CPU remap.
+    culled: OnceLock<Option<Culled>>,
+    /// `map_x` and `map_y` uploaded for [`Backend::OpenCl`], on first use.
+    device: OnceLock<(UMat, UMat)>,
+    /// The maps and mask uploaded for [`Backend::Cuda`], on first use.
+    #[cfg(feature = "cuda")]
+    cuda: OnceLock<CudaMaps>,
+}
+
+/// Maps shifted to sample a source ROI holding every pixel they read, so
+/// narrow views of huge panoramas only stream the rows and columns they
+/// touch through the caches.
+struct Culled {
+    rect: opencv::core::Rect,
+    map_x: prelude::Mat,
+    map_y: prelude::Mat,
+}
+
+impl Culled {
+    fn touches_edge(&self, src: &prelude::Mat) -> bool {
+        let rect = self.rect;
+        rect.x == 0 || rect.y == 0 || rect.x + rect.width >= src.cols() || rect.y + rect.height >= src.rows()
+    }
+}
+
+#[cfg(feature = "cuda")]
+struct CudaMaps {
+    map_x: GpuMat,
+    map_y: GpuMat,
+    invalid: Option<GpuMat>,
+}
+
+// SAFETY: the `Mat` fields are `Sync` already; `UMat` and `GpuMat` are not,
+// hence this impl. Those copies are uploaded once behind `OnceLock` and then
+// only passed to OpenCV as input arrays. Kernels only read the device
+// buffers, and the bookkeeping a shared read touches in OpenCV (reference
+// counts, `UMatData`'s host/device sync flags) uses atomics or is guarded by
+// `UMatData`'s own lock, so concurrent remaps from one set of device maps
+// never race.
+unsafe impl Sync for RemapMaps {}
+
+impl RemapMaps {
+    pub(crate) fn new(maps: &Maps) -> Result<RemapMaps> {
+        Ok(RemapMaps {
+            map_x: to_mat(&maps.map_x)?,
+            map_y: to_mat(&maps.map_y)?,
+            invalid: maps.invalid.as_ref().map(to_mat).transpose()?,
+            fixed: None,
+            culled: OnceLock::new(),
+            device: OnceLock::new(),
+            #[cfg(feature = "cuda")]
+            cuda: OnceLock::new(),
+        })
+    }
+
+    /// Adds `dx` to every source column, wrapping into `[0, period)`, and
+    /// drops the device copies so the next remap uploads the shifted maps.
+    pub(crate) fn shift_x(&mut self, dx: f32, period: f32) -> Result<()> {
+        interop::mat_view_mut::<f32>(&mut self.map_x)?.mapv_inplace(|x| (x + dx).rem_euclid(period));
+        if let Some((_, fractions)) = &self.fixed {
+            self.set_fixed_point(fractions.empty())?;
+        }
+        self.culled = OnceLock::new();
+        self.device = OnceLock::new();
+        #[cfg(feature = "cuda")]
+        {
+            self.cuda = OnceLock::new();
+        }
+        Ok(())
+    }
+
+    /// Adds fixed-point copies of the maps for CPU remaps: source positions
+    /// rounded to 1/32 pixel, or to whole pixels for `nearest`, which OpenCV
+    /// samples noticeably faster than `f32` maps.
+    pub(crate) fn set_fixed_point(&mut self, nearest: bool) -> Result<()> {
+        let (mut positions, mut fractions) = (prelude::Mat::default(), prelude::Mat::default());
+        opencv::imgproc::convert_maps(&self.map_x, &self.map_y, &mut positions, &mut fractions, opencv::core::CV_16SC2, nearest)?;
+        self.fixed = Some((positions, fractions));
+        Ok(())
+    }
+
+    pub(crate) fn is_fixed_point(&self) -> bool {
+        self.fixed.is_some()
+    }
+
+    /// The culled maps for `src`, when the maps read less than half of it.
+    fn culled(&self, src: &prelude::Mat) -> Result<Option<&Culled>> {
+        if let Some(culled) = self.culled.get() {
+            // Built for the first source seen; others of another size use
+            // the full maps.
+            let fits = culled.as_ref().is_some_and(|c| c.rect.x + c.rect.width <= src.cols() && c.rect.y + c.rect.height <= src.rows());
+            return Ok(culled.as_ref().filter(|_| fits));
+        }
+        let (cols, rows) = (src.cols(), src.rows());
+        let bounds = |map: &prelude::Mat, size: i32| -> Result<(i32, i32)> {
+            let (lo, hi) = interop::mat_view::<f32>(map)?
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            // Interpolation kernels reach up to four pixels past a sample.
+            let lo = (lo.floor() as i32 - 4).clamp(0, size - 1);
+            Ok((lo, (hi.ceil() as i32 + 4).clamp(lo, size - 1)))
+        };
+        let ((x0, x1), (y0, y1)) = (bounds(&self.map_x, cols)?, bounds(&self.map_y, rows)?);
+        let rect = opencv::core::Rect::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1);
+        let culled = if 2 * rect.area() as i64 <= cols as i64 * rows as i64 {
+            let (mut map_x, mut map_y) = (prelude::Mat::default(), prelude::Mat::default());
+            self.map_x.convert_to(&mut map_x, opencv::core::CV_32F, 1.0, -x0 as f64)?;
+            self.map_y.convert_to(&mut map_y, opencv::core::CV_32F, 1.0, -y0 as f64)?;
+            Some(Culled { rect, map_x, map_y })
+        } else {
+            None
+        };
+        Ok(self.culled.get_or_init(|| culled).as_ref())
+    }
+
+    fn device_maps(&self) -> Result<&(UMat, UMat)> {
+        if let Some(maps) = self.device.get() {
+            return Ok(maps);
+        }
+        let upload = |map: &prelude::Mat| -> Result<UMat> {
+            let mut device = UMat::new(UMatUsageFlags::USAGE_DEFAULT);
+            map.copy_to(&mut device)?;
+            Ok(device)
+        };
+        let maps = (upload(&self.map_x)?, upload(&self.map_y)?);
+        Ok(self.device.get_or_init(|| maps))
+    }
+
+    #[cfg(feature = "cuda")]
+    fn cuda_maps(&self) -> Result<&CudaMaps> {
+        if let Some(maps) = self.cuda.get() {
+            return Ok(maps);
+        }
+        let upload = |map: &prelude::Mat| -> Result<GpuMat> {
+            let mut device = GpuMat::new_def()?;
+            device.upload(map)?;
+            Ok(device)
+        };
+        let maps = CudaMaps {
+            map_x: upload(&self.map_x)?,
+            map_y: upload(&self.map_y)?,
+            invalid: self.invalid.as_ref().map(upload).transpose()?,
+        };
+        Ok(self.cuda.get_or_init(|| maps))
+    }
+
+    /// `remap` between device-resident frames on `stream`, including the
+    /// fill of pixels without a source direction. CUDA has no Lanczos
+    /// kernel or transparent border, so those fall back to cubic sampling
+    /// and a zero border.
+    #[cfg(feature = "cuda")]
+    pub(crate) fn remap_cuda(&self, src: &GpuMat, options: &RenderOptions, dst: &mut GpuMat, stream: &mut Stream) -> Result<()> {
+        let maps = self.cuda_maps()?;
+        let interpolation = match options.interpolation {
+            Interpolation::Lanczos4 => Interpolation::Cubic,
+            other => other,
+        };
+        let (border_mode, border_value) = match options.border {
+            Border::Transparent => (opencv::core::BORDER_CONSTANT, opencv::core::Scalar::all(0.0)),
+            border => border.to_cv(src.channels()?),
+        };
+        opencv::cudawarping::remap(src, dst, &maps.map_x, &maps.map_y, interpolation.to_cv(), border_mode, border_value, stream)?;
+        if let Some(invalid) = &maps.invalid {
+            dst.set_to_3(options.border.fill_value(src.channels()?), invalid, stream)?;
+        }
+        Ok(())
+    }
+
+    /// [`remap_cuda`](Self::remap_cuda) for a host frame, uploading it and
+    /// downloading the result.
+    #[cfg(feature = "cuda")]
+    fn remap_on_cuda(&self, src: &prelude::Mat, options: &RenderOptions, dst: &mut prelude::Mat) -> Result<()> {
+        let mut stream = Stream::default()?;
+        let mut device_src = GpuMat::new_def()?;
+        device_src.upload(src)?;
+        let mut device_dst = GpuMat::new_def()?;
+        self.remap_cuda(&device_src, options, &mut device_dst, &mut stream)?;
+        stream.wait_for_completion()?;
+        device_dst.download(dst)?;
+        Ok(())
+    }
+
+    /// `remap` through OpenCV's transparent API; the result lands back in
+    /// `dst` on the host.
+    fn remap_on_device(&self, src: &prelude::Mat, options: &RenderOptions, dst: &mut prelude::Mat) -> Result<()> {
+        let (map_x, map_y) = self.device_maps()?;
+        let (border_mode, border_value) = options.border.to_cv(src.channels());
+        let mut device_src = UMat::new(UMatUsageFlags::USAGE_DEFAULT);
+        src.copy_to(&mut device_src)?;
+        let mut device_dst = UMat::new(UMatUsageFlags::USAGE_DEFAULT);
+        if options.border == Border::Transparent {
+            dst.copy_to(&mut device_dst)?;
+        }
+        opencv::imgproc::remap(
+            &device_src,
+            &mut device_dst,
+            map_x,
+            map_y,
+            options.interpolation.to_cv(),
+            border_mode,
+            border_value,
+        )?;
+        device_dst.copy_to(dst)?;
+        Ok(())
+    }
+
+    /// Remaps into `dst`, reusing its allocation when it already has the
+    /// output size and type, and fills pixels without a source direction.
+    pub(crate) fn remap_into(&self, src: &prelude::Mat, options: &RenderOptions, dst: &mut prelude::Mat) -> Result<()> {
+        let _timer = stats::time(Stage::Remap);
+        let (border_mode, border_value) = options.border.to_cv(src.channels());
+        let reusable = dst.rows() == self.map_x.rows() && dst.cols() == self.map_x.cols() && dst.typ() == src.typ();
+        if options.border == Border::Transparent && !reusable {
+            *dst = prelude::Mat::new_rows_cols_with_default(self.map_x.rows(), self.map_x.cols(), src.typ(), opencv::core::Scalar::all(0.0))?;
+        }
+        match options.effective_backend() {
+            Backend::OpenCl if Backend::OpenCl.is_available() => self.remap_on_device(src, options, dst)?,
+            #[cfg(feature = "cuda")]
+            Backend::Cuda if Backend::Cuda.is_available() => self.remap_on_cuda(src, options, dst)?,
+            _ => {
+                let culled = if self.fixed.is_none() { self.culled(src)? } else { None };
+                // Wrapping inside the ROI would wrap to its own opposite
+                // edge instead of the panorama's.
+                let culled = culled.filter(|culled| options.border != Border::Wrap || !culled.touches_edge(src));
+                let source = culled.map(|culled| prelude::Mat::roi(src, culled.rect)).transpose()?;
+                let (map1, map2) = match (&self.fixed, culled) {
+                    (Some((positions, fractions)), _) => (positions, fractions),
+                    (None, Some(culled)) => (&culled.map_x, &culled.map_y),
+                    (None, None) => (&self.map_x, &self.map_y),
+                };
+                let source = source.as_deref().unwrap_or(src);
+                let (rows, cols) = (map1.rows(), map1.cols());
+                let band = options.band_rows(cols as usize, src.elem_size()?) as i32;
+                if band >= rows {
+                    opencv::imgproc::remap(
+                        source, dst,
+                        map1,
+                        map2,
+                        options.interpolation.to_cv(),
+                        border_mode,
+                        border_value
+                    )?
+                } else {
+                    if dst.rows() != rows || dst.cols() != cols || dst.typ() != src.typ() {
+                        // SAFETY: the bands below write every pixel.
+                        unsafe { dst.create_rows_cols(rows, cols, src.typ())? };
+                    }
+                    for top in (0..rows).step_by(band as usize) {
+                        let rect = opencv::core::Rect::new(0, top, cols, band.min(rows - top));
+                        let mut out = prelude::Mat::roi_mut(dst, rect)?;
+                        let band_map1 = prelude::Mat::roi(map1, rect)?;
+                        // Nearest fixed-point maps have no fractions.
+                        let band_map2 = if map2.empty() { None } else { Some(prelude::Mat::roi(map2, rect)?) };
+                        opencv::imgproc::remap(
+                            source, &mut out,
+                            &*band_map1,
+                            band_map2.as_deref().unwrap_or(map2),
+                            options.interpolation.to_cv(),
+                            border_mode,
+                            border_value
+                        )?;
+                    }
+                }
+            }
+        }
+        if let Some(invalid) = &self.invalid {
+            dst.set_to(&options.border.fill_value(src.channels()), invalid)?;
+        }
+        Ok(())
+    }
+
+    /// Remaps and converts to the requested output format.
+    pub(crate) fn render_into(&self, src: &prelude::Mat, options: &RenderOptions, dst: &mut prelude::Mat) -> Result<()> {
+        if options.output_format == OutputFormat::Native {
+            return self.remap_into(src, options, dst);
+        }
+        let mut persp = pool::take_output(self.map_x.rows(), self.map_x.cols(), src.typ(), options.border)?;
+        self.remap_into(src, options, &mut persp)?;
+        output::convert_into(&persp, options.output_format, dst)?;
+        pool::recycle(persp);
+        Ok(())
+    }
+
+    pub(crate) fn render(&self, src: &prelude::Mat, options: &RenderOptions) -> Result<prelude::Mat> {
+        let mut dst = pool::take_output(self.map_x.rows(), self.map_x.cols(), src.typ(), options.border)?;
+        self.render_into(src, options, &mut dst)?;
+        Ok(dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Remaps an 8x8 patch starting at source column `x0` of a 64x32 source
+    /// through `RemapMaps` and through OpenCV with the full maps.
+    fn culled_and_full(x0: f32) -> (RemapMaps, prelude::Mat, prelude::Mat, prelude::Mat) {
+        let mut src = prelude::Mat::new_rows_cols_with_default(32, 64, opencv::core::CV_8UC1, opencv::core::Scalar::all(0.0)).unwrap();
+        for (i, value) in src.data_bytes_mut().unwrap().iter_mut().enumerate() {
+            *value = (i * 37 % 251) as u8;
+        }
+        let maps = Maps {
+            map_x: ndarray::Array2::from_shape_fn((8, 8), |(_, u)| x0 + u as f32 * 0.5),
+            map_y: ndarray::Array2::from_shape_fn((8, 8), |(v, _)| 12.25 + v as f32 * 0.5),
+            invalid: None,
+        };
+        let maps = RemapMaps::new(&maps).unwrap();
+        let options = RenderOptions::default();
+        let mut culled = prelude::Mat::default();
+        maps.remap_into(&src, &options, &mut culled).unwrap();
+        let (border_mode, border_value) = options.border.to_cv(src.channels());
+        let mut full = prelude::Mat::default();
+        opencv::imgproc::remap(&src, &mut full, &maps.map_x, &maps.map_y, options.interpolation.to_cv(), border_mode, border_value).unwrap();
+        (maps, src, culled, full)
+    }
+
+    #[test]
+    fn culled_views_match_full_maps() {
+        let (maps, src, culled, full) = culled_and_full(20.25);
+        assert!(maps.culled(&src).unwrap().is_some());
+        assert_eq!(culled.data_bytes().unwrap(), full.data_bytes().unwrap());
+    }
+
+    #[test]
+    fn views_across_the_seam_wrap_to_the_opposite_edge() {
+        // Cubic taps past column 63 must read columns 0 and 1.
+        let (_, _, culled, full) = culled_and_full(60.25);
+        assert_eq!(culled.data_bytes().unwrap(), full.data_bytes().unwrap());
+    }
+}
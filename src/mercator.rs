@@ -0,0 +1,165 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// Latitude cutoff of Web Mercator tiles, which makes the map square.
+pub const WEB_MERCATOR_MAX_LAT: f64 = 85.051_128_779_806_59;
+
+/// Which great circle the Mercator cylinder touches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MercatorAspect {
+    /// Tangent along the equator: longitude runs across the image and the
+    /// poles are cut at the maximum latitude.
+    #[default]
+    Normal,
+    /// Tangent along the central meridian, which runs vertically through
+    /// the image; the cutoff limits the angular distance from it.
+    Transverse,
+}
+
+/// A conformal Mercator map of the whole panorama. Shapes are preserved
+/// when the pixel scale matches on both axes, e.g. a square output with
+/// [`WEB_MERCATOR_MAX_LAT`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MercatorParams {
+    pub(crate) aspect: MercatorAspect,
+    pub(crate) max_lat: f64,
+    pub(crate) yaw: f64,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for MercatorParams {
+    fn default() -> Self {
+        MercatorParams::new()
+    }
+}
+
+impl MercatorParams {
+    pub fn new() -> MercatorParams {
+        MercatorParams {
+            aspect: MercatorAspect::default(),
+            max_lat: WEB_MERCATOR_MAX_LAT,
+            yaw: 0.0,
+            width: 1024,
+            height: 1024,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn transverse() -> MercatorParams {
+        MercatorParams::new().aspect(MercatorAspect::Transverse)
+    }
+
+    pub fn aspect(mut self, aspect: MercatorAspect) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// Latitude (or, for the transverse aspect, distance from the central
+    /// meridian) at the edge of the map, below 90 degrees.
+    pub fn max_lat(mut self, max_lat: impl Into<Angle>) -> Self {
+        self.max_lat = max_lat.into().degrees();
+        self
+    }
+
+    /// Longitude of the map center.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    pub fn output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.max_lat > 0.0 && self.max_lat < 90.0) {
+            return Err(Error::InvalidParameter(format!("max latitude must be in (0, 90) degrees, got {}", self.max_lat)));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<Mercator> {
+        self.validate()?;
+        let half_lat = 0.5 * self.max_lat.to_radians();
+        Ok(Mercator {
+            aspect: self.aspect,
+            rotation: rotation::euler_to_matrix(self.yaw, 0.0, 0.0),
+            width: self.width,
+            height: self.height,
+            y_max: (std::f64::consts::FRAC_PI_4 + half_lat).tan().ln(),
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+}
+
+/// A Mercator map resolved from [`MercatorParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mercator {
+    aspect: MercatorAspect,
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+    y_max: f64,
+}
+
+impl Projection for Mercator {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let s = (u + 0.5) / self.width as f64 - 0.5;
+        let t = 0.5 - (v + 0.5) / self.height as f64;
+        // Along and across the tangent circle, as fractions of the image.
+        let (along, across) = match self.aspect {
+            MercatorAspect::Normal => (s, t),
+            MercatorAspect::Transverse => (t, -s),
+        };
+        let lon = along * 360.0;
+        let lat = (2.0 * across * self.y_max).sinh().atan().to_degrees();
+        let ray = coords::lonlat_to_ray(lon, lat);
+        let ray = match self.aspect {
+            MercatorAspect::Normal => ray,
+            // Turns the auxiliary equator into the central meridian.
+            MercatorAspect::Transverse => [ray[1], -ray[0], ray[2]],
+        };
+        Some(rotation::apply(&self.rotation, ray))
+    }
+}
+
+impl Equirectangular {
+    /// Normal-aspect Mercator map of `output_size` (`(width, height)`) cut
+    /// at `max_lat` degrees.
+    pub fn to_mercator(&self, max_lat: f64, output_size: (u32, u32)) -> Result<prelude::Mat> {
+        let (width, height) = output_size;
+        MercatorParams::new()
+            .max_lat(Deg(max_lat))
+            .output_size(width, height)
+            .project(self)
+    }
+}
@@ -0,0 +1,206 @@
+use opencv::core::Vector;
+use opencv::imgproc;
+use opencv::prelude;
+use opencv::prelude::{MatTraitConst, MatTraitConstManual};
+
+use crate::error::{Error, Result};
+use crate::stats::{self, Stage};
+
+/// Pixel layout of projected images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputFormat {
+    /// Same depth and channels as the source panorama (BGR for color images).
+    #[default]
+    Native,
+    Bgr,
+    Rgb,
+    Rgba,
+    /// Single-channel `CV_32F` mat of `3 * height` rows holding the R, G and
+    /// B planes one after another (CHW), scaled to 0-1 for integer sources.
+    PlanarF32,
+}
+
+fn color_code(channels: i32, format: OutputFormat) -> Result<Option<i32>> {
+    let code = match (channels, format) {
+        (_, OutputFormat::Native) => None,
+        (1, OutputFormat::Bgr) => Some(imgproc::COLOR_GRAY2BGR),
+        (3, OutputFormat::Bgr) => None,
+        (4, OutputFormat::Bgr) => Some(imgproc::COLOR_BGRA2BGR),
+        (1, OutputFormat::Rgb | OutputFormat::PlanarF32) => Some(imgproc::COLOR_GRAY2RGB),
+        (3, OutputFormat::Rgb | OutputFormat::PlanarF32) => Some(imgproc::COLOR_BGR2RGB),
+        (4, OutputFormat::Rgb | OutputFormat::PlanarF32) => Some(imgproc::COLOR_BGRA2RGB),
+        (1, OutputFormat::Rgba) => Some(imgproc::COLOR_GRAY2RGBA),
+        (3, OutputFormat::Rgba) => Some(imgproc::COLOR_BGR2RGBA),
+        (4, OutputFormat::Rgba) => Some(imgproc::COLOR_BGRA2RGBA),
+        (channels, format) => {
+            return Err(Error::InvalidParameter(format!("cannot convert a {channels}-channel image to {format:?}")));
+        }
+    };
+    Ok(code)
+}
+
+fn unit_scale(depth: i32) -> f64 {
+    match depth {
+        opencv::core::CV_8U => 1.0 / 255.0,
+        opencv::core::CV_16U => 1.0 / 65535.0,
+        opencv::core::CV_16S => 1.0 / 32767.0,
+        _ => 1.0,
+    }
+}
+
+/// Converts a projected image to `format`, writing into `dst`.
+pub(crate) fn convert_into(src: &prelude::Mat, format: OutputFormat, dst: &mut prelude::Mat) -> Result<()> {
+    let code = color_code(src.channels(), format)?;
+    if format != OutputFormat::PlanarF32 {
+        match code {
+            Some(code) => imgproc::cvt_color(src, dst, code, 0)?,
+            None => src.copy_to(dst)?,
+        }
+        return Ok(());
+    }
+
+    let mut rgb = prelude::Mat::default();
+    match code {
+        Some(code) => imgproc::cvt_color(src, &mut rgb, code, 0)?,
+        None => rgb = src.try_clone()?,
+    }
+    let mut float = prelude::Mat::default();
+    rgb.convert_to(&mut float, opencv::core::CV_32F, unit_scale(src.depth()), 0.0)?;
+    let mut planes = Vector::<prelude::Mat>::new();
+    opencv::core::split(&float, &mut planes)?;
+    opencv::core::vconcat(&planes, dst)?;
+    Ok(())
+}
+
+/// Encoder settings for views written straight to files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EncodeOptions {
+    pub(crate) jpeg_quality: u8,
+    pub(crate) png_compression: u8,
+    pub(crate) band_rows: u32,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions::new()
+    }
+}
+
+impl EncodeOptions {
+    pub fn new() -> EncodeOptions {
+        EncodeOptions {
+            jpeg_quality: 95,
+            png_compression: 3,
+            band_rows: 256,
+        }
+    }
+
+    /// JPEG and WebP quality from 1 to 100.
+    pub fn jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = quality;
+        self
+    }
+
+    /// PNG compression level from 0 (none, fastest) to 9 (smallest).
+    pub fn png_compression(mut self, level: u8) -> Self {
+        self.png_compression = level;
+        self
+    }
+
+    /// Output rows rendered and handed to a streaming encoder at a time.
+    pub fn band_rows(mut self, rows: u32) -> Self {
+        self.band_rows = rows;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=100).contains(&self.jpeg_quality) {
+            return Err(Error::InvalidParameter(format!("JPEG quality must be in [1, 100], got {}", self.jpeg_quality)));
+        }
+        if self.png_compression > 9 {
+            return Err(Error::InvalidParameter(format!("PNG compression must be in [0, 9], got {}", self.png_compression)));
+        }
+        if self.band_rows == 0 {
+            return Err(Error::InvalidParameter("band height must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The settings as `imwrite` flags.
+    pub(crate) fn to_cv(&self) -> Vector<i32> {
+        Vector::from_slice(&[
+            opencv::imgcodecs::IMWRITE_JPEG_QUALITY,
+            self.jpeg_quality as i32,
+            opencv::imgcodecs::IMWRITE_WEBP_QUALITY,
+            self.jpeg_quality as i32,
+            opencv::imgcodecs::IMWRITE_PNG_COMPRESSION,
+            self.png_compression as i32,
+        ])
+    }
+
+    pub(crate) fn png_compression_level(&self) -> png::Compression {
+        match self.png_compression {
+            0 => png::Compression::NoCompression,
+            1 => png::Compression::Fastest,
+            2..=3 => png::Compression::Fast,
+            4..=6 => png::Compression::Balanced,
+            _ => png::Compression::High,
+        }
+    }
+}
+
+/// Streams 8-bit gray, BGR or BGRA rows into a PNG file as they are
+/// rendered, so the whole image never exists at once.
+pub(crate) struct PngStream {
+    writer: png::StreamWriter<'static, std::io::BufWriter<std::fs::File>>,
+}
+
+fn png_error(e: png::EncodingError) -> Error {
+    Error::Io(std::io::Error::other(e))
+}
+
+impl PngStream {
+    pub(crate) fn create(path: &std::path::Path, width: u32, height: u32, channels: i32, options: &EncodeOptions) -> Result<PngStream> {
+        let color = match channels {
+            1 => png::ColorType::Grayscale,
+            3 => png::ColorType::Rgb,
+            4 => png::ColorType::Rgba,
+            _ => return Err(Error::InvalidParameter(format!("cannot stream a {channels}-channel image to PNG"))),
+        };
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(options.png_compression_level());
+        let writer = encoder.write_header().map_err(png_error)?.into_stream_writer().map_err(png_error)?;
+        Ok(PngStream { writer })
+    }
+
+    /// Appends the rows of an 8-bit band in OpenCV's channel order.
+    pub(crate) fn write_band(&mut self, band: &prelude::Mat) -> Result<()> {
+        let _timer = stats::time(Stage::Encode);
+        let code = match band.channels() {
+            3 => Some(imgproc::COLOR_BGR2RGB),
+            4 => Some(imgproc::COLOR_BGRA2RGBA),
+            _ => None,
+        };
+        let mut rgb = prelude::Mat::default();
+        let rows = match code {
+            Some(code) => {
+                imgproc::cvt_color(band, &mut rgb, code, 0)?;
+                &rgb
+            }
+            None => band,
+        };
+        std::io::Write::write_all(&mut self.writer, rows.data_bytes()?)?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        let _timer = stats::time(Stage::Encode);
+        self.writer.finish().map_err(png_error)
+    }
+}
@@ -0,0 +1,212 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::annotate::{self, draw_polylines};
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::sampling::Border;
+
+/// A longitude/latitude grid with a highlighted horizon and heading labels,
+/// drawn over panoramas or projected views for debugging and survey-style
+/// output. Colors are RGBA.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Graticule {
+    pub(crate) spacing: f64,
+    pub(crate) color: [f64; 4],
+    pub(crate) horizon_color: [f64; 4],
+    pub(crate) thickness: i32,
+    pub(crate) labels: bool,
+    pub(crate) compass: bool,
+    pub(crate) font_scale: f64,
+}
+
+impl Default for Graticule {
+    fn default() -> Self {
+        Graticule::new()
+    }
+}
+
+impl Graticule {
+    pub fn new() -> Graticule {
+        Graticule {
+            spacing: 30.0,
+            color: [255.0, 255.0, 255.0, 255.0],
+            horizon_color: [255.0, 255.0, 0.0, 255.0],
+            thickness: 1,
+            labels: true,
+            compass: false,
+            font_scale: 0.5,
+        }
+    }
+
+    /// Degrees between grid lines in both longitude and latitude.
+    pub fn spacing(mut self, spacing: f64) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn color(mut self, color: [f64; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn horizon_color(mut self, color: [f64; 4]) -> Self {
+        self.horizon_color = color;
+        self
+    }
+
+    /// Line width in pixels; the horizon is drawn one pixel wider.
+    pub fn thickness(mut self, thickness: i32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Whether meridians are labeled with their heading where they cross
+    /// the horizon.
+    pub fn labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Labels headings 0, 90, 180 and 270 as N, E, S and W, for panoramas
+    /// whose longitude 0 faces north.
+    pub fn compass(mut self, compass: bool) -> Self {
+        self.compass = compass;
+        self
+    }
+
+    pub fn font_scale(mut self, scale: f64) -> Self {
+        self.font_scale = scale;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.spacing > 0.0 && self.spacing <= 180.0) {
+            return Err(Error::InvalidParameter(format!("grid spacing must be in (0, 180] degrees, got {}", self.spacing)));
+        }
+        if self.thickness < 1 {
+            return Err(Error::InvalidParameter(format!("line thickness must be at least 1, got {}", self.thickness)));
+        }
+        if !(self.font_scale > 0.0 && self.font_scale.is_finite()) {
+            return Err(Error::InvalidParameter(format!("font scale must be positive, got {}", self.font_scale)));
+        }
+        Ok(())
+    }
+
+    fn meridians(&self) -> Vec<f64> {
+        let count = (360.0 / self.spacing).floor() as usize;
+        (0..count).map(|i| -180.0 + i as f64 * self.spacing).collect()
+    }
+
+    /// Parallels strictly between the poles, horizon excluded.
+    fn parallels(&self) -> Vec<f64> {
+        let count = (90.0 / self.spacing).ceil() as i64;
+        (-count..=count)
+            .map(|i| i as f64 * self.spacing)
+            .filter(|&lat| lat != 0.0 && lat.abs() < 90.0)
+            .collect()
+    }
+
+    fn label(&self, lon: f64) -> String {
+        let heading = lon.rem_euclid(360.0).round() as i64 % 360;
+        match (self.compass, heading) {
+            (true, 0) => "N".to_string(),
+            (true, 90) => "E".to_string(),
+            (true, 180) => "S".to_string(),
+            (true, 270) => "W".to_string(),
+            _ => heading.to_string(),
+        }
+    }
+
+    fn put_label(&self, image: &mut prelude::Mat, text: &str, (x, y): (f64, f64), color: opencv::core::Scalar) -> Result<()> {
+        let origin = opencv::core::Point::new(x.round() as i32 + 3, y.round() as i32 - 4);
+        opencv::imgproc::put_text(
+            image,
+            text,
+            origin,
+            opencv::imgproc::FONT_HERSHEY_SIMPLEX,
+            self.font_scale,
+            color,
+            self.thickness,
+            opencv::imgproc::LINE_AA,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Draws onto `image`, a `pano_size` equirectangular frame or a crop of
+    /// one at `offset`.
+    pub fn draw_equirect(&self, image: &mut prelude::Mat, pano_size: (u32, u32), offset: (u32, u32)) -> Result<()> {
+        self.validate()?;
+        let channels = image.channels();
+        let (color, horizon) = (Border::Constant(self.color).to_cv(channels).1, Border::Constant(self.horizon_color).to_cv(channels).1);
+        let pixel = |lon: f64, lat: f64| coords::lonlat_to_equirect(lon, lat, pano_size.0, pano_size.1);
+        let mut lines: Vec<Vec<(f64, f64)>> = self.meridians().into_iter().map(|lon| vec![pixel(lon, 90.0), pixel(lon, -90.0)]).collect();
+        lines.push(vec![pixel(180.0, 90.0), pixel(180.0, -90.0)]);
+        lines.extend(self.parallels().into_iter().map(|lat| vec![pixel(-180.0, lat), pixel(180.0, lat)]));
+        // As with footprints, a crop may hold the frame's right end on its left.
+        for shift in [0.0, pano_size.0 as f64 - 1.0] {
+            let origin = (offset.0 as f64 - shift, offset.1 as f64);
+            draw_polylines(image, &lines, origin, color, self.thickness)?;
+            draw_polylines(image, &[vec![pixel(-180.0, 0.0), pixel(180.0, 0.0)]], origin, horizon, self.thickness + 1)?;
+            if self.labels {
+                for lon in self.meridians() {
+                    let (x, y) = pixel(lon, 0.0);
+                    self.put_label(image, &self.label(lon), (x - origin.0, y - origin.1), horizon)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws onto `image`, a view rendered with `params`.
+    pub fn draw_view(&self, image: &mut prelude::Mat, params: &PerspectiveParams) -> Result<()> {
+        self.validate()?;
+        let channels = image.channels();
+        let (color, horizon) = (Border::Constant(self.color).to_cv(channels).1, Border::Constant(self.horizon_color).to_cv(channels).1);
+        let around = |lat: f64| (0..=4).map(|i| (-180.0 + 90.0 * i as f64, lat)).collect::<Vec<_>>();
+        let mut lines = Vec::new();
+        for lon in self.meridians() {
+            lines.extend(annotate::project_polyline(params, &[(lon, 90.0), (lon, 0.0), (lon, -90.0)])?);
+        }
+        for lat in self.parallels() {
+            lines.extend(annotate::project_polyline(params, &around(lat))?);
+        }
+        draw_polylines(image, &lines, (0.0, 0.0), color, self.thickness)?;
+        draw_polylines(image, &annotate::project_polyline(params, &around(0.0))?, (0.0, 0.0), horizon, self.thickness + 1)?;
+        if self.labels {
+            let meridians = self.meridians();
+            let crossings: Vec<_> = meridians.iter().map(|&lon| (lon, 0.0)).collect();
+            for (lon, point) in meridians.iter().zip(annotate::project_points(params, &crossings)?) {
+                if let (Some(pixel), true) = (point.pixel, point.in_view) {
+                    self.put_label(image, &self.label(*lon), pixel, horizon)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders `params` from `pano` with the grid drawn over it.
+    pub fn render_view(&self, pano: &Equirectangular, params: &PerspectiveParams) -> Result<prelude::Mat> {
+        let mut image = params.project(pano)?;
+        self.draw_view(&mut image, params)?;
+        Ok(image)
+    }
+}
+
+impl Equirectangular {
+    /// Copy of the panorama with `graticule` drawn over it.
+    pub fn draw_graticule(&self, graticule: &Graticule) -> Result<prelude::Mat> {
+        let mut image = self.as_mat().try_clone()?;
+        let (pano_size, offset) = match self.crop() {
+            Some(crop) => ((crop.full_width, crop.full_height), (crop.left, crop.top)),
+            None => ((self.width(), self.height()), (0, 0)),
+        };
+        graticule.draw_equirect(&mut image, pano_size, offset)?;
+        Ok(image)
+    }
+}
@@ -0,0 +1,48 @@
+use opencv::core::Vector;
+use opencv::prelude;
+
+use crate::angle::Deg;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+
+impl Equirectangular {
+    /// Views of a `rows`x`cols` virtual camera rig, each with a `fov` degree
+    /// square field of view. Rows are evenly spaced in pitch from the top
+    /// down and columns in yaw from -180 degrees, in row-major order.
+    pub fn overview_views(rows: u32, cols: u32, fov: f64, cell_size: u32) -> Vec<PerspectiveParams> {
+        (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let pitch = 90.0 - 180.0 * (row as f64 + 0.5) / rows as f64;
+                let yaw = -180.0 + 360.0 * (col as f64 + 0.5) / cols as f64;
+                PerspectiveParams::new()
+                    .fov(Deg(fov))
+                    .yaw(Deg(yaw))
+                    .pitch(Deg(pitch))
+                    .output_size(cell_size, cell_size)
+            })
+            .collect()
+    }
+
+    /// Contact sheet of [`overview_views`](Self::overview_views) covering
+    /// the whole sphere in one image as wide as the panorama.
+    pub fn render_overview(&self, rows: u32, cols: u32, fov: f64) -> Result<prelude::Mat> {
+        if rows == 0 || cols == 0 {
+            return Err(Error::InvalidParameter(format!("overview grid {rows}x{cols} must not be empty")));
+        }
+        let cell_size = (self.width() / cols).max(1);
+        let mut views = self.get_perspectives(&Equirectangular::overview_views(rows, cols, fov, cell_size))?.into_iter();
+
+        let mut sheet_rows = Vector::<prelude::Mat>::new();
+        for _ in 0..rows {
+            let row = Vector::<prelude::Mat>::from_iter(views.by_ref().take(cols as usize));
+            let mut joined = prelude::Mat::default();
+            opencv::core::hconcat(&row, &mut joined)?;
+            sheet_rows.push(joined);
+        }
+        let mut sheet = prelude::Mat::default();
+        opencv::core::vconcat(&sheet_rows, &mut sheet)?;
+        Ok(sheet)
+    }
+}
@@ -0,0 +1,167 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// A Pannini (vedutismo) view: the sphere is mapped onto a vertical
+/// cylinder and then projected onto the image plane from a point
+/// `compression` cylinder radii behind its axis. Verticals and radial lines
+/// stay straight, so very wide interiors look natural. A compression of 0
+/// is rectilinear and 1 is the classic Pannini.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PanniniParams {
+    pub(crate) fov: f64,
+    pub(crate) compression: f64,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for PanniniParams {
+    fn default() -> Self {
+        PanniniParams::new()
+    }
+}
+
+impl PanniniParams {
+    pub fn new() -> PanniniParams {
+        PanniniParams {
+            fov: 150.0,
+            compression: 1.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            width: 1920,
+            height: 1080,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Horizontal field of view. With compression `d` it must stay below
+    /// the angle where `d + cos(fov / 2)` reaches 0, i.e. 360 degrees for
+    /// `d >= 1`.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    pub fn compression(mut self, compression: f64) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    /// Positive values look up. Verticals only stay straight at 0.
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    pub fn output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.compression >= 0.0 && self.compression.is_finite()) {
+            return Err(Error::InvalidParameter(format!("compression must be non-negative, got {}", self.compression)));
+        }
+        let in_range = self.fov > 0.0 && self.fov < 360.0 && self.compression + (0.5 * self.fov.to_radians()).cos() > 1e-6;
+        if !in_range {
+            return Err(Error::InvalidParameter(format!(
+                "fov {} degrees is too wide for compression {}",
+                self.fov, self.compression
+            )));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<Pannini> {
+        self.validate()?;
+        let d = self.compression;
+        let (sin, cos) = (0.5 * self.fov.to_radians()).sin_cos();
+        Ok(Pannini {
+            rotation: rotation::euler_to_matrix(self.yaw, self.pitch, self.roll),
+            width: self.width,
+            height: self.height,
+            compression: d,
+            half_width: (d + 1.0) * sin / (d + cos),
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+}
+
+/// A Pannini view resolved from [`PanniniParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pannini {
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+    compression: f64,
+    half_width: f64,
+}
+
+impl Projection for Pannini {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let x = (2.0 * (u + 0.5) / width - 1.0) * self.half_width;
+        let y = (2.0 * (v + 0.5) - height) / width * self.half_width;
+        let d = self.compression;
+        // Inverse of x = S sin(lon), S = (d + 1) / (d + cos(lon)).
+        let k = x * x / ((d + 1.0) * (d + 1.0));
+        let discriminant = k * k * d * d - (k + 1.0) * (k * d * d - 1.0);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let cos_lon = (-k * d + discriminant.sqrt()) / (k + 1.0);
+        let s = (d + 1.0) / (d + cos_lon);
+        let lon = x.atan2(s * cos_lon);
+        Some(rotation::apply(&self.rotation, [lon.sin(), y / s, lon.cos()]))
+    }
+}
+
+impl Equirectangular {
+    /// Positional shorthand for [`PanniniParams::project`], angles in degrees.
+    pub fn get_pannini(&self, fov: f64, compression: f64, theta: f64, height: u32, width: u32) -> Result<prelude::Mat> {
+        PanniniParams::new()
+            .fov(Deg(fov))
+            .compression(compression)
+            .yaw(Deg(theta))
+            .output_size(width, height)
+            .project(self)
+    }
+}
@@ -0,0 +1,491 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg, Rad};
+use crate::distortion::Distortion;
+use crate::effects::CaptureEffects;
+use crate::error::{Error, Result};
+use crate::output::OutputFormat;
+use crate::perspective::Equirectangular;
+use crate::rotation::{self, EulerFrame, EulerOrder, Matrix3};
+use crate::sampling::{Backend, Border, Interpolation, Precision, RenderOptions};
+use crate::stats::{self, Stats};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum ExplicitRotation {
+    Quaternion([f64; 4]),
+    Matrix(Matrix3),
+    LookAt { lon: f64, lat: f64, up: [f64; 3] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PerspectiveParams {
+    pub(crate) fov_x: Option<f64>,
+    pub(crate) fov_y: Option<f64>,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    euler: (EulerOrder, EulerFrame),
+    rotation: Option<ExplicitRotation>,
+    intrinsics: Option<Matrix3>,
+    principal_offset: (f64, f64),
+    zoom: f64,
+    distortion: Option<Distortion>,
+    effects: CaptureEffects,
+    translation: [f64; 3],
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) interpolation: Interpolation,
+    pub(crate) border: Border,
+    pub(crate) output_format: OutputFormat,
+    pub(crate) backend: Backend,
+    pub(crate) precision: Precision,
+    pub(crate) deterministic: bool,
+    pub(crate) remap_band_rows: Option<u32>,
+}
+
+impl Default for PerspectiveParams {
+    fn default() -> Self {
+        PerspectiveParams::new()
+    }
+}
+
+impl PerspectiveParams {
+    pub fn new() -> PerspectiveParams {
+        PerspectiveParams {
+            fov_x: None,
+            fov_y: None,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            euler: (EulerOrder::default(), EulerFrame::default()),
+            rotation: None,
+            intrinsics: None,
+            principal_offset: (0.0, 0.0),
+            zoom: 1.0,
+            distortion: None,
+            effects: CaptureEffects::new(),
+            translation: [0.0, 0.0, 0.0],
+            width: 1280,
+            height: 720,
+            interpolation: Interpolation::default(),
+            border: Border::default(),
+            output_format: OutputFormat::default(),
+            backend: Backend::default(),
+            precision: Precision::default(),
+            deterministic: false,
+            remap_band_rows: None,
+        }
+    }
+
+    /// Looking forward (longitude 0) with a 90 degree field of view.
+    pub fn front() -> PerspectiveParams {
+        PerspectiveParams::new()
+    }
+
+    /// Looking backward (longitude 180).
+    pub fn rear() -> PerspectiveParams {
+        PerspectiveParams::new().yaw(Deg(180.0))
+    }
+
+    pub fn left() -> PerspectiveParams {
+        PerspectiveParams::new().yaw(Deg(-90.0))
+    }
+
+    pub fn right() -> PerspectiveParams {
+        PerspectiveParams::new().yaw(Deg(90.0))
+    }
+
+    /// Wide 1080p dashcam view, tilted slightly toward the road.
+    pub fn driving_cam() -> PerspectiveParams {
+        PerspectiveParams::new()
+            .fov(Deg(120.0))
+            .pitch(Deg(-10.0))
+            .output_size(1920, 1080)
+    }
+
+    /// One eye of a typical headset: 1440x1600 with a 100 degree horizontal
+    /// field of view, offset sideways by half the interpupillary distance
+    /// `ipd` (meters). See [`translation`](Self::translation) for when the
+    /// offset has an effect.
+    pub fn vr_eye(eye: Eye, ipd: f64) -> PerspectiveParams {
+        let offset = match eye {
+            Eye::Left => -0.5 * ipd,
+            Eye::Right => 0.5 * ipd,
+        };
+        PerspectiveParams::new()
+            .fov(Deg(100.0))
+            .output_size(1440, 1600)
+            .translation([offset, 0.0, 0.0])
+    }
+
+    /// Horizontal field of view. Same as [`fov_x`](Self::fov_x).
+    pub fn fov(self, fov: impl Into<Angle>) -> Self {
+        self.fov_x(fov)
+    }
+
+    /// Horizontal field of view. When only one of `fov_x` and
+    /// `fov_y` is set the other follows from square pixels; with neither set
+    /// the horizontal field of view is 90 degrees.
+    pub fn fov_x(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_x = Some(fov.into().degrees());
+        self
+    }
+
+    /// Vertical field of view.
+    pub fn fov_y(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov_y = Some(fov.into().degrees());
+        self
+    }
+
+    /// Horizontal field of view plus the width-to-height ratio of the image
+    /// plane it covers, independent of the output resolution.
+    pub fn fov_with_aspect(self, fov_x: impl Into<Angle>, aspect: f64) -> Self {
+        let fov_x = fov_x.into();
+        let fov_y = 2.0 * f64::atan(f64::tan(0.5 * fov_x.radians()) / aspect);
+        self.fov_x(fov_x).fov_y(Rad(fov_y))
+    }
+
+    /// Rotation about the vertical axis.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    /// Rotation about the horizontal axis.
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    /// Rotation about the viewing direction.
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    /// How yaw, pitch and roll combine, for angles exported by other tools.
+    /// The default is intrinsic [`EulerOrder::YXZ`].
+    pub fn euler_order(mut self, order: EulerOrder, frame: EulerFrame) -> Self {
+        self.euler = (order, frame);
+        self
+    }
+
+    /// Camera-to-world orientation as a `[w, x, y, z]` quaternion. Takes
+    /// precedence over yaw, pitch and roll.
+    pub fn rotation_quat(mut self, q: [f64; 4]) -> Self {
+        self.rotation = Some(ExplicitRotation::Quaternion(q));
+        self
+    }
+
+    /// Camera-to-world orientation as a row-major rotation matrix. Takes
+    /// precedence over yaw, pitch and roll.
+    pub fn rotation_matrix(mut self, r: Matrix3) -> Self {
+        self.rotation = Some(ExplicitRotation::Matrix(r));
+        self
+    }
+
+    /// Centers the view on a point of the panorama. Longitude
+    /// increases to the right across the panorama and latitude is positive
+    /// above the horizon. The horizon is kept level.
+    pub fn look_at(mut self, lon: impl Into<Angle>, lat: impl Into<Angle>) -> Self {
+        self.yaw = lon.into().degrees();
+        self.pitch = lat.into().degrees();
+        self.roll = 0.0;
+        self.euler = (EulerOrder::default(), EulerFrame::default());
+        self.rotation = None;
+        self
+    }
+
+    /// Like [`look_at`](Self::look_at) but keeps the camera's up direction as
+    /// close as possible to `up`; see [`rotation::look_at_matrix`].
+    pub fn look_at_with_up(mut self, lon: impl Into<Angle>, lat: impl Into<Angle>, up: [f64; 3]) -> Self {
+        self.rotation = Some(ExplicitRotation::LookAt { lon: lon.into().degrees(), lat: lat.into().degrees(), up });
+        self
+    }
+
+    /// Drops an explicit rotation, explicit intrinsics and the vertical field
+    /// of view, so yaw, pitch, roll and the horizontal field of view alone
+    /// decide where the view looks and how much it sees.
+    pub(crate) fn without_explicit_pose(mut self) -> Self {
+        self.rotation = None;
+        self.intrinsics = None;
+        self.fov_y = None;
+        self
+    }
+
+    /// Resolves the camera-to-world rotation from whichever orientation was set.
+    pub fn rotation(&self) -> Result<Matrix3> {
+        match self.rotation {
+            Some(ExplicitRotation::Quaternion(q)) => rotation::quat_to_matrix(q),
+            Some(ExplicitRotation::Matrix(r)) => {
+                rotation::validate_matrix(&r)?;
+                Ok(r)
+            }
+            Some(ExplicitRotation::LookAt { lon, lat, up }) => rotation::look_at_matrix(lon, lat, up),
+            None => Ok(rotation::euler_to_matrix_with(self.yaw, self.pitch, self.roll, self.euler.0, self.euler.1)),
+        }
+    }
+
+    /// Digital zoom factor multiplied into the focal length derived from the
+    /// field of view; 2.0 halves the visible extent.
+    pub fn zoom(mut self, zoom: f64) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Field of view `(horizontal, vertical)` in degrees actually covered by
+    /// the output, after zoom, principal point and explicit intrinsics.
+    pub fn effective_fov(&self) -> (f64, f64) {
+        let k = self.intrinsics();
+        let span = |extent: u32, c: f64, f: f64| {
+            (f64::atan((extent as f64 - 1.0 - c + 0.5) / f) + f64::atan((c + 0.5) / f)).to_degrees()
+        };
+        (span(self.width, k[0][2], k[0][0]), span(self.height, k[1][2], k[1][1]))
+    }
+
+    /// Focal lengths `(fx, fy)` in output pixels.
+    pub fn focal_lengths(&self) -> (f64, f64) {
+        let zoom = self.zoom;
+        let focal = |fov: f64, extent: u32| zoom * 0.5 * (extent as f64) / f64::tan(0.5 * fov.to_radians());
+        match (self.fov_x, self.fov_y) {
+            (Some(fx), Some(fy)) => (focal(fx, self.width), focal(fy, self.height)),
+            (None, Some(fy)) => {
+                let f = focal(fy, self.height);
+                (f, f)
+            }
+            (fx, None) => {
+                let f = focal(fx.unwrap_or(90.0), self.width);
+                (f, f)
+            }
+        }
+    }
+
+    /// Uses an explicit camera matrix `[[fx, skew, cx], [0, fy, cy], [0, 0, 1]]`
+    /// in output pixels instead of deriving one from the field of view.
+    pub fn with_intrinsics(mut self, k: Matrix3) -> Self {
+        self.intrinsics = Some(k);
+        self
+    }
+
+    /// Shifts the principal point from the image center by `(dx, dy)` output
+    /// pixels, giving an off-axis (shift lens) projection. With a level
+    /// camera and a positive `dy`, the view looks upward while verticals stay
+    /// parallel. Ignored when explicit intrinsics are set.
+    pub fn principal_point_offset(mut self, dx: f64, dy: f64) -> Self {
+        self.principal_offset = (dx, dy);
+        self
+    }
+
+    /// Camera intrinsics matrix for the configured output.
+    pub fn intrinsics(&self) -> Matrix3 {
+        if let Some(k) = self.intrinsics {
+            return k;
+        }
+        let (fx, fy) = self.focal_lengths();
+        let (dx, dy) = self.principal_offset;
+        let cx = (self.width as f64 - 1.0) / 2.0 + dx;
+        let cy = (self.height as f64 - 1.0) / 2.0 + dy;
+        [
+            [fx, 0.0, cx],
+            [0.0, fy, cy],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+
+    /// Applies Brown–Conrady lens distortion after the pinhole projection,
+    /// so the output looks as if taken through that lens with these
+    /// intrinsics.
+    pub fn distortion(mut self, distortion: Distortion) -> Self {
+        self.distortion = Some(distortion);
+        self
+    }
+
+    pub fn distortion_coefficients(&self) -> Option<Distortion> {
+        self.distortion
+    }
+
+    /// Vignetting and chromatic aberration added to the rendered view.
+    pub fn effects(mut self, effects: CaptureEffects) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    pub fn capture_effects(&self) -> CaptureEffects {
+        self.effects
+    }
+
+    /// Camera position relative to the panorama center, in the camera frame
+    /// convention (x right, y down, z forward). A panorama alone has no
+    /// parallax, so plain projections ignore this; it is kept so the same
+    /// view definition can drive renderers that know scene depth, such as
+    /// [`DepthMap::view`](crate::DepthMap::view).
+    pub fn translation(mut self, translation: [f64; 3]) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn translation_vector(&self) -> [f64; 3] {
+        self.translation
+    }
+
+    pub fn output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn border(mut self, border: Border) -> Self {
+        self.border = border;
+        self
+    }
+
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Where the view is remapped; see [`Backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Guarantees bit-identical output on every run of the same build; see
+    /// [`RenderOptions::deterministic`].
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Output rows per CPU `remap` call; see [`RenderOptions::remap_band_rows`].
+    pub fn remap_band_rows(mut self, rows: u32) -> Self {
+        self.remap_band_rows = Some(rows);
+        self
+    }
+
+    /// Precision of the geometry behind the sampling maps; see [`Precision`].
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Takes every field of `options`.
+    pub(crate) fn with_render_options(mut self, options: &RenderOptions) -> Self {
+        self.interpolation = options.interpolation;
+        self.border = options.border;
+        self.output_format = options.output_format;
+        self.backend = options.backend;
+        self.deterministic = options.deterministic;
+        self.remap_band_rows = options.remap_band_rows;
+        self
+    }
+
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            interpolation: self.interpolation,
+            border: self.border,
+            output_format: self.output_format,
+            backend: self.backend,
+            deterministic: self.deterministic,
+            remap_band_rows: self.remap_band_rows,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for fov in [self.fov_x, self.fov_y].into_iter().flatten() {
+            if !(fov > 0.0 && fov < 180.0) {
+                return Err(Error::InvalidParameter(format!("fov must be in (0, 180) degrees, got {fov}")));
+            }
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidParameter(format!("output size must be non-zero, got {}x{}", self.width, self.height)));
+        }
+        if !(self.zoom > 0.0 && self.zoom.is_finite()) {
+            return Err(Error::InvalidParameter(format!("zoom must be positive, got {}", self.zoom)));
+        }
+        if let Some(k) = self.intrinsics {
+            if k[2] != [0.0, 0.0, 1.0] || k[1][0] != 0.0 || !(k[0][0] > 0.0 && k[1][1] > 0.0) {
+                return Err(Error::InvalidParameter(format!("{k:?} is not a camera matrix")));
+            }
+        }
+        if let Some(distortion) = &self.distortion {
+            distortion.validate()?;
+        }
+        self.effects.validate()?;
+        self.rotation()?;
+        Ok(())
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        self.validate()?;
+        pano.render(self)
+    }
+
+    /// Like [`project`](Self::project), also returning where the time went.
+    pub fn project_with_stats(&self, pano: &Equirectangular) -> Result<(prelude::Mat, Stats)> {
+        let (image, stats) = stats::measure(|| self.project(pano));
+        Ok((image?, stats))
+    }
+
+    /// Like [`project`](Self::project), computing the view in bands of
+    /// `band_rows` rows so no full-frame maps are ever resident; see
+    /// [`Equirectangular::render_bands`].
+    pub fn project_tiled(&self, pano: &Equirectangular, band_rows: u32) -> Result<prelude::Mat> {
+        self.validate()?;
+        pano.render_tiled(self, band_rows)
+    }
+
+    /// The same view at `scale` (in (0, 1]) of the output size, with
+    /// bilinear sampling and no capture effects: a fast stand-in for
+    /// interactive scrubbing, with [`project`](Self::project) rendering the
+    /// full-quality frame once the view settles.
+    pub fn preview_params(&self, scale: f64) -> Result<PerspectiveParams> {
+        if !(scale > 0.0 && scale <= 1.0) {
+            return Err(Error::InvalidParameter(format!("preview scale must be in (0, 1], got {scale}")));
+        }
+        self.validate()?;
+        let width = ((self.width as f64 * scale).round() as u32).max(1);
+        let height = ((self.height as f64 * scale).round() as u32).max(1);
+        let (sx, sy) = (width as f64 / self.width as f64, height as f64 / self.height as f64);
+        // Scale about pixel corners so the preview covers the same field.
+        let k = self.intrinsics();
+        let mut preview = self.clone().output_size(width, height).with_intrinsics([
+            [k[0][0] * sx, k[0][1] * sx, (k[0][2] + 0.5) * sx - 0.5],
+            [0.0, k[1][1] * sy, (k[1][2] + 0.5) * sy - 0.5],
+            [0.0, 0.0, 1.0],
+        ]);
+        preview.effects = CaptureEffects::default();
+        if preview.interpolation != Interpolation::Nearest {
+            preview.interpolation = Interpolation::Linear;
+        }
+        Ok(preview)
+    }
+
+    /// Renders [`preview_params`](Self::preview_params) from `pano`.
+    pub fn preview(&self, pano: &Equirectangular, scale: f64) -> Result<prelude::Mat> {
+        self.preview_params(scale)?.project(pano)
+    }
+
+    /// Like [`project`](Self::project), also returning a `CV_8U` mask that
+    /// is 255 where the view has panorama data. Only cropped panoramas have
+    /// uncovered pixels.
+    pub fn project_with_mask(&self, pano: &Equirectangular) -> Result<(prelude::Mat, prelude::Mat)> {
+        self.validate()?;
+        pano.render_with_mask(self)
+    }
+}
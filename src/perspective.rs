@@ -1,113 +1,456 @@
-use ndarray::{Axis, s, Zip, concatenate, stack};
-use ndarray_linalg::Inverse;
-use opencv::{imgcodecs, prelude};
-use opencv::calib3d::rodrigues;
-use opencv::prelude::{MatExprTraitConst, MatTraitConst, MatTraitConstManual};
+use ndarray::{Axis, s, Zip, concatenate};
+use opencv::{imgcodecs, imgproc, prelude};
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::Deg;
+use crate::batch;
+use crate::cache;
+use crate::effects;
+use crate::error::{Error, Result};
+use crate::maps;
+use crate::output::{self, EncodeOptions, OutputFormat};
+use crate::params::PerspectiveParams;
+use crate::pool;
+use crate::projection::{EquirectProjection, Projection};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+use crate::source::{self, EquirectSource};
+
+/// Placement of a cropped panorama inside the full equirectangular frame it
+/// was cut from, in pixels of the full frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CroppedPanoParams {
+    pub full_width: u32,
+    pub full_height: u32,
+    pub left: u32,
+    pub top: u32,
+}
 
 pub struct Equirectangular {
     src: prelude::Mat,
     height: i32,
     width: i32,
+    crop: Option<CroppedPanoParams>,
+    orientation: Option<Matrix3>,
 }
 
 impl Equirectangular {
-    pub fn new(img_name: &str) -> Equirectangular {
-        let src = imgcodecs::imread(img_name, imgcodecs::IMREAD_COLOR).expect("Could not read image!");
-        let height = src.cols();
-        let width = src.rows();
+    pub fn new(img_name: &str) -> Result<Equirectangular> {
+        std::fs::metadata(img_name)?;
+        let src = imgcodecs::imread(img_name, imgcodecs::IMREAD_UNCHANGED)?;
+        if src.empty() {
+            return Err(Error::Decode(format!("{img_name} is not a supported image")));
+        }
+        Equirectangular::from_mat(src)
+    }
+
+    /// Loads `path` at the coarsest of full, 1/2, 1/4 and 1/8 resolution
+    /// that is still at least `min_width` pixels wide. Only the header is
+    /// read to choose, and JPEG decoders skip most of the work at reduced
+    /// sizes, so thumbnails of huge files load quickly. Reduced images are
+    /// decoded as 8-bit BGR.
+    pub fn open_reduced(path: &str, min_width: u32) -> Result<Equirectangular> {
+        let (width, _) = image::image_dimensions(path).map_err(|e| Error::Decode(format!("{path}: {e}")))?;
+        let flag = match [8, 4, 2].into_iter().find(|factor| width / factor >= min_width) {
+            Some(8) => imgcodecs::IMREAD_REDUCED_COLOR_8,
+            Some(4) => imgcodecs::IMREAD_REDUCED_COLOR_4,
+            Some(2) => imgcodecs::IMREAD_REDUCED_COLOR_2,
+            _ => return Equirectangular::new(path),
+        };
+        let src = imgcodecs::imread(path, flag)?;
+        if src.empty() {
+            return Err(Error::Decode(format!("{path} is not a supported image")));
+        }
+        Equirectangular::from_mat(src)
+    }
+
+    /// Loads `path` at the lowest resolution that still renders `params`
+    /// without losing detail at the view's center; see
+    /// [`open_reduced`](Self::open_reduced).
+    pub fn open_for_view(path: &str, params: &PerspectiveParams) -> Result<Equirectangular> {
+        params.validate()?;
+        let k = params.intrinsics();
+        let (fx, fy) = (k[0][0], k[1][1]);
+        // The panorama spans 2π radians across; the view samples f pixels
+        // per radian at its center.
+        let min_width = (std::f64::consts::TAU * fx.max(fy)).ceil() as u32;
+        Equirectangular::open_reduced(path, min_width)
+    }
+
+    /// Wraps an already decoded panorama: BGR, BGRA, or any other channel
+    /// layout such as single-channel depth, mask or thermal images. 8-bit,
+    /// 16-bit and floating point depths are kept as-is through projection.
+    pub fn from_mat(src: prelude::Mat) -> Result<Equirectangular> {
+        if src.empty() {
+            return Err(Error::InvalidParameter("source image is empty".to_string()));
+        }
+        let depth = src.depth();
+        if ![opencv::core::CV_8U, opencv::core::CV_16U, opencv::core::CV_16S, opencv::core::CV_32F, opencv::core::CV_64F].contains(&depth) {
+            return Err(Error::InvalidParameter(format!("unsupported image depth {depth}")));
+        }
+        let height = src.rows();
+        let width = src.cols();
 
-        Equirectangular {
+        Ok(Equirectangular {
             src,
             height,
             width,
+            crop: None,
+            orientation: None,
+        })
+    }
+
+    /// Builds a panorama from a tightly packed RGB8 buffer.
+    pub fn from_bytes(data: &[u8], width: u32, height: u32) -> Result<Equirectangular> {
+        Equirectangular::from_packed(data, width, height, 3, imgproc::COLOR_RGB2BGR)
+    }
+
+    /// Builds a panorama from a tightly packed RGBA8 buffer, keeping alpha.
+    pub fn from_rgba_bytes(data: &[u8], width: u32, height: u32) -> Result<Equirectangular> {
+        Equirectangular::from_packed(data, width, height, 4, imgproc::COLOR_RGBA2BGRA)
+    }
+
+    /// Builds a panorama from a tightly packed 16-bit RGB buffer.
+    pub fn from_rgb16(data: &[u16], width: u32, height: u32) -> Result<Equirectangular> {
+        Equirectangular::from_packed(data, width, height, 3, imgproc::COLOR_RGB2BGR)
+    }
+
+    /// Builds a panorama from a tightly packed 32-bit float RGB buffer, e.g.
+    /// linear HDR radiance.
+    pub fn from_rgb32f(data: &[f32], width: u32, height: u32) -> Result<Equirectangular> {
+        Equirectangular::from_packed(data, width, height, 3, imgproc::COLOR_RGB2BGR)
+    }
+
+    /// Builds a single-channel panorama (depth map, mask, thermal, ...) from
+    /// a tightly packed buffer of 8-bit, 16-bit or float samples.
+    pub fn from_gray<T: opencv::core::DataType>(data: &[T], width: u32, height: u32) -> Result<Equirectangular> {
+        let expected = (width as usize) * (height as usize);
+        if width == 0 || height == 0 || data.len() != expected {
+            return Err(Error::InvalidParameter(format!(
+                "expected {expected} samples for a {width}x{height} single-channel image, got {}",
+                data.len()
+            )));
         }
+        let flat = prelude::Mat::from_slice(data)?;
+        Equirectangular::from_mat(flat.reshape(1, height as i32)?.try_clone()?)
     }
 
-    pub fn get_perspective(&self, fov: f64, theta: f64, phi: f64, height: u32, width: u32) -> prelude::Mat {
-        let f = 0.5 * (width as f64) * 1.0 / f64::tan(0.5 * fov / 180.0 * std::f64::consts::PI);
-        let cx = (width as f64 - 1.0) / 2.0;
-        let cy = (height as f64 - 1.0) / 2.0;
-        let k: ndarray::Array2<f64> = ndarray::arr2(&[
-            [f, 0.0, cx],
-            [0.0, f, cy],
-            [0.0, 0.0, 1.0],
-        ]);
-
-        let k_inv = k.inv().expect("Could not invert matrix!");
-
-        let x = ndarray::Array2::from_shape_fn((height as usize, width as usize), |(_i, j)| j as f64);
-        let y = ndarray::Array2::from_shape_fn((height as usize, width as usize), |(i, _)| i as f64);
-        let z = ndarray::Array2::from_elem((height as usize, width as usize), 1.0);
-        let xyz = stack(Axis(2), &[x.view(), y.view(), z.view()])
-            .expect("Failed to stack arrays");
-
-        let n_points = (height as usize) * (width as usize);
-        let xyz_2d = xyz.to_shape((n_points, 3)).expect("Failed to reshape xyz").to_owned();
-        let transformed = xyz_2d.dot(&k_inv.t());
-        let reshaped_xyz = transformed.to_shape((height as usize, width as usize, 3)).expect("Failed to reshape transformed xyz").to_owned();
-
-        let y_axis = opencv::core::Vec3d::from([0.0, 1.0, 0.0]);
-        let x_axis = opencv::core::Vec3d::from([1.0, 0.0, 0.0]);
-
-        let theta_rad = theta.to_radians();
-        let phi_rad = phi.to_radians();
-
-        let mut r1 = prelude::Mat::default();
-        rodrigues(&opencv::core::Mat::from_slice(&[y_axis[0] * theta_rad, y_axis[1] * theta_rad, y_axis[2] * theta_rad]).unwrap(), &mut r1, &mut opencv::core::Mat::default()).unwrap();
-
-        let mut r2 = prelude::Mat::default();
-        let x_axis_mat = opencv::core::Mat::from_slice_2d(&[[x_axis[0]], [x_axis[1]], [x_axis[2]]]).unwrap();
-        let mut r1_dot_x_axis = prelude::Mat::default();
-        opencv::core::gemm(&r1, &x_axis_mat, 1.0, &prelude::Mat::default(), 0.0, &mut r1_dot_x_axis, 0).unwrap();
-        let r1_dot_x_axis_vec = r1_dot_x_axis.to_vec_2d::<f64>().unwrap();
-        rodrigues(&opencv::core::Mat::from_slice(&[
-            r1_dot_x_axis_vec[0][0] * phi_rad,
-            r1_dot_x_axis_vec[1][0] * phi_rad,
-            r1_dot_x_axis_vec[2][0] * phi_rad
-        ]).unwrap(), &mut r2, &mut opencv::core::Mat::default()).unwrap();
-
-        let r = (r2 * r1).into_result().unwrap().to_mat().unwrap();
-
-        let r_vec = r.to_vec_2d::<f64>().unwrap(); // Vec<Vec<f64>>
-        let r_nd = ndarray::Array2::from_shape_vec((3, 3), r_vec.into_iter().flatten().collect())
-            .expect("Failed to create ndarray from r");
-
-        let reshaped_xyz_2d = reshaped_xyz.to_shape((n_points, 3)).expect("Failed to reshape reshaped_xyz").to_owned();
-        let rotated = reshaped_xyz_2d.dot(&r_nd.t());
-        let rotated_xyz = rotated.to_shape((height as usize, width as usize, 3))
-            .expect("Failed to reshape rotated xyz").to_owned();
-
-        let lonlat = xyz_to_lonlat(rotated_xyz);
-        let xy = lonlat_to_xy(lonlat, (self.width as usize, self.height as usize));
-
-        let mut persp = prelude::Mat::default();
-
-        let binding = xy.map_axis(Axis(2), |v| v[0] as f32)
-            .into_dimensionality::<ndarray::Ix2>().unwrap();
-        let x_values = binding
-            .as_standard_layout();
-        let binding = xy.map_axis(Axis(2), |v| v[1] as f32)
-            .into_dimensionality::<ndarray::Ix2>().unwrap();
-        let y_values = binding
-            .as_standard_layout();
-        let (r_rows, r_cols) = x_values.dim();
-        let x = prelude::Mat::new_rows_cols_with_data(r_rows as i32, r_cols as i32, x_values.as_slice().unwrap()).unwrap();
-        let y = prelude::Mat::new_rows_cols_with_data(r_rows as i32, r_cols as i32, y_values.as_slice().unwrap()).unwrap();
-
-        opencv::imgproc::remap(
-            &self.src, &mut persp,
-            &x,
-            &y,
-            opencv::imgproc::INTER_CUBIC,
-            opencv::core::BORDER_WRAP,
-            opencv::core::Scalar::all(0.0)
-        ).unwrap();
-
-        persp
+    fn from_packed<T: opencv::core::DataType>(data: &[T], width: u32, height: u32, channels: usize, code: i32) -> Result<Equirectangular> {
+        let expected = (width as usize) * (height as usize) * channels;
+        if width == 0 || height == 0 || data.len() != expected {
+            return Err(Error::InvalidParameter(format!(
+                "expected {expected} samples for a {width}x{height} image with {channels} channels, got {}",
+                data.len()
+            )));
+        }
+        let flat = prelude::Mat::from_slice(data)?;
+        let packed = flat.reshape(channels as i32, height as i32)?;
+        let mut converted = prelude::Mat::default();
+        imgproc::cvt_color(&packed, &mut converted, code, 0)?;
+        Equirectangular::from_mat(converted)
+    }
+
+    /// Decodes an in-memory encoded image (PNG, JPEG, ...).
+    pub fn from_encoded(data: &[u8]) -> Result<Equirectangular> {
+        let buf = prelude::Mat::from_slice(data)?;
+        let src = imgcodecs::imdecode(&buf, imgcodecs::IMREAD_UNCHANGED)?;
+        if src.empty() {
+            return Err(Error::Decode("buffer is not a supported image".to_string()));
+        }
+        Equirectangular::from_mat(src)
+    }
+
+    /// Marks the image as a crop of a larger panorama. Views that look
+    /// outside the covered region are filled with the border color; the crop
+    /// may straddle the ±180° meridian.
+    pub fn with_crop(mut self, crop: CroppedPanoParams) -> Result<Equirectangular> {
+        if self.width() > crop.full_width || crop.left >= crop.full_width || crop.top + self.height() > crop.full_height {
+            return Err(Error::InvalidParameter(format!(
+                "a {}x{} crop does not fit at ({}, {}) in a {}x{} panorama",
+                self.width, self.height, crop.left, crop.top, crop.full_width, crop.full_height
+            )));
+        }
+        self.crop = Some(crop);
+        Ok(self)
+    }
+
+    pub fn crop(&self) -> Option<CroppedPanoParams> {
+        self.crop
+    }
+
+    /// Declares the true up direction as seen in the panorama (e.g. from an
+    /// IMU gravity reading, negated), in the camera frame convention where a
+    /// level panorama has `up = [0.0, -1.0, 0.0]`. Every projection is then
+    /// computed against the corrected horizon, with no extra resampling.
+    pub fn with_up(self, up: [f64; 3]) -> Result<Equirectangular> {
+        let leveling = rotation::between([0.0, -1.0, 0.0], up)?;
+        self.with_orientation(leveling)
+    }
+
+    /// Like [`with_up`](Self::with_up) with a full rotation from corrected
+    /// world directions to panorama directions, which can also fix heading.
+    pub fn with_orientation(mut self, orientation: Matrix3) -> Result<Equirectangular> {
+        rotation::validate_matrix(&orientation)?;
+        self.orientation = Some(orientation);
+        Ok(self)
+    }
+
+    pub fn orientation(&self) -> Option<Matrix3> {
+        self.orientation
+    }
+
+    /// Camera-to-panorama rotation of a view, including the orientation.
+    pub(crate) fn view_rotation(&self, params: &PerspectiveParams) -> Result<Matrix3> {
+        let camera = params.rotation()?;
+        Ok(match &self.orientation {
+            Some(orientation) => rotation::matmul(orientation, &camera),
+            None => camera,
+        })
+    }
+
+    /// Positional shorthand for [`PerspectiveParams::project`], angles in degrees.
+    pub fn get_perspective(&self, fov: f64, theta: f64, phi: f64, height: u32, width: u32) -> Result<prelude::Mat> {
+        PerspectiveParams::new()
+            .fov(Deg(fov))
+            .yaw(Deg(theta))
+            .pitch(Deg(phi))
+            .output_size(width, height)
+            .project(self)
+    }
+
+    /// Renders a view through the [`cache`](crate::cache) of sampling maps.
+    pub(crate) fn render(&self, params: &PerspectiveParams) -> Result<prelude::Mat> {
+        let key = cache::MapKey {
+            params: params.clone(),
+            pano_size: (self.width(), self.height()),
+            crop: self.crop,
+            orientation: self.orientation,
+        };
+        let maps = cache::get_or_build(key, || {
+            let maps = maps::perspective_maps(params, &self.view_rotation(params)?, self.size(), self.crop.as_ref())?;
+            maps::RemapMaps::new(&maps)
+        })?;
+        self.render_view(&maps, params)
+    }
+
+    /// Like [`render`](Self::render), also returning the validity mask.
+    pub(crate) fn render_with_mask(&self, params: &PerspectiveParams) -> Result<(prelude::Mat, prelude::Mat)> {
+        let maps = maps::perspective_maps(params, &self.view_rotation(params)?, self.size(), self.crop.as_ref())?;
+        let image = self.render_view(&maps::RemapMaps::new(&maps)?, params)?;
+        let mask = maps.coverage((self.width(), self.height()), self.crop.is_none());
+        Ok((image, maps::to_mat(&mask)?))
+    }
+
+    /// Renders `params` in bands of `band_rows` output rows, handing each
+    /// band to `sink` with the index of its first row. Only one band of
+    /// maps and output exists at a time, and each band reads only the
+    /// source rows it samples, so views of gigapixel panoramas render in
+    /// bounded memory. Bands keep the panorama's format and skip capture
+    /// effects; [`project_tiled`](PerspectiveParams::project_tiled)
+    /// assembles a finished image.
+    pub fn render_bands(
+        &self,
+        params: &PerspectiveParams,
+        band_rows: u32,
+        mut sink: impl FnMut(u32, &prelude::Mat) -> Result<()>,
+    ) -> Result<()> {
+        params.validate()?;
+        if band_rows == 0 {
+            return Err(Error::InvalidParameter("band height must be non-zero".to_string()));
+        }
+        let rotation = self.view_rotation(params)?;
+        let options = params.render_options().for_coverage(self.crop.is_some());
+        let mut band = prelude::Mat::default();
+        for top in (0..params.height).step_by(band_rows as usize) {
+            let rows = top as usize..(top + band_rows).min(params.height) as usize;
+            let mut maps = maps::perspective_band_maps(params, &rotation, self.size(), self.crop.as_ref(), rows)?;
+            let (first, last) = maps.map_y.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &y| (lo.min(y), hi.max(y)));
+            // Interpolation kernels reach up to four rows past a sample.
+            let y0 = (first.floor() as i32 - 4).clamp(0, self.height - 1);
+            let y1 = (last.ceil() as i32 + 4).clamp(y0, self.height - 1);
+            maps.map_y.mapv_inplace(|y| y - y0 as f32);
+            let source = prelude::Mat::roi(&self.src, opencv::core::Rect::new(0, y0, self.width, y1 - y0 + 1))?;
+            maps::RemapMaps::new(&maps)?.remap_into(&*source, &options, &mut band)?;
+            sink(top, &band)?;
+        }
+        Ok(())
+    }
+
+    /// [`render_bands`](Self::render_bands) assembled into the full view,
+    /// then finished like any other render.
+    pub(crate) fn render_tiled(&self, params: &PerspectiveParams, band_rows: u32) -> Result<prelude::Mat> {
+        let mut view = prelude::Mat::default();
+        self.render_bands(params, band_rows, |top, band| {
+            if view.empty() {
+                view = prelude::Mat::new_rows_cols_with_default(params.height as i32, params.width as i32, band.typ(), opencv::core::Scalar::all(0.0))?;
+            }
+            let mut rows = prelude::Mat::roi_mut(&mut view, opencv::core::Rect::new(0, top as i32, band.cols(), band.rows()))?;
+            band.copy_to(&mut rows)?;
+            Ok(())
+        })?;
+        let effects = params.capture_effects();
+        if !effects.is_identity() {
+            let k = params.intrinsics();
+            view = effects.apply(&view, (k[0][2], k[1][2]))?;
+        }
+        if params.output_format == OutputFormat::Native {
+            return Ok(view);
+        }
+        let mut dst = prelude::Mat::default();
+        output::convert_into(&view, params.output_format, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Projects `params` straight to the image file `path`, in the format
+    /// its extension names. 8-bit PNGs without capture effects are rendered
+    /// in bands that go straight into the encoder, so neither the view nor
+    /// its maps are ever held whole; other outputs are rendered once and
+    /// encoded with `options`.
+    pub fn project_to_file(&self, path: impl AsRef<std::path::Path>, params: &PerspectiveParams, options: &EncodeOptions) -> Result<()> {
+        params.validate()?;
+        options.validate()?;
+        let path = path.as_ref();
+        let png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        let channels = match params.output_format {
+            OutputFormat::Native => Some(self.channels() as i32),
+            OutputFormat::Bgr => Some(3),
+            _ => None,
+        };
+        if let (true, true, opencv::core::CV_8U, Some(channels)) = (png, params.capture_effects().is_identity(), self.depth(), channels) {
+            let mut stream = output::PngStream::create(path, params.width, params.height, channels, options)?;
+            let mut converted = prelude::Mat::default();
+            self.render_bands(params, options.band_rows, |_, band| {
+                if params.output_format == OutputFormat::Native {
+                    return stream.write_band(band);
+                }
+                output::convert_into(band, params.output_format, &mut converted)?;
+                stream.write_band(&converted)
+            })?;
+            return stream.finish();
+        }
+        let image = self.render(params)?;
+        batch::write(path, &image, options)?;
+        pool::recycle(image);
+        Ok(())
+    }
+
+    fn render_view(&self, maps: &maps::RemapMaps, params: &PerspectiveParams) -> Result<prelude::Mat> {
+        let options = params.render_options().for_coverage(self.crop.is_some());
+        let mut dst = pool::take_output(params.height as i32, params.width as i32, self.src.typ(), options.border)?;
+        effects::render_into(maps, &self.src, params, &options, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Renders any output [`Projection`] of the panorama.
+    pub fn render_projection<P: Projection + ?Sized>(&self, projection: &P, options: &RenderOptions) -> Result<prelude::Mat> {
+        source::render(&self.src, &self.source(), projection, options)
+    }
+
+    /// Like [`render_projection`](Self::render_projection), also returning a
+    /// `CV_8U` mask that is 255 where the output has panorama data and 0
+    /// where it was filled with the border (outside a crop, outside a
+    /// fisheye circle, ...).
+    pub fn render_projection_with_mask<P: Projection + ?Sized>(&self, projection: &P, options: &RenderOptions) -> Result<(prelude::Mat, prelude::Mat)> {
+        source::render_with_mask(&self.src, &self.source(), projection, options)
+    }
+
+    /// Source geometry of this panorama, including its crop.
+    pub fn source(&self) -> EquirectSource {
+        let mut source = EquirectSource::new(self.width(), self.height());
+        if let Some(crop) = self.crop {
+            source = source.with_crop(crop);
+        }
+        if let Some(orientation) = self.orientation {
+            source = source.with_rotation(orientation);
+        }
+        source
+    }
+
+    /// Resamples the whole panorama under a global rotation (degrees), so
+    /// that the direction at `yaw`/`pitch` becomes the new forward
+    /// direction and `roll` levels a tilted horizon. A cropped panorama
+    /// becomes a full frame with the uncovered area filled with black.
+    pub fn rotate(&self, yaw: f64, pitch: f64, roll: f64) -> Result<Equirectangular> {
+        self.rotate_matrix(&rotation::euler_to_matrix(yaw, pitch, roll), &RenderOptions::default())
+    }
+
+    /// Like [`rotate`](Self::rotate) with an explicit rotation matrix and
+    /// render options.
+    pub fn rotate_matrix(&self, rotation: &Matrix3, options: &RenderOptions) -> Result<Equirectangular> {
+        rotation::validate_matrix(rotation)?;
+        let (width, height) = match self.crop {
+            Some(crop) => (crop.full_width, crop.full_height),
+            None => (self.width(), self.height()),
+        };
+        let frame = EquirectProjection::new(width, height).with_rotation(*rotation);
+        Equirectangular::from_mat(self.render_projection(&frame, options)?)
+    }
+
+    /// Renders several views, in order.
+    pub fn get_perspectives(&self, views: &[PerspectiveParams]) -> Result<Vec<prelude::Mat>> {
+        views
+            .iter()
+            .map(|params| {
+                params.validate()?;
+                self.render(params)
+            })
+            .collect()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    /// Width divided by height; 2.0 for a full panorama.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Checks that the image (or the full frame of a crop) covers the whole
+    /// sphere, i.e. is 2:1 within 1%. Partial panoramas without crop
+    /// metadata still project but with stretched geometry.
+    pub fn validate(&self) -> Result<()> {
+        let (width, height) = match self.crop {
+            Some(crop) => (crop.full_width, crop.full_height),
+            None => (self.width(), self.height()),
+        };
+        if (width as f64 / height as f64 - 2.0).abs() > 0.02 {
+            return Err(Error::AspectRatio { width, height });
+        }
+        Ok(())
+    }
+
+    pub fn channels(&self) -> u32 {
+        self.src.channels() as u32
+    }
+
+    /// Whether the panorama carries an alpha channel that projections keep.
+    pub fn has_alpha(&self) -> bool {
+        self.src.channels() == 4
+    }
+
+    /// OpenCV depth of the samples (`CV_8U`, `CV_16U`, `CV_32F`, ...);
+    /// projections have the same depth.
+    pub fn depth(&self) -> i32 {
+        self.src.depth()
+    }
+
+    pub fn as_mat(&self) -> &prelude::Mat {
+        &self.src
+    }
+
+    /// Panorama size as `(height, width)`.
+    pub(crate) fn size(&self) -> (usize, usize) {
+        (self.height as usize, self.width as usize)
     }
 }
 
-fn xyz_to_lonlat(xyz: ndarray::Array3<f64>) -> ndarray::Array3<f64> {
+pub fn xyz_to_lonlat(xyz: ndarray::Array3<f64>) -> Result<ndarray::Array3<f64>> {
     let norm = xyz.map_axis(Axis(2), |v| v.dot(&v).sqrt())
         .insert_axis(Axis(2));
     let xyz_norm = xyz / norm;
@@ -121,10 +464,10 @@ fn xyz_to_lonlat(xyz: ndarray::Array3<f64>) -> ndarray::Array3<f64> {
         .map_collect(|&a, &b| a.atan2(b));
     let lat = y.mapv(|a| a.asin());
 
-    concatenate(Axis(2), &[lon.view(), lat.view()]).expect("Failed to concatenate the arrays")
+    Ok(concatenate(Axis(2), &[lon.view(), lat.view()])?)
 }
 
-fn lonlat_to_xy(lonlat: ndarray::Array3<f64>, shape: (usize, usize)) -> ndarray::Array3<f64> {
+pub fn lonlat_to_xy(lonlat: ndarray::Array3<f64>, shape: (usize, usize)) -> Result<ndarray::Array3<f64>> {
     let (h, w) = shape;
     let x = lonlat
         .slice(s![.., .., 0..1])
@@ -134,6 +477,5 @@ fn lonlat_to_xy(lonlat: ndarray::Array3<f64>, shape: (usize, usize)) -> ndarray:
         .slice(s![.., .., 1..2])
         .to_owned()
         .mapv(|v| (v / std::f64::consts::PI + 0.5) * ((h as f64) - 1.0));
-    concatenate(Axis(2), &[x.view(), y.view()])
-        .expect("Failed to concatenate X and Y")
-}
\ No newline at end of file
+    Ok(concatenate(Axis(2), &[x.view(), y.view()])?)
+}
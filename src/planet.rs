@@ -0,0 +1,140 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::error::{Error, Result};
+use crate::fisheye::FisheyeModel;
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// A stereographic "little planet" view. By default it is centered on the
+/// nadir so the ground becomes a small globe surrounded by sky; centering
+/// on the zenith gives the inverted "tunnel" look.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LittlePlanetParams {
+    pub(crate) fov: f64,
+    pub(crate) lon: f64,
+    pub(crate) lat: f64,
+    pub(crate) rotation: f64,
+    pub(crate) size: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for LittlePlanetParams {
+    fn default() -> Self {
+        LittlePlanetParams::new()
+    }
+}
+
+impl LittlePlanetParams {
+    pub fn new() -> LittlePlanetParams {
+        LittlePlanetParams {
+            fov: 300.0,
+            lon: 0.0,
+            lat: -90.0,
+            rotation: 0.0,
+            size: 1024,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Centered on the zenith instead of the nadir.
+    pub fn tunnel() -> LittlePlanetParams {
+        LittlePlanetParams::new().center(Deg(0.0), Deg(90.0))
+    }
+
+    /// Angle covered by the circle inscribed in the output, below 360
+    /// degrees. This sets the scale: larger values shrink the planet.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    /// Direction at the center of the output, latitude positive above the
+    /// horizon.
+    pub fn center(mut self, lon: impl Into<Angle>, lat: impl Into<Angle>) -> Self {
+        self.lon = lon.into().degrees();
+        self.lat = lat.into().degrees();
+        self
+    }
+
+    /// Spin of the planet about its center.
+    pub fn rotation(mut self, rotation: impl Into<Angle>) -> Self {
+        self.rotation = rotation.into().degrees();
+        self
+    }
+
+    /// Width and height of the square output.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fov > 0.0 && self.fov < 360.0) {
+            return Err(Error::InvalidParameter(format!("little planet fov must be in (0, 360) degrees, got {}", self.fov)));
+        }
+        if self.size == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<LittlePlanet> {
+        self.validate()?;
+        let radius = self.size as f64 / 2.0;
+        Ok(LittlePlanet {
+            rotation: rotation::euler_to_matrix(self.lon, self.lat, self.rotation),
+            size: self.size,
+            focal: radius / FisheyeModel::Stereographic.radius(0.5 * self.fov.to_radians()),
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+}
+
+/// A little planet view resolved from [`LittlePlanetParams`]. Unlike a
+/// fisheye it fills the whole square, corners included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LittlePlanet {
+    rotation: Matrix3,
+    size: u32,
+    focal: f64,
+}
+
+impl Projection for LittlePlanet {
+    fn output_size(&self) -> (u32, u32) {
+        (self.size, self.size)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let center = self.size as f64 / 2.0 - 0.5;
+        let (dx, dy) = (u - center, v - center);
+        let theta = FisheyeModel::Stereographic.theta((dx * dx + dy * dy).sqrt() / self.focal)?;
+        let phi = dy.atan2(dx);
+        let ray = [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+        Some(rotation::apply(&self.rotation, ray))
+    }
+}
+
+impl Equirectangular {
+    /// Positional shorthand for a nadir-centered [`LittlePlanetParams`],
+    /// angles in degrees.
+    pub fn to_little_planet(&self, fov: f64, rotation: f64, size: u32) -> Result<prelude::Mat> {
+        LittlePlanetParams::new()
+            .fov(Deg(fov))
+            .rotation(Deg(rotation))
+            .size(size)
+            .project(self)
+    }
+}
@@ -0,0 +1,134 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::{Angle, Deg};
+use crate::backproject::Perspective;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::sampling::RenderOptions;
+use crate::stitch::Mosaic;
+
+/// One of the two poles of the panorama.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pole {
+    /// Straight down, where tripods and camera operators end up.
+    #[default]
+    Nadir,
+    /// Straight up.
+    Zenith,
+}
+
+impl Pole {
+    fn pitch(self) -> f64 {
+        match self {
+            Pole::Nadir => -90.0,
+            Pole::Zenith => 90.0,
+        }
+    }
+}
+
+/// A square view of a pole for retouching: extract it, edit it (tripod
+/// removal, a logo, ...) and merge it back. The top of a nadir patch faces
+/// longitude 0, and so does the bottom of a zenith patch.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PolePatch {
+    pub(crate) pole: Pole,
+    pub(crate) fov: f64,
+    pub(crate) size: u32,
+    pub(crate) feather: f64,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for PolePatch {
+    fn default() -> Self {
+        PolePatch::new(Pole::default())
+    }
+}
+
+impl PolePatch {
+    pub fn new(pole: Pole) -> PolePatch {
+        PolePatch {
+            pole,
+            fov: 90.0,
+            size: 1024,
+            feather: 0.1,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Field of view across the patch, below 180 degrees.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    /// Width and height of the square patch.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Feathered margin along the patch edges when it is merged back, as a
+    /// fraction of its size; see [`Mosaic::feather`].
+    pub fn feather(mut self, feather: f64) -> Self {
+        self.feather = feather;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.size == 0 {
+            return Err(Error::InvalidParameter("patch size must be non-zero".to_string()));
+        }
+        self.view().validate()
+    }
+
+    /// The perspective view the patch is rendered with.
+    pub fn view(&self) -> PerspectiveParams {
+        PerspectiveParams::new()
+            .fov(Deg(self.fov))
+            .pitch(Deg(self.pole.pitch()))
+            .output_size(self.size, self.size)
+            .with_render_options(&self.options)
+    }
+
+    pub fn extract(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        self.validate()?;
+        self.view().project(pano)
+    }
+
+    /// Blends an edited `patch` of the patch size back into `pano`, which
+    /// must be uncropped. Outside the feathered margin the patch replaces
+    /// the panorama.
+    pub fn replace(&self, pano: &Equirectangular, patch: prelude::Mat) -> Result<Equirectangular> {
+        self.validate()?;
+        let view = Perspective::new(patch, self.view())?;
+        Mosaic::new((pano.width(), pano.height()))
+            .feather(self.feather)
+            .options(self.options)
+            .merge_into(pano, &[view])
+    }
+}
+
+impl Equirectangular {
+    /// Top-down `size`x`size` view of the nadir with a `fov` degree field
+    /// of view.
+    pub fn extract_nadir(&self, fov: f64, size: u32) -> Result<prelude::Mat> {
+        PolePatch::new(Pole::Nadir).fov(Deg(fov)).size(size).extract(self)
+    }
+
+    /// Merges a nadir patch produced by [`extract_nadir`](Self::extract_nadir)
+    /// with the same `fov` back into the panorama.
+    pub fn replace_nadir(&self, patch: prelude::Mat, fov: f64) -> Result<Equirectangular> {
+        let size = patch.cols() as u32;
+        PolePatch::new(Pole::Nadir).fov(Deg(fov)).size(size).replace(self, patch)
+    }
+}
@@ -0,0 +1,167 @@
+//! Process-wide pool of frame buffers, so long-running processes that render
+//! the same sizes over and over (video, servers) reuse memory instead of
+//! allocating every intermediate and output afresh.
+//!
+//! Intermediates go back to the pool on their own. Rendered frames belong
+//! to the caller; handing them back with [`recycle`] once they are consumed
+//! lets the next render write into them.
+
+use std::sync::{Mutex, MutexGuard};
+
+use opencv::prelude;
+use opencv::prelude::{MatTrait, MatTraitConst};
+
+use crate::error::Result;
+use crate::sampling::Border;
+use crate::stats;
+
+/// Mats and buffers kept of each kind unless changed with [`set_capacity`].
+pub const DEFAULT_CAPACITY: usize = 8;
+
+struct Pool {
+    capacity: usize,
+    /// Oldest first.
+    mats: Vec<prelude::Mat>,
+    buffers: Vec<Vec<f32>>,
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    capacity: DEFAULT_CAPACITY,
+    mats: Vec::new(),
+    buffers: Vec::new(),
+});
+
+fn pool() -> MutexGuard<'static, Pool> {
+    // The pool holds no invariants a panicking thread could break.
+    POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A pooled mat of `rows` x `cols` and type `typ` for OpenCV to write into,
+/// or an empty one it will allocate when none is free.
+pub(crate) fn take_mat(rows: i32, cols: i32, typ: i32) -> prelude::Mat {
+    let mut pool = pool();
+    match pool.mats.iter().position(|mat| mat.rows() == rows && mat.cols() == cols && mat.typ() == typ) {
+        Some(index) => pool.mats.swap_remove(index),
+        None => {
+            stats::count_allocation();
+            prelude::Mat::default()
+        }
+    }
+}
+
+/// [`take_mat`] for a fresh render with `border`: pooled mats still hold
+/// an old frame, which [`Border::Transparent`] would leave showing in the
+/// pixels it does not cover, so those are zeroed first.
+pub(crate) fn take_output(rows: i32, cols: i32, typ: i32, border: Border) -> Result<prelude::Mat> {
+    let mut mat = take_mat(rows, cols, typ);
+    if border == Border::Transparent && !mat.empty() {
+        mat.set_to(&opencv::core::Scalar::all(0.0), &opencv::core::no_array())?;
+    }
+    Ok(mat)
+}
+
+/// Hands a frame back for later renders to write into. The frame must not
+/// share its data with another mat still in use; ROIs and non-continuous
+/// mats are dropped instead.
+pub fn recycle(frame: prelude::Mat) {
+    if frame.empty() || frame.is_submatrix() || !frame.is_continuous() {
+        return;
+    }
+    let mut pool = pool();
+    if pool.capacity == 0 {
+        return;
+    }
+    if pool.mats.len() >= pool.capacity {
+        pool.mats.remove(0);
+    }
+    pool.mats.push(frame);
+}
+
+/// A zeroed `f32` array of `shape` backed by a pooled buffer; give it back
+/// with [`recycle_array`].
+pub(crate) fn zeros(shape: (usize, usize)) -> Result<ndarray::Array2<f32>> {
+    let len = shape.0 * shape.1;
+    let mut buffer = {
+        let mut pool = pool();
+        match pool.buffers.iter().position(|buffer| buffer.capacity() >= len) {
+            Some(index) => pool.buffers.swap_remove(index),
+            None => {
+                stats::count_allocation();
+                Vec::new()
+            }
+        }
+    };
+    buffer.clear();
+    buffer.resize(len, 0.0);
+    Ok(ndarray::Array2::from_shape_vec(shape, buffer)?)
+}
+
+/// [`zeros`] filled with `f` of each `(row, column)`.
+pub(crate) fn from_shape_fn(shape: (usize, usize), f: impl Fn((usize, usize)) -> f32) -> Result<ndarray::Array2<f32>> {
+    let mut array = zeros(shape)?;
+    array.indexed_iter_mut().for_each(|(index, value)| *value = f(index));
+    Ok(array)
+}
+
+pub(crate) fn recycle_array(array: ndarray::Array2<f32>) {
+    let (buffer, _) = array.into_raw_vec_and_offset();
+    let mut pool = pool();
+    if pool.capacity == 0 {
+        return;
+    }
+    if pool.buffers.len() >= pool.capacity {
+        pool.buffers.remove(0);
+    }
+    pool.buffers.push(buffer);
+}
+
+/// Sets how many mats and how many array buffers are kept, dropping the
+/// oldest beyond it; 0 disables pooling.
+pub fn set_capacity(capacity: usize) {
+    let mut pool = pool();
+    pool.capacity = capacity;
+    let excess = pool.mats.len().saturating_sub(capacity);
+    pool.mats.drain(..excess);
+    let excess = pool.buffers.len().saturating_sub(capacity);
+    pool.buffers.drain(..excess);
+}
+
+pub fn capacity() -> usize {
+    pool().capacity
+}
+
+/// Frees every pooled buffer.
+pub fn clear() {
+    let mut pool = pool();
+    pool.mats.clear();
+    pool.buffers.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Deg;
+    use crate::params::PerspectiveParams;
+    use crate::perspective::{CroppedPanoParams, Equirectangular};
+    use opencv::prelude::MatTraitConstManual;
+
+    fn filled(rows: i32, cols: i32) -> prelude::Mat {
+        prelude::Mat::new_rows_cols_with_default(rows, cols, opencv::core::CV_8UC3, opencv::core::Scalar::all(200.0)).unwrap()
+    }
+
+    #[test]
+    fn recycled_frames_do_not_show_through_transparent_borders() {
+        // An odd size no other test renders, so the recycled frame is the one
+        // taken back.
+        let params = PerspectiveParams::new().output_size(37, 23).border(Border::Transparent);
+        let full = Equirectangular::from_mat(filled(128, 256)).unwrap();
+        recycle(params.project(&full).unwrap());
+
+        // The crop covers longitudes -180 to -90 only, so a view at 90 sees
+        // none of it.
+        let crop = CroppedPanoParams { full_width: 256, full_height: 128, left: 0, top: 32 };
+        let cropped = Equirectangular::from_mat(filled(64, 64)).unwrap().with_crop(crop).unwrap();
+        let view = params.clone().yaw(Deg(90.0)).project(&cropped).unwrap();
+        assert!(view.data_bytes().unwrap().iter().all(|&value| value == 0));
+    }
+}
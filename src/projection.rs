@@ -0,0 +1,93 @@
+use crate::coords;
+use crate::distortion::Distortion;
+use crate::error::Result;
+use crate::params::PerspectiveParams;
+use crate::rotation::{self, Matrix3};
+
+pub type Vec3 = [f64; 3];
+
+/// An output camera model: which direction on the sphere each output pixel
+/// looks at. Implementing this is all a new projection needs to be rendered
+/// with [`Equirectangular::render_projection`](crate::Equirectangular::render_projection)
+/// or cached in a [`Projector`](crate::Projector).
+pub trait Projection {
+    /// Output size as `(width, height)`.
+    fn output_size(&self) -> (u32, u32);
+
+    /// World direction (x right, y down, z forward at longitude 0, not
+    /// necessarily unit length) seen through pixel `(u, v)`, or `None` for
+    /// pixels outside the projection's image area, which are filled with the
+    /// border color.
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3>;
+}
+
+/// A pinhole camera resolved from [`PerspectiveParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerspectiveCamera {
+    k: Matrix3,
+    distortion: Option<Distortion>,
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+}
+
+impl PerspectiveCamera {
+    pub fn new(params: &PerspectiveParams) -> Result<PerspectiveCamera> {
+        params.validate()?;
+        Ok(PerspectiveCamera {
+            k: params.intrinsics(),
+            distortion: params.distortion_coefficients(),
+            rotation: params.rotation()?,
+            width: params.width,
+            height: params.height,
+        })
+    }
+}
+
+impl Projection for PerspectiveCamera {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        Some(rotation::apply(&self.rotation, coords::unproject_lens(&self.k, self.distortion.as_ref(), u, v)))
+    }
+}
+
+/// A full equirectangular frame, optionally rotated: pixel `(x, y)` looks at
+/// the same longitude and latitude as in the source panorama convention,
+/// then `rotation` is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquirectProjection {
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+}
+
+impl EquirectProjection {
+    pub fn new(width: u32, height: u32) -> EquirectProjection {
+        EquirectProjection {
+            rotation: rotation::IDENTITY,
+            width,
+            height,
+        }
+    }
+
+    /// Camera-to-world rotation of the frame; the output's center looks
+    /// along the rotation's forward axis.
+    pub fn with_rotation(mut self, rotation: Matrix3) -> EquirectProjection {
+        self.rotation = rotation;
+        self
+    }
+}
+
+impl Projection for EquirectProjection {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let (lon, lat) = coords::equirect_to_lonlat(u, v, self.width, self.height);
+        Some(rotation::apply(&self.rotation, coords::lonlat_to_ray(lon, lat)))
+    }
+}
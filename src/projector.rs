@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+#[cfg(feature = "cuda")]
+use opencv::prelude::GpuMatTraitConst;
+
+use crate::batch::{self, BatchReport};
+use crate::effects;
+use crate::error::{Error, Result};
+use crate::maps;
+use crate::output::EncodeOptions;
+use crate::params::PerspectiveParams;
+use crate::perspective::{CroppedPanoParams, Equirectangular};
+use crate::pool;
+use crate::projection::Projection;
+use crate::rotation::{self, Matrix3};
+use crate::sampling::{Interpolation, RenderOptions};
+use crate::source::SourceProjection;
+use crate::table::RayTable;
+
+/// A fixed virtual camera with its remap maps precomputed, so each frame
+/// only costs a single `remap`.
+pub struct Projector {
+    params: Option<PerspectiveParams>,
+    options: RenderOptions,
+    pano_width: u32,
+    pano_height: u32,
+    crop: Option<CroppedPanoParams>,
+    orientation: Option<Matrix3>,
+    maps: maps::RemapMaps,
+}
+
+fn check_pano_size(pano_size: (u32, u32)) -> Result<()> {
+    let (pano_width, pano_height) = pano_size;
+    if pano_width == 0 || pano_height == 0 {
+        return Err(Error::InvalidParameter(format!("panorama size must be non-zero, got {pano_width}x{pano_height}")));
+    }
+    Ok(())
+}
+
+impl Projector {
+    /// Builds the maps for panoramas of `pano_size` `(width, height)`.
+    pub fn new(params: &PerspectiveParams, pano_size: (u32, u32)) -> Result<Projector> {
+        Projector::build(params, pano_size, None, None)
+    }
+
+    /// Builds the maps for panoramas with the size, crop and orientation of
+    /// `pano`.
+    pub fn for_panorama(params: &PerspectiveParams, pano: &Equirectangular) -> Result<Projector> {
+        Projector::build(params, (pano.width(), pano.height()), pano.crop(), pano.orientation())
+    }
+
+    /// Builds the maps for panoramas of `pano_size` from the precomputed
+    /// camera rays of `table`, which must match the output size and lens of
+    /// `params`. Only the rotation and lon/lat conversion remain per view.
+    pub fn from_table(table: &RayTable, params: &PerspectiveParams, pano_size: (u32, u32)) -> Result<Projector> {
+        table.check(params)?;
+        Projector::build_with(params, pano_size, None, None, |to_pano, size, crop| Ok(maps::table_maps(table, to_pano, size, crop)))
+    }
+
+    fn build(
+        params: &PerspectiveParams,
+        pano_size: (u32, u32),
+        crop: Option<CroppedPanoParams>,
+        orientation: Option<Matrix3>,
+    ) -> Result<Projector> {
+        Projector::build_with(params, pano_size, crop, orientation, |to_pano, size, crop| {
+            maps::perspective_maps(params, to_pano, size, crop)
+        })
+    }
+
+    fn build_with(
+        params: &PerspectiveParams,
+        pano_size: (u32, u32),
+        crop: Option<CroppedPanoParams>,
+        orientation: Option<Matrix3>,
+        build_maps: impl FnOnce(&Matrix3, (usize, usize), Option<&CroppedPanoParams>) -> Result<maps::Maps>,
+    ) -> Result<Projector> {
+        params.validate()?;
+        check_pano_size(pano_size)?;
+        let (pano_width, pano_height) = pano_size;
+        let camera = params.rotation()?;
+        let to_pano = orientation.map_or(camera, |orientation| rotation::matmul(&orientation, &camera));
+        let maps = build_maps(&to_pano, (pano_height as usize, pano_width as usize), crop.as_ref())?;
+
+        Ok(Projector {
+            params: Some(params.clone()),
+            options: params.render_options().for_coverage(crop.is_some()),
+            pano_width,
+            pano_height,
+            crop,
+            orientation,
+            maps: maps::RemapMaps::new(&maps)?,
+        })
+    }
+
+    /// Switches CPU remaps to fixed-point `CV_16SC2` maps, trading source
+    /// positions rounded to 1/32 pixel for faster remaps of large outputs.
+    /// The `f32` maps are kept for [`maps`](Self::maps) and the GPU backends.
+    pub fn with_fixed_point(mut self) -> Result<Projector> {
+        self.maps.set_fixed_point(self.options.interpolation == Interpolation::Nearest)?;
+        Ok(self)
+    }
+
+    /// Turns the view by `delta`, a world-frame rotation applied on top of
+    /// the camera's, as when a viewer pans. A pure turn about the vertical
+    /// axis of an uncropped panorama only shifts every source column by the
+    /// same amount, so the maps are offset in place instead of rebuilt;
+    /// anything else recomputes them once for the composed rotation.
+    ///
+    /// The offset is rounded to `f32` on every call, so after many small
+    /// turns the maps can drift by a fraction of a pixel from a fresh build.
+    pub fn rotate(&mut self, delta: &Matrix3) -> Result<()> {
+        rotation::validate_matrix(delta)?;
+        let Some(params) = &self.params else {
+            return Err(Error::InvalidParameter("only perspective projectors can be rotated".to_string()));
+        };
+        let camera = rotation::matmul(delta, &params.rotation()?);
+        let params = params.clone().rotation_matrix(camera);
+        // The delta as seen in the panorama's own frame.
+        let turn = match &self.orientation {
+            Some(orientation) => rotation::matmul(&rotation::matmul(orientation, delta), &rotation::transpose(orientation)),
+            None => *delta,
+        };
+        if self.crop.is_none() && (turn[1][1] - 1.0).abs() < 1e-12 {
+            // A turn by `angle` about y adds `angle` to every longitude.
+            let angle = turn[0][2].atan2(turn[0][0]);
+            let period = self.pano_width as f64 - 1.0;
+            self.maps.shift_x((angle / (2.0 * std::f64::consts::PI) * period) as f32, period as f32)?;
+            self.params = Some(params);
+            return Ok(());
+        }
+        let fixed_point = self.maps.is_fixed_point();
+        *self = Projector::build(&params, (self.pano_width, self.pano_height), self.crop, self.orientation)?;
+        if fixed_point {
+            self.maps.set_fixed_point(self.options.interpolation == Interpolation::Nearest)?;
+        }
+        Ok(())
+    }
+
+    /// Builds the maps of any output [`Projection`] over any
+    /// [`SourceProjection`], e.g. [`Equirectangular::source`].
+    pub fn from_projection<P, S>(projection: &P, source: &S, options: &RenderOptions) -> Result<Projector>
+    where
+        P: Projection + ?Sized,
+        S: SourceProjection + ?Sized,
+    {
+        let (pano_width, pano_height) = source.source_size();
+        check_pano_size((pano_width, pano_height))?;
+        let maps = maps::build_maps(projection, source);
+
+        Ok(Projector {
+            params: None,
+            options: options.for_coverage(!source.wraps_horizontally()),
+            pano_width,
+            pano_height,
+            crop: None,
+            orientation: None,
+            maps: maps::RemapMaps::new(&maps)?,
+        })
+    }
+
+    /// The perspective view this projector renders, if it was built from one.
+    pub fn params(&self) -> Option<&PerspectiveParams> {
+        self.params.as_ref()
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// The sampling maps: for every output pixel, the source x and y
+    /// coordinate it is read from.
+    pub fn maps(&self) -> Result<(ndarray::Array2<f32>, ndarray::Array2<f32>)> {
+        Ok((maps::to_array(&self.maps.map_x)?, maps::to_array(&self.maps.map_y)?))
+    }
+
+    /// The sampling maps as `CV_32FC1` mats, ready for `opencv::imgproc::remap`.
+    pub fn map_mats(&self) -> (&prelude::Mat, &prelude::Mat) {
+        (&self.maps.map_x, &self.maps.map_y)
+    }
+
+    /// Projects one frame. `src` must match the panorama size the projector
+    /// was built for.
+    pub fn apply(&self, src: &prelude::Mat) -> Result<prelude::Mat> {
+        let mut dst = pool::take_output(self.maps.map_x.rows(), self.maps.map_x.cols(), src.typ(), self.options.border)?;
+        self.apply_into(src, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Projects one frame into `dst`, reusing its buffer when it already has
+    /// the output size and type. With [`Border::Transparent`](crate::Border::Transparent)
+    /// uncovered pixels keep whatever `dst` held before.
+    pub fn apply_into(&self, src: &prelude::Mat, dst: &mut prelude::Mat) -> Result<()> {
+        self.check_source(src)?;
+        match &self.params {
+            Some(params) => effects::render_into(&self.maps, src, params, &self.options, dst),
+            None => self.maps.render_into(src, &self.options, dst),
+        }
+    }
+
+    /// Projects every panorama in `paths` into `out_dir`, keeping each
+    /// file's name and format. The maps are shared, so each file only costs
+    /// its decode, one `remap` and its encode, and one thread per core
+    /// overlaps the IO and encoding of different files. A failing file does
+    /// not stop the others. Inputs sharing a file name, or an `out_dir`
+    /// that would write over an input, are rejected before anything is
+    /// rendered.
+    pub fn apply_many<P: AsRef<Path>>(&self, paths: impl IntoIterator<Item = P>, out_dir: impl AsRef<Path>) -> Result<BatchReport> {
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir)?;
+        let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_path_buf()).collect();
+        let mut jobs = Vec::with_capacity(paths.len());
+        let mut names = HashSet::new();
+        let inputs: HashSet<PathBuf> = paths.iter().map(|path| batch::resolve(path)).collect();
+        for path in &paths {
+            let name = path
+                .file_name()
+                .ok_or_else(|| Error::InvalidParameter(format!("{} does not name a file", path.display())))?;
+            if !names.insert(name) {
+                return Err(Error::InvalidParameter(format!("several inputs are named {}", Path::new(name).display())));
+            }
+            let output = out_dir.join(name);
+            if inputs.contains(&batch::resolve(&output)) {
+                return Err(Error::InvalidParameter(format!("writing {} would replace an input", output.display())));
+            }
+            jobs.push((path, output));
+        }
+        let next = AtomicUsize::new(0);
+        let report = Mutex::new(BatchReport::default());
+        let project = |path: &Path, output: &Path| -> Result<()> {
+            let src = opencv::imgcodecs::imread(batch::path_str(path)?, opencv::imgcodecs::IMREAD_UNCHANGED)?;
+            if src.empty() {
+                return Err(Error::Decode(format!("{} is not a supported image", path.display())));
+            }
+            let image = self.apply(&src)?;
+            batch::write(output, &image, &EncodeOptions::default())?;
+            pool::recycle(image);
+            Ok(())
+        };
+
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some((path, output)) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let result = project(path, output);
+                        let mut report = report.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                        match result {
+                            Ok(()) => report.written += 1,
+                            Err(error) => report.failures.push((path.to_path_buf(), error)),
+                        }
+                    }
+                });
+            }
+        });
+        Ok(report.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// Projects a frame that already lives on the GPU into `dst`, queued on
+    /// `stream`. The maps are uploaded on the first call and stay on the
+    /// device, so video frames never round-trip through host memory. The
+    /// output keeps the source format and skips capture effects.
+    #[cfg(feature = "cuda")]
+    pub fn apply_device(&self, src: &opencv::core::GpuMat, dst: &mut opencv::core::GpuMat, stream: &mut opencv::core::Stream) -> Result<()> {
+        self.check_size(src.cols(), src.rows())?;
+        self.maps.remap_cuda(src, &self.options, dst, stream)
+    }
+
+    fn check_source(&self, src: &prelude::Mat) -> Result<()> {
+        self.check_size(src.cols(), src.rows())
+    }
+
+    fn check_size(&self, cols: i32, rows: i32) -> Result<()> {
+        if cols != self.pano_width as i32 || rows != self.pano_height as i32 {
+            return Err(Error::InvalidParameter(format!(
+                "projector was built for {}x{} panoramas, got {}x{}",
+                self.pano_width, self.pano_height, cols, rows
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_many_never_overwrites() {
+        let dir = std::env::temp_dir().join(format!("e2p-apply-many-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let projector = Projector::new(&PerspectiveParams::new().output_size(16, 16), (64, 32)).unwrap();
+        let same_name = projector.apply_many([dir.join("a/pano.jpg"), dir.join("b/pano.jpg")], dir.join("views"));
+        assert!(matches!(same_name, Err(Error::InvalidParameter(_))));
+        let in_place = projector.apply_many([dir.join("pano.jpg")], &dir);
+        assert!(matches!(in_place, Err(Error::InvalidParameter(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
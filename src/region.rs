@@ -0,0 +1,252 @@
+use opencv::prelude;
+
+use crate::angle::Deg;
+use crate::annotate::LonLatBox;
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation;
+use crate::sampling::RenderOptions;
+
+/// How a [`RegionCrop`] lays out its region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RegionStyle {
+    /// Columns evenly spaced in longitude and rows in latitude, like a
+    /// piece of the equirectangular image itself.
+    #[default]
+    LonLat,
+    /// A perspective view centered on the region and just large enough to
+    /// contain it. Regions must fit in one hemisphere around their center.
+    Gnomonic,
+}
+
+/// Extracts the part of the sphere inside a [`LonLatBox`], at a chosen
+/// resolution. Regions may wrap across the ±180° meridian.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionCrop {
+    pub(crate) region: LonLatBox,
+    pub(crate) style: RegionStyle,
+    pub(crate) width: Option<u32>,
+    pub(crate) options: RenderOptions,
+}
+
+impl RegionCrop {
+    pub fn new(region: LonLatBox) -> RegionCrop {
+        RegionCrop {
+            region,
+            style: RegionStyle::default(),
+            width: None,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn style(mut self, style: RegionStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Output width; the height follows from the region's shape. Without
+    /// it the crop keeps the panorama's resolution.
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let LonLatBox { lat_min, lat_max, .. } = self.region;
+        if !(lat_min >= -90.0 && lat_min < lat_max && lat_max <= 90.0) {
+            return Err(Error::InvalidParameter(format!("latitude range [{lat_min}, {lat_max}] is empty or out of range")));
+        }
+        if !(self.region.lon_min.is_finite() && self.region.lon_span() > 0.0) {
+            return Err(Error::InvalidParameter(format!("longitude range [{}, {}] is empty", self.region.lon_min, self.region.lon_max)));
+        }
+        if let Some(width) = self.width {
+            if width < 2 {
+                return Err(Error::InvalidParameter(format!("crop width must be at least 2, got {width}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Window for [`RegionStyle::LonLat`], `width` pixels wide.
+    pub fn window(&self, width: u32) -> Result<LonLatWindow> {
+        self.validate()?;
+        if width < 2 {
+            return Err(Error::InvalidParameter(format!("crop width must be at least 2, got {width}")));
+        }
+        let lon_span = self.region.lon_span();
+        let lat_span = self.region.lat_max - self.region.lat_min;
+        let height = ((width as f64 - 1.0) * lat_span / lon_span).round() as u32 + 1;
+        Ok(LonLatWindow {
+            lon_min: self.region.lon_min,
+            lon_span,
+            lat_max: self.region.lat_max,
+            lat_span,
+            width,
+            height: height.max(2),
+        })
+    }
+
+    /// View for [`RegionStyle::Gnomonic`], `width` pixels wide, with
+    /// intrinsics fitted tightly around the region.
+    pub fn perspective(&self, width: u32) -> Result<PerspectiveParams> {
+        self.validate()?;
+        let (lon, lat) = self.center();
+        let (min, max) = self.tangent_bounds(lon, lat)?;
+        let focal = width as f64 / (max.0 - min.0);
+        let height = (focal * (max.1 - min.1)).round().max(1.0) as u32;
+        Ok(PerspectiveParams::new()
+            .yaw(Deg(lon))
+            .pitch(Deg(lat))
+            .output_size(width, height)
+            .with_intrinsics([
+                [focal, 0.0, -min.0 * focal - 0.5],
+                [0.0, focal, -min.1 * focal - 0.5],
+                [0.0, 0.0, 1.0],
+            ])
+            .with_render_options(&self.options))
+    }
+
+    /// The ordinary centered, unrolled perspective view of `width` by
+    /// `height` pixels with the smallest field of view that still contains
+    /// the whole region.
+    pub fn fit_view(&self, width: u32, height: u32) -> Result<PerspectiveParams> {
+        self.validate()?;
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidParameter(format!("view size must be non-zero, got {width}x{height}")));
+        }
+        // The region's extent on the image plane is lopsided away from the
+        // lon/lat midpoint, so re-aim at the middle of its bounds until the
+        // view is centered on them.
+        let (mut lon, mut lat) = self.center();
+        for _ in 0..8 {
+            let (min, max) = self.tangent_bounds(lon, lat)?;
+            let middle = [(min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0, 1.0];
+            let direction = rotation::apply(&rotation::euler_to_matrix(lon, lat, 0.0), middle);
+            let (next_lon, next_lat) = coords::ray_to_lonlat(direction);
+            let moved = ((next_lon - lon + 180.0).rem_euclid(360.0) - 180.0, next_lat - lat);
+            (lon, lat) = (next_lon, next_lat);
+            if moved.0.abs() < 1e-6 && moved.1.abs() < 1e-6 {
+                break;
+            }
+        }
+        let (min, max) = self.tangent_bounds(lon, lat)?;
+        let aspect = width as f64 / height as f64;
+        let half_x = min.0.abs().max(max.0.abs());
+        let half_y = min.1.abs().max(max.1.abs());
+        let fov_x = 2.0 * half_x.max(aspect * half_y).atan().to_degrees();
+        if fov_x >= 179.0 {
+            return Err(Error::InvalidParameter(format!("{:?} is too large to fit in a perspective view", self.region)));
+        }
+        Ok(PerspectiveParams::new()
+            .yaw(Deg(lon))
+            .pitch(Deg(lat))
+            .fov_with_aspect(Deg(fov_x), aspect)
+            .output_size(width, height)
+            .with_render_options(&self.options))
+    }
+
+    /// [`fit_view`](Self::fit_view) rendered from `pano`, paired with the
+    /// view it used.
+    pub fn fit(&self, pano: &Equirectangular, width: u32, height: u32) -> Result<(PerspectiveParams, prelude::Mat)> {
+        let params = self.fit_view(width, height)?;
+        let image = params.project(pano)?;
+        Ok((params, image))
+    }
+
+    /// Bounds `(min, max)` of the region's outline on the image plane of
+    /// an unrolled view looking at `(lon, lat)`.
+    fn tangent_bounds(&self, lon: f64, lat: f64) -> Result<((f64, f64), (f64, f64))> {
+        let world_to_camera = rotation::transpose(&rotation::euler_to_matrix(lon, lat, 0.0));
+        let (mut min, mut max) = ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY));
+        for (lon, lat) in self.region.outline() {
+            let [x, y, z] = rotation::apply(&world_to_camera, coords::lonlat_to_ray(lon, lat));
+            if z < 1e-3 {
+                return Err(Error::InvalidParameter(format!("{:?} is too large for a gnomonic crop", self.region)));
+            }
+            let (x, y) = (x / z, y / z);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        Ok((min, max))
+    }
+
+    fn center(&self) -> (f64, f64) {
+        let lon = self.region.lon_min + self.region.lon_span() / 2.0;
+        (
+            (lon + 180.0).rem_euclid(360.0) - 180.0,
+            (self.region.lat_min + self.region.lat_max) / 2.0,
+        )
+    }
+
+    /// Output width matching the pixel density of `pano` at the region.
+    fn native_width(&self, pano: &Equirectangular) -> u32 {
+        let full_width = pano.crop().map_or(pano.width(), |crop| crop.full_width) as f64;
+        let width = match self.style {
+            RegionStyle::LonLat => self.region.lon_span() / 360.0 * (full_width - 1.0) + 1.0,
+            RegionStyle::Gnomonic => {
+                let half = (self.region.lon_span() / 2.0).min(89.0).to_radians();
+                let lat = self.center().1.abs().min(89.0).to_radians();
+                2.0 * half.tan() * lat.cos() * full_width / std::f64::consts::TAU
+            }
+        };
+        (width.round() as u32).max(2)
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        let width = self.width.unwrap_or_else(|| self.native_width(pano));
+        match self.style {
+            RegionStyle::LonLat => pano.render_projection(&self.window(width)?, &self.options),
+            RegionStyle::Gnomonic => self.perspective(width)?.project(pano),
+        }
+    }
+}
+
+/// A longitude-latitude window resolved from [`RegionCrop`]: the corner
+/// pixels sit on the region's corners.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LonLatWindow {
+    lon_min: f64,
+    lon_span: f64,
+    lat_max: f64,
+    lat_span: f64,
+    width: u32,
+    height: u32,
+}
+
+impl Projection for LonLatWindow {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let lon = self.lon_min + u / (self.width as f64 - 1.0) * self.lon_span;
+        let lat = self.lat_max - v / (self.height as f64 - 1.0) * self.lat_span;
+        Some(coords::lonlat_to_ray(lon, lat))
+    }
+}
+
+impl Equirectangular {
+    /// The region between the given meridians and parallels (degrees) at
+    /// the panorama's resolution. `lon_max` below `lon_min` wraps across
+    /// the ±180° meridian.
+    pub fn crop_region(&self, lon_min: f64, lon_max: f64, lat_min: f64, lat_max: f64) -> Result<prelude::Mat> {
+        RegionCrop::new(LonLatBox::new(lon_min, lon_max, lat_min, lat_max)).project(self)
+    }
+
+    /// The tightest `width` by `height` perspective view containing the
+    /// region between the given meridians and parallels, with its params;
+    /// see [`RegionCrop::fit_view`].
+    pub fn fit_region(&self, lon_min: f64, lon_max: f64, lat_min: f64, lat_max: f64, width: u32, height: u32) -> Result<(PerspectiveParams, prelude::Mat)> {
+        RegionCrop::new(LonLatBox::new(lon_min, lon_max, lat_min, lat_max)).fit(self, width, height)
+    }
+}
@@ -0,0 +1,47 @@
+//! One entry point for converting between any input and output geometry.
+//!
+//! Sources: [`EquirectSource`](crate::EquirectSource) (or
+//! [`Equirectangular::source`](crate::Equirectangular::source)),
+//! [`CubeMap::source`](crate::CubeMap::source),
+//! [`FisheyeLens::source`](crate::FisheyeLens::source),
+//! [`PanoramaStrip::source`](crate::PanoramaStrip::source) and
+//! [`PerspectiveSource`](crate::PerspectiveSource). Outputs: any
+//! [`Projection`], plus cube maps through [`reproject_to_cubemap`].
+
+use opencv::prelude;
+
+use crate::cubemap::{CubeMap, CubemapParams, FaceView};
+use crate::error::Result;
+use crate::projection::Projection;
+use crate::sampling::RenderOptions;
+use crate::source::{self, SourceProjection};
+
+/// Renders `projection` from `src`, an image whose geometry is `source`.
+pub fn reproject<S, P>(src: &prelude::Mat, source: &S, projection: &P, options: &RenderOptions) -> Result<prelude::Mat>
+where
+    S: SourceProjection + ?Sized,
+    P: Projection + ?Sized,
+{
+    source::render(src, source, projection, options)
+}
+
+/// Like [`reproject`], also returning a `CV_8U` mask of output pixels the
+/// source covers.
+pub fn reproject_with_mask<S, P>(src: &prelude::Mat, source: &S, projection: &P, options: &RenderOptions) -> Result<(prelude::Mat, prelude::Mat)>
+where
+    S: SourceProjection + ?Sized,
+    P: Projection + ?Sized,
+{
+    source::render_with_mask(src, source, projection, options)
+}
+
+/// Renders all six faces of a cube map from any source.
+pub fn reproject_to_cubemap<S: SourceProjection + ?Sized>(src: &prelude::Mat, source: &S, params: &CubemapParams) -> Result<CubeMap> {
+    params.validate()?;
+    let order = params.order.faces();
+    let faces = order
+        .iter()
+        .map(|&face| reproject(src, source, &FaceView::new(face, params.style, params.face_size)?, &params.options))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(CubeMap::from_faces(params.face_size, order, params.style, faces))
+}
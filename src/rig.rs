@@ -0,0 +1,109 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::rotation::{self, Matrix3};
+
+/// One named camera of a [`Rig`]; its view carries the intrinsics and its
+/// orientation and position relative to the rig.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RigCamera {
+    pub name: String,
+    pub params: PerspectiveParams,
+}
+
+/// A set of virtual cameras mounted together, rendered from a panorama in
+/// one call. The rig itself can be turned with [`orientation`](Self::orientation),
+/// which rotates every camera with it.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Rig {
+    pub(crate) cameras: Vec<RigCamera>,
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+}
+
+impl Rig {
+    pub fn new() -> Rig {
+        Rig::default()
+    }
+
+    /// Six 1280x960 cameras around a car: front and rear with 100 degree
+    /// lenses plus four 120 degree side cameras at ±60 and ±120 degrees.
+    pub fn automotive_surround() -> Rig {
+        let camera = |yaw: f64, fov: f64| {
+            PerspectiveParams::new()
+                .fov(Deg(fov))
+                .yaw(Deg(yaw))
+                .output_size(1280, 960)
+        };
+        Rig::new()
+            .camera("front", camera(0.0, 100.0))
+            .camera("front_right", camera(60.0, 120.0))
+            .camera("rear_right", camera(120.0, 120.0))
+            .camera("rear", camera(180.0, 100.0))
+            .camera("rear_left", camera(-120.0, 120.0))
+            .camera("front_left", camera(-60.0, 120.0))
+    }
+
+    pub fn camera(mut self, name: impl Into<String>, params: PerspectiveParams) -> Self {
+        self.cameras.push(RigCamera { name: name.into(), params });
+        self
+    }
+
+    /// Orientation of the whole rig in the panorama.
+    pub fn orientation(mut self, yaw: impl Into<Angle>, pitch: impl Into<Angle>, roll: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self.pitch = pitch.into().degrees();
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    pub fn cameras(&self) -> &[RigCamera] {
+        &self.cameras
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.cameras.is_empty() {
+            return Err(Error::InvalidParameter("rig has no cameras".to_string()));
+        }
+        for (i, camera) in self.cameras.iter().enumerate() {
+            if self.cameras[..i].iter().any(|other| other.name == camera.name) {
+                return Err(Error::InvalidParameter(format!("rig camera name {:?} is used twice", camera.name)));
+            }
+            camera.params.validate()?;
+        }
+        Ok(())
+    }
+
+    fn mount(&self) -> Matrix3 {
+        rotation::euler_to_matrix(self.yaw, self.pitch, self.roll)
+    }
+
+    /// Every camera's view in panorama coordinates, with the rig
+    /// orientation applied.
+    pub fn views(&self) -> Result<Vec<PerspectiveParams>> {
+        self.validate()?;
+        let mount = self.mount();
+        self.cameras
+            .iter()
+            .map(|camera| {
+                let world = rotation::matmul(&mount, &camera.params.rotation()?);
+                let position = rotation::apply(&mount, camera.params.translation_vector());
+                Ok(camera.params.clone().rotation_matrix(world).translation(position))
+            })
+            .collect()
+    }
+
+    /// Renders every camera, in the order they were added, as
+    /// `(name, image)` pairs.
+    pub fn apply(&self, pano: &Equirectangular) -> Result<Vec<(String, prelude::Mat)>> {
+        let images = pano.get_perspectives(&self.views()?)?;
+        Ok(self.cameras.iter().map(|camera| camera.name.clone()).zip(images).collect())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::coords;
+use crate::error::{Error, Result};
+
+pub type Matrix3 = [[f64; 3]; 3];
+
+pub const IDENTITY: Matrix3 = [
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+];
+
+pub fn matmul(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+pub fn transpose(m: &Matrix3) -> Matrix3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+}
+
+pub fn apply(m: &Matrix3, v: [f64; 3]) -> [f64; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+/// Rodrigues rotation of `angle` radians about the unit vector `axis`.
+pub fn axis_angle(axis: [f64; 3], angle: f64) -> Matrix3 {
+    let [x, y, z] = axis;
+    let (s, c) = angle.sin_cos();
+    let t = 1.0 - c;
+    [
+        [c + t * x * x, t * x * y - s * z, t * x * z + s * y],
+        [t * x * y + s * z, c + t * y * y, t * y * z - s * x],
+        [t * x * z - s * y, t * y * z + s * x, c + t * z * z],
+    ]
+}
+
+/// Camera rotation for yaw about the vertical axis, then pitch about the
+/// rotated horizontal axis, then roll about the resulting viewing direction.
+/// All angles in degrees.
+pub fn euler_to_matrix(yaw: f64, pitch: f64, roll: f64) -> Matrix3 {
+    let r1 = axis_angle([0.0, 1.0, 0.0], yaw.to_radians());
+    let r2 = axis_angle(apply(&r1, [1.0, 0.0, 0.0]), pitch.to_radians());
+    let r21 = matmul(&r2, &r1);
+    let r3 = axis_angle(apply(&r21, [0.0, 0.0, 1.0]), roll.to_radians());
+    matmul(&r3, &r21)
+}
+
+/// Order in which yaw (about the vertical y axis), pitch (about x) and roll
+/// (about the z viewing axis) are composed, as used by tools that name
+/// their angles this way. Axes are this crate's frame, so a tool with z up
+/// needs its axis names mapped first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::upper_case_acronyms)]
+pub enum EulerOrder {
+    /// Yaw, then pitch, then roll: [`euler_to_matrix`].
+    #[default]
+    YXZ,
+    YZX,
+    XYZ,
+    XZY,
+    ZXY,
+    ZYX,
+}
+
+/// Whether each Euler rotation turns about the axes as already rotated by
+/// the previous ones (intrinsic) or about the fixed world axes (extrinsic).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EulerFrame {
+    #[default]
+    Intrinsic,
+    Extrinsic,
+}
+
+/// Like [`euler_to_matrix`] with an explicit composition order and frame.
+/// Extrinsic `XYZ` equals intrinsic `ZYX` and so on.
+pub fn euler_to_matrix_with(yaw: f64, pitch: f64, roll: f64, order: EulerOrder, frame: EulerFrame) -> Matrix3 {
+    let about = |axis: char| match axis {
+        'X' => axis_angle([1.0, 0.0, 0.0], pitch.to_radians()),
+        'Y' => axis_angle([0.0, 1.0, 0.0], yaw.to_radians()),
+        _ => axis_angle([0.0, 0.0, 1.0], roll.to_radians()),
+    };
+    let axes = match order {
+        EulerOrder::YXZ => ['Y', 'X', 'Z'],
+        EulerOrder::YZX => ['Y', 'Z', 'X'],
+        EulerOrder::XYZ => ['X', 'Y', 'Z'],
+        EulerOrder::XZY => ['X', 'Z', 'Y'],
+        EulerOrder::ZXY => ['Z', 'X', 'Y'],
+        EulerOrder::ZYX => ['Z', 'Y', 'X'],
+    };
+    let [a, b, c] = axes.map(about);
+    // Intrinsic rotations compose left to right, extrinsic right to left.
+    match frame {
+        EulerFrame::Intrinsic => matmul(&matmul(&a, &b), &c),
+        EulerFrame::Extrinsic => matmul(&matmul(&c, &b), &a),
+    }
+}
+
+/// Rotation matrix for a `[w, x, y, z]` quaternion. The quaternion does not
+/// need to be normalized but must be non-zero.
+pub fn quat_to_matrix(q: [f64; 4]) -> Result<Matrix3> {
+    let norm = q.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if !norm.is_finite() || norm <= f64::EPSILON {
+        return Err(Error::InvalidParameter(format!("quaternion {q:?} has no valid rotation")));
+    }
+    let [w, x, y, z] = q.map(|v| v / norm);
+    Ok([
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+        [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+    ])
+}
+
+pub fn determinant(m: &Matrix3) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Checks that `m` is a proper rotation (orthonormal with determinant 1).
+pub fn validate_matrix(m: &Matrix3) -> Result<()> {
+    let should_be_identity = matmul(m, &transpose(m));
+    let orthonormal = (0..3).all(|i| (0..3).all(|j| (should_be_identity[i][j] - IDENTITY[i][j]).abs() < 1e-6));
+    if !orthonormal || (determinant(m) - 1.0).abs() > 1e-6 {
+        return Err(Error::InvalidParameter(format!("{m:?} is not a rotation matrix")));
+    }
+    Ok(())
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f64; 3]) -> Option<[f64; 3]> {
+    let norm = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    (norm > 1e-9).then(|| v.map(|c| c / norm))
+}
+
+/// Smallest rotation taking direction `from` onto direction `to`; neither
+/// needs to be unit length.
+pub fn between(from: [f64; 3], to: [f64; 3]) -> Result<Matrix3> {
+    let (Some(a), Some(b)) = (normalize(from), normalize(to)) else {
+        return Err(Error::InvalidParameter(format!("cannot rotate {from:?} onto {to:?}")));
+    };
+    let cos = (a[0] * b[0] + a[1] * b[1] + a[2] * b[2]).clamp(-1.0, 1.0);
+    let axis = match normalize(cross(a, b)) {
+        Some(axis) => axis,
+        None if cos > 0.0 => return Ok(IDENTITY),
+        // Opposite directions: any axis perpendicular to `a` works.
+        None => normalize(cross(a, [1.0, 0.0, 0.0]))
+            .or_else(|| normalize(cross(a, [0.0, 1.0, 0.0])))
+            .expect("a unit vector is not parallel to both x and y"),
+    };
+    Ok(axis_angle(axis, cos.acos()))
+}
+
+/// Rotation whose viewing direction points at `lon`/`lat` (degrees, latitude
+/// positive above the horizon) with the camera's up direction as close to
+/// `up` as possible. Vectors use the camera frame convention: x right, y down,
+/// z forward at longitude 0, so a level horizon has `up = [0.0, -1.0, 0.0]`.
+pub fn look_at_matrix(lon: f64, lat: f64, up: [f64; 3]) -> Result<Matrix3> {
+    let forward = coords::lonlat_to_ray(lon, lat);
+    let along = up[0] * forward[0] + up[1] * forward[1] + up[2] * forward[2];
+    let down = normalize([
+        along * forward[0] - up[0],
+        along * forward[1] - up[1],
+        along * forward[2] - up[2],
+    ])
+    .ok_or_else(|| Error::InvalidParameter(format!("up vector {up:?} is parallel to the viewing direction")))?;
+    let right = cross(down, forward);
+    Ok([
+        [right[0], down[0], forward[0]],
+        [right[1], down[1], forward[1]],
+        [right[2], down[2], forward[2]],
+    ])
+}
@@ -0,0 +1,62 @@
+//! `async` wrappers that run projections on tokio's blocking thread pool, so
+//! servers never stall their runtime on decoding, map building or remapping.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use opencv::prelude;
+
+use crate::error::{Error, Result};
+use crate::output::EncodeOptions;
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+use crate::projector::Projector;
+
+/// Runs `f` with `tokio::task::spawn_blocking`. A panic in `f` resumes in
+/// the caller.
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(error) => Err(Error::Io(std::io::Error::other(error))),
+    }
+}
+
+impl Equirectangular {
+    /// [`Equirectangular::new`] off the runtime.
+    pub async fn open_async(path: impl Into<PathBuf>) -> Result<Equirectangular> {
+        let path = path.into();
+        blocking(move || Equirectangular::new(crate::batch::path_str(&path)?)).await
+    }
+
+    /// [`project_to_file`](Equirectangular::project_to_file) off the runtime.
+    pub async fn project_to_file_async(
+        self: Arc<Self>,
+        path: impl Into<PathBuf>,
+        params: PerspectiveParams,
+        options: EncodeOptions,
+    ) -> Result<()> {
+        let path = path.into();
+        blocking(move || self.project_to_file(&path, &params, &options)).await
+    }
+}
+
+impl PerspectiveParams {
+    /// [`project`](PerspectiveParams::project) off the runtime. The panorama
+    /// is shared so concurrent requests render from one decoded copy.
+    pub async fn project_async(&self, pano: Arc<Equirectangular>) -> Result<prelude::Mat> {
+        let params = self.clone();
+        blocking(move || params.project(&pano)).await
+    }
+}
+
+impl Projector {
+    /// [`apply`](Projector::apply) off the runtime.
+    pub async fn apply_async(self: Arc<Self>, src: prelude::Mat) -> Result<prelude::Mat> {
+        blocking(move || self.apply(&src)).await
+    }
+}
@@ -0,0 +1,187 @@
+use opencv::imgproc;
+
+use crate::output::OutputFormat;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    Nearest,
+    Linear,
+    #[default]
+    Cubic,
+    Lanczos4,
+}
+
+impl Interpolation {
+    pub(crate) fn to_cv(self) -> i32 {
+        match self {
+            Interpolation::Nearest => imgproc::INTER_NEAREST,
+            Interpolation::Linear => imgproc::INTER_LINEAR,
+            Interpolation::Cubic => imgproc::INTER_CUBIC,
+            Interpolation::Lanczos4 => imgproc::INTER_LANCZOS4,
+        }
+    }
+}
+
+/// What to sample where a map coordinate falls outside the source image.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Border {
+    /// Wrap around to the opposite edge, seamless across the ±180° meridian.
+    #[default]
+    Wrap,
+    /// Mirror the edge pixels.
+    Reflect,
+    /// Fill with a fixed `[r, g, b, a]` color, in the value range of the
+    /// source depth (e.g. 0-255 for 8-bit, 0-65535 for 16-bit, 0-1 for float).
+    Constant([f64; 4]),
+    /// Leave the destination pixel untouched (zero for fresh outputs).
+    Transparent,
+}
+
+impl Border {
+    /// Border to use where a partial panorama has no data: wrapping or
+    /// mirroring would sample unrelated content, so those become black.
+    pub(crate) fn outside_coverage(self) -> Border {
+        match self {
+            Border::Wrap | Border::Reflect => Border::Constant([0.0, 0.0, 0.0, 0.0]),
+            other => other,
+        }
+    }
+
+    /// Value written into output pixels that have no source direction at all.
+    pub(crate) fn fill_value(self, channels: i32) -> opencv::core::Scalar {
+        match self {
+            Border::Constant(_) => self.to_cv(channels).1,
+            _ => opencv::core::Scalar::all(0.0),
+        }
+    }
+
+    /// OpenCV border mode and fill value for an image with `channels`
+    /// channels. Single-channel images are filled with the red component and
+    /// two-channel images with red plus alpha.
+    pub(crate) fn to_cv(self, channels: i32) -> (i32, opencv::core::Scalar) {
+        match self {
+            Border::Wrap => (opencv::core::BORDER_WRAP, opencv::core::Scalar::all(0.0)),
+            Border::Reflect => (opencv::core::BORDER_REFLECT, opencv::core::Scalar::all(0.0)),
+            Border::Constant([r, g, b, a]) => {
+                let value = match channels {
+                    1 => opencv::core::Scalar::new(r, 0.0, 0.0, 0.0),
+                    2 => opencv::core::Scalar::new(r, a, 0.0, 0.0),
+                    _ => opencv::core::Scalar::new(b, g, r, a),
+                };
+                (opencv::core::BORDER_CONSTANT, value)
+            }
+            Border::Transparent => (opencv::core::BORDER_TRANSPARENT, opencv::core::Scalar::all(0.0)),
+        }
+    }
+}
+
+/// Floating-point width of the per-pixel geometry that builds the
+/// sampling maps. The maps themselves are always `f32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Precision {
+    /// Fast and accurate to well under a hundredth of a pixel on panoramas
+    /// up to 16K wide.
+    #[default]
+    F32,
+    /// For accuracy-sensitive measurements. Lens distortion is always
+    /// undistorted in `f64`.
+    F64,
+    /// Eight pixels at a time with vectorized polynomial approximations of
+    /// the trigonometry, several times faster than `F32`. Views with lens
+    /// distortion use `F32`.
+    #[cfg(feature = "simd")]
+    Simd(SimdAccuracy),
+}
+
+/// Accuracy of the [`Precision::Simd`] approximations.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SimdAccuracy {
+    /// Angles within 1e-5 radians: under 0.03 pixels on a 16K panorama.
+    Low,
+    #[default]
+    High,
+}
+
+/// Where `remap` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Backend {
+    #[default]
+    Cpu,
+    /// OpenCV's transparent API: maps are uploaded once as `UMat`s and each
+    /// frame is remapped on the OpenCL device. Falls back to the CPU when
+    /// OpenCV has no usable OpenCL device.
+    OpenCl,
+    /// `cudawarping::remap` on the first CUDA device, with the maps kept
+    /// on the device. Falls back to the CPU without a CUDA device.
+    #[cfg(feature = "cuda")]
+    Cuda,
+}
+
+impl Backend {
+    /// Whether this backend can actually run here.
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Cpu => true,
+            Backend::OpenCl => opencv::core::have_opencl().unwrap_or(false) && opencv::core::use_opencl().unwrap_or(false),
+            #[cfg(feature = "cuda")]
+            Backend::Cuda => opencv::core::get_cuda_enabled_device_count().unwrap_or(0) > 0,
+        }
+    }
+}
+
+/// How a projection samples the panorama and lays out its result.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RenderOptions {
+    pub interpolation: Interpolation,
+    pub border: Border,
+    pub output_format: OutputFormat,
+    pub backend: Backend,
+    /// Remaps on the CPU whatever the backend, so the output is bit-identical
+    /// on every run of the same build, at any thread count. Map building is
+    /// always deterministic: every pixel is computed on its own. The GPU
+    /// backends round interpolation weights their own way and differ from
+    /// the CPU by up to a couple of levels per 8-bit channel, wgpu's texture
+    /// filtering the most. Builds for other targets may differ in the last
+    /// bit of the trigonometry, which rarely changes a pixel.
+    pub deterministic: bool,
+    /// Output rows per CPU `remap` call. Large views are remapped in bands
+    /// so each band's output and maps stay in cache; `None` sizes bands to
+    /// about 1 MiB, and a value of at least the output height remaps in one
+    /// call.
+    pub remap_band_rows: Option<u32>,
+}
+
+impl RenderOptions {
+    /// Options adjusted for a panorama that may not cover the full sphere:
+    /// partial panoramas never wrap.
+    pub(crate) fn for_coverage(&self, partial: bool) -> RenderOptions {
+        let mut options = *self;
+        if partial {
+            options.border = options.border.outside_coverage();
+        }
+        options
+    }
+
+    /// Rows per CPU remap band for outputs `cols` wide with `elem_size`
+    /// bytes per pixel.
+    pub(crate) fn band_rows(&self, cols: usize, elem_size: usize) -> u32 {
+        const BAND_BYTES: usize = 1 << 20;
+        // Each pixel also reads two `f32` map entries.
+        self.remap_band_rows
+            .unwrap_or_else(|| (BAND_BYTES / (cols * (elem_size + 8)).max(1)) as u32)
+            .max(1)
+    }
+
+    /// The backend remaps actually run on.
+    pub(crate) fn effective_backend(&self) -> Backend {
+        if self.deterministic { Backend::Cpu } else { self.backend }
+    }
+}
@@ -0,0 +1,221 @@
+//! Extraction recipes read from TOML, so the views cut from a set of
+//! panoramas can be checked in next to them and rendered again on demand.
+//!
+//! ```toml
+//! input = "lobby.jpg"          # or inputs = ["a.jpg", "b.jpg"]
+//! out_dir = "views"            # defaults to the scene file's directory
+//! quality = 90
+//!
+//! [[view]]
+//! name = "entrance"
+//! fov = 100
+//! yaw = -30
+//! pitch = 5
+//! size = "1920x1080"
+//! format = "jpg"
+//! ```
+//!
+//! Relative paths are taken from the scene file's directory. View `name` of
+//! a single input is written to `out_dir/name.format`; with several inputs
+//! each gets a directory named after its file stem.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::angle::Deg;
+use crate::batch::{Batch, BatchReport};
+use crate::error::{Error, Result};
+use crate::output::EncodeOptions;
+use crate::params::PerspectiveParams;
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    input: Option<PathBuf>,
+    #[serde(default)]
+    inputs: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    quality: Option<u8>,
+    #[serde(rename = "view")]
+    views: Vec<ViewFile>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ViewFile {
+    name: String,
+    fov: Option<f64>,
+    #[serde(default)]
+    yaw: f64,
+    #[serde(default)]
+    pitch: f64,
+    #[serde(default)]
+    roll: f64,
+    size: Option<String>,
+    format: Option<String>,
+}
+
+/// One named view of a [`Scene`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneView {
+    pub name: String,
+    pub params: PerspectiveParams,
+    /// File extension picking the output format; `jpg` unless given.
+    pub format: String,
+}
+
+/// Panoramas and the views to render from each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    inputs: Vec<PathBuf>,
+    out_dir: PathBuf,
+    encode: EncodeOptions,
+    views: Vec<SceneView>,
+}
+
+/// Whether `name` is one plain file name, so joining it stays inside the
+/// output directory.
+fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+fn parse_size(size: &str) -> Result<(u32, u32)> {
+    size.split_once(['x', 'X'])
+        .and_then(|(width, height)| Some((width.trim().parse().ok()?, height.trim().parse().ok()?)))
+        .ok_or_else(|| Error::InvalidParameter(format!("size must look like 1920x1080, got `{size}`")))
+}
+
+impl Scene {
+    /// Reads the scene file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Scene::from_toml(&text, path.parent().unwrap_or(Path::new("")))
+            .map_err(|e| Error::InvalidParameter(format!("{}: {e}", path.display())))
+    }
+
+    /// Parses a scene, taking relative paths from `base`.
+    pub fn from_toml(text: &str, base: &Path) -> Result<Scene> {
+        let file: SceneFile = toml::from_str(text).map_err(|e| Error::InvalidParameter(e.to_string()))?;
+        let mut inputs = file.inputs;
+        inputs.extend(file.input);
+        let mut encode = EncodeOptions::new();
+        if let Some(quality) = file.quality {
+            encode = encode.jpeg_quality(quality);
+        }
+        let views = file
+            .views
+            .into_iter()
+            .map(|view| {
+                let mut params = PerspectiveParams::new()
+                    .yaw(Deg(view.yaw))
+                    .pitch(Deg(view.pitch))
+                    .roll(Deg(view.roll));
+                if let Some(fov) = view.fov {
+                    params = params.fov(Deg(fov));
+                }
+                if let Some(size) = &view.size {
+                    let (width, height) = parse_size(size)?;
+                    params = params.output_size(width, height);
+                }
+                Ok(SceneView {
+                    name: view.name,
+                    params,
+                    format: view.format.unwrap_or_else(|| "jpg".to_string()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let scene = Scene {
+            inputs: inputs.into_iter().map(|input| base.join(input)).collect(),
+            out_dir: base.join(file.out_dir.unwrap_or_default()),
+            encode,
+            views,
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.inputs.is_empty() {
+            return Err(Error::InvalidParameter("a scene needs an input or inputs".to_string()));
+        }
+        if self.views.is_empty() {
+            return Err(Error::InvalidParameter("a scene needs at least one [[view]]".to_string()));
+        }
+        let mut names = HashSet::new();
+        for view in &self.views {
+            for (what, value) in [("name", &view.name), ("format", &view.format)] {
+                if !is_file_name(value) {
+                    return Err(Error::InvalidParameter(format!("view {what} `{value}` must be a plain file name")));
+                }
+            }
+            if !names.insert(&view.name) {
+                return Err(Error::InvalidParameter(format!("view `{}` is listed twice", view.name)));
+            }
+            view.params
+                .validate()
+                .map_err(|e| Error::InvalidParameter(format!("view `{}`: {e}", view.name)))?;
+        }
+        let stems: HashSet<_> = self.inputs.iter().map(|input| input.file_stem()).collect();
+        if stems.len() < self.inputs.len() {
+            return Err(Error::InvalidParameter("inputs need distinct file names".to_string()));
+        }
+        self.encode.validate()
+    }
+
+    pub fn inputs(&self) -> &[PathBuf] {
+        &self.inputs
+    }
+
+    pub fn views(&self) -> &[SceneView] {
+        &self.views
+    }
+
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// Where view `index` of `input` is written.
+    pub fn output_path(&self, input: &Path, index: usize) -> PathBuf {
+        let view = &self.views[index];
+        let dir = match (self.inputs.len(), input.file_stem()) {
+            (2.., Some(stem)) => self.out_dir.join(stem),
+            _ => self.out_dir.clone(),
+        };
+        dir.join(format!("{}.{}", view.name, view.format))
+    }
+
+    /// Renders every view of every input with a [`Batch`], creating the
+    /// output directories first.
+    pub fn run(&self) -> Result<BatchReport> {
+        for input in &self.inputs {
+            for index in 0..self.views.len() {
+                if let Some(parent) = self.output_path(input, index).parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+            }
+        }
+        Batch::new(self.views.iter().map(|view| view.params.clone()).collect())
+            .encode_options(self.encode)
+            .run(self.inputs.clone(), |input, index| self.output_path(input, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene(view: &str) -> Result<Scene> {
+        Scene::from_toml(&format!("inputs = [\"a.jpg\", \"b.jpg\"]\nout_dir = \"views\"\n[[view]]\n{view}\n"), Path::new("recipes"))
+    }
+
+    #[test]
+    fn outputs_stay_in_out_dir() {
+        let scene = scene("name = \"front\"\nsize = \"64x32\"\nformat = \"png\"").unwrap();
+        assert_eq!(scene.output_path(&scene.inputs()[1], 0), Path::new("recipes/views/b/front.png"));
+        for view in ["name = \"../../x\"", "name = \"/tmp/x\"", "name = \"a/b\"", "name = \"..\"", "name = \"x\"\nformat = \"../png\""] {
+            assert!(matches!(scene(view), Err(Error::InvalidParameter(_))), "{view} was accepted");
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use opencv::prelude;
+
+use crate::angle::Deg;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+
+/// Timing curve of a [`Sweep`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slowly and accelerates.
+    EaseIn,
+    /// Starts fast and decelerates.
+    EaseOut,
+    /// Smooth start and stop (smoothstep).
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased progress for `t` in `[0, 1]`.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Viewing direction and horizontal field of view, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pose {
+    pub yaw: f64,
+    pub pitch: f64,
+    pub fov: f64,
+}
+
+impl Pose {
+    pub fn new(yaw: f64, pitch: f64, fov: f64) -> Pose {
+        Pose { yaw, pitch, fov }
+    }
+}
+
+/// A camera move from one pose to another over a fixed number of frames.
+/// Yaw is interpolated as given, so an end yaw of 360 from 0 makes a full
+/// orbit.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sweep {
+    pub base: PerspectiveParams,
+    pub start: Pose,
+    pub end: Pose,
+    pub frames: u32,
+    pub easing: Easing,
+}
+
+impl Sweep {
+    pub fn new(start: Pose, end: Pose, frames: u32) -> Sweep {
+        Sweep {
+            base: PerspectiveParams::new(),
+            start,
+            end,
+            frames,
+            easing: Easing::default(),
+        }
+    }
+
+    /// Full turn around the horizon at the given pitch and field of view.
+    pub fn orbit(pitch: f64, fov: f64, frames: u32) -> Sweep {
+        Sweep::new(Pose::new(0.0, pitch, fov), Pose::new(360.0, pitch, fov), frames)
+    }
+
+    /// View providing the output size, roll and render options of every
+    /// frame. The sweep owns yaw, pitch and field of view: an explicit
+    /// rotation (quaternion, matrix or look-at with up), explicit intrinsics
+    /// and a vertical field of view on the base are dropped from every frame.
+    pub fn base(mut self, base: PerspectiveParams) -> Self {
+        self.base = base;
+        self
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.frames == 0 {
+            return Err(Error::InvalidParameter("a sweep needs at least one frame".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Pose of frame `index`; the first and last frames hit the start and
+    /// end poses exactly.
+    pub fn pose(&self, index: u32) -> Pose {
+        let t = if self.frames > 1 { index as f64 / (self.frames - 1) as f64 } else { 0.0 };
+        let t = self.easing.apply(t);
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Pose {
+            yaw: lerp(self.start.yaw, self.end.yaw),
+            pitch: lerp(self.start.pitch, self.end.pitch),
+            fov: lerp(self.start.fov, self.end.fov),
+        }
+    }
+
+    pub fn view(&self, index: u32) -> PerspectiveParams {
+        let pose = self.pose(index);
+        self.base.clone().without_explicit_pose().yaw(Deg(pose.yaw)).pitch(Deg(pose.pitch)).fov(Deg(pose.fov))
+    }
+
+    pub fn views(&self) -> impl Iterator<Item = PerspectiveParams> + '_ {
+        (0..self.frames).map(|index| self.view(index))
+    }
+}
+
+impl Equirectangular {
+    /// Lazily renders every frame of `sweep`.
+    pub fn sweep<'a>(&'a self, sweep: &'a Sweep) -> Result<impl Iterator<Item = Result<prelude::Mat>> + 'a> {
+        sweep.validate()?;
+        Ok(sweep.views().map(move |view| view.project(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps_override_explicit_orientation_and_fov() {
+        let base = PerspectiveParams::new()
+            .output_size(64, 32)
+            .look_at_with_up(Deg(10.0), Deg(0.0), [0.0, -1.0, 0.0])
+            .fov_y(Deg(30.0));
+        let sweep = Sweep::new(Pose::new(0.0, 0.0, 60.0), Pose::new(90.0, 20.0, 100.0), 3).base(base);
+        let (first, last) = (sweep.view(0), sweep.view(2));
+        assert_ne!(first.rotation().unwrap(), last.rotation().unwrap());
+        assert_eq!(last.rotation().unwrap(), PerspectiveParams::new().yaw(Deg(90.0)).pitch(Deg(20.0)).rotation().unwrap());
+        assert_eq!(last.focal_lengths(), PerspectiveParams::new().output_size(64, 32).fov(Deg(100.0)).focal_lengths());
+    }
+}
@@ -0,0 +1,160 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::coords;
+use crate::distortion::Distortion;
+use crate::error::{Error, Result};
+use crate::maps;
+use crate::params::PerspectiveParams;
+use crate::perspective::CroppedPanoParams;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// An input image model: where in the source image each world direction is
+/// recorded. The counterpart of [`Projection`] on the sampling side.
+pub trait SourceProjection {
+    /// Source image size as `(width, height)`.
+    fn source_size(&self) -> (u32, u32);
+
+    /// Source pixel `(x, y)` recording world direction `dir` (x right, y
+    /// down, z forward at longitude 0), or `None` if the source does not
+    /// cover it.
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)>;
+
+    /// Whether sampling past the left and right edges continues on the
+    /// opposite edge. Sources that do not wrap never use
+    /// [`Border::Wrap`](crate::Border::Wrap).
+    fn wraps_horizontally(&self) -> bool {
+        false
+    }
+}
+
+/// Full or cropped equirectangular source geometry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquirectSource {
+    width: u32,
+    height: u32,
+    crop: Option<CroppedPanoParams>,
+    rotation: Option<Matrix3>,
+}
+
+impl EquirectSource {
+    pub fn new(width: u32, height: u32) -> EquirectSource {
+        EquirectSource { width, height, crop: None, rotation: None }
+    }
+
+    pub fn with_crop(mut self, crop: CroppedPanoParams) -> EquirectSource {
+        self.crop = Some(crop);
+        self
+    }
+
+    pub fn crop(&self) -> Option<CroppedPanoParams> {
+        self.crop
+    }
+
+    /// Rotation from world directions to panorama directions, applied
+    /// before every lookup.
+    pub fn with_rotation(mut self, rotation: Matrix3) -> EquirectSource {
+        self.rotation = Some(rotation);
+        self
+    }
+}
+
+impl SourceProjection for EquirectSource {
+    fn source_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        let dir = self.rotation.map_or(dir, |rotation| rotation::apply(&rotation, dir));
+        Some(maps::sample_point(dir, (self.height as usize, self.width as usize), self.crop.as_ref()))
+    }
+
+    fn wraps_horizontally(&self) -> bool {
+        self.crop.is_none()
+    }
+}
+
+/// A pinhole image taken with a [`PerspectiveParams`] view, as a source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerspectiveSource {
+    k: Matrix3,
+    distortion: Option<Distortion>,
+    world_to_camera: Matrix3,
+    width: u32,
+    height: u32,
+}
+
+impl PerspectiveSource {
+    pub fn new(params: &PerspectiveParams) -> Result<PerspectiveSource> {
+        params.validate()?;
+        Ok(PerspectiveSource {
+            k: params.intrinsics(),
+            distortion: params.distortion_coefficients(),
+            world_to_camera: rotation::transpose(&params.rotation()?),
+            width: params.width,
+            height: params.height,
+        })
+    }
+}
+
+impl SourceProjection for PerspectiveSource {
+    fn source_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn pixel_for_direction(&self, dir: Vec3) -> Option<(f32, f32)> {
+        let (x, y) = coords::project_lens(&self.k, self.distortion.as_ref(), rotation::apply(&self.world_to_camera, dir))?;
+        let inside = x >= -0.5 && y >= -0.5 && x <= self.width as f64 - 0.5 && y <= self.height as f64 - 0.5;
+        inside.then_some((x as f32, y as f32))
+    }
+}
+
+pub(crate) fn check_source_size<S: SourceProjection + ?Sized>(source: &S, src: &prelude::Mat) -> Result<()> {
+    let (width, height) = source.source_size();
+    if src.cols() != width as i32 || src.rows() != height as i32 {
+        return Err(Error::InvalidParameter(format!(
+            "source geometry is {width}x{height} but the image is {}x{}",
+            src.cols(), src.rows()
+        )));
+    }
+    Ok(())
+}
+
+/// Renders `projection` from an image `src` described by `source`.
+pub fn render<S, P>(src: &prelude::Mat, source: &S, projection: &P, options: &RenderOptions) -> Result<prelude::Mat>
+where
+    S: SourceProjection + ?Sized,
+    P: Projection + ?Sized,
+{
+    let maps = source_maps(src, source, projection)?;
+    maps::RemapMaps::new(&maps)?.render(src, &options.for_coverage(!source.wraps_horizontally()))
+}
+
+/// Like [`render`], also returning a `CV_8U` validity mask that is 255 where
+/// the output pixel has source data and 0 where it was filled with the
+/// border, so "black content" can be told apart from "no data".
+pub fn render_with_mask<S, P>(src: &prelude::Mat, source: &S, projection: &P, options: &RenderOptions) -> Result<(prelude::Mat, prelude::Mat)>
+where
+    S: SourceProjection + ?Sized,
+    P: Projection + ?Sized,
+{
+    let maps = source_maps(src, source, projection)?;
+    let image = maps::RemapMaps::new(&maps)?.render(src, &options.for_coverage(!source.wraps_horizontally()))?;
+    let mask = maps.coverage(source.source_size(), source.wraps_horizontally());
+    Ok((image, maps::to_mat(&mask)?))
+}
+
+fn source_maps<S, P>(src: &prelude::Mat, source: &S, projection: &P) -> Result<maps::Maps>
+where
+    S: SourceProjection + ?Sized,
+    P: Projection + ?Sized,
+{
+    check_source_size(source, src)?;
+    let (width, height) = projection.output_size();
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidParameter(format!("output size must be non-zero, got {width}x{height}")));
+    }
+    Ok(maps::build_maps(projection, source))
+}
@@ -0,0 +1,105 @@
+//! Timing breakdown of projections, so users can see where the time goes
+//! without attaching a profiler. With the `tracing` feature every stage also
+//! opens a span.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Time spent per stage and buffers allocated while [`measure`] ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Computing sampling maps; zero when they came from the
+    /// [`cache`](crate::cache).
+    pub map_build: Duration,
+    pub remap: Duration,
+    /// Encoding and writing image files.
+    pub encode: Duration,
+    /// Frame buffers allocated because none was free in the
+    /// [`pool`](crate::pool).
+    pub allocations: usize,
+}
+
+impl Stats {
+    /// Time of all measured stages together.
+    pub fn total(&self) -> Duration {
+        self.map_build + self.remap + self.encode
+    }
+
+    fn merge(self, other: Stats) -> Stats {
+        Stats {
+            map_build: self.map_build + other.map_build,
+            remap: self.remap + other.remap,
+            encode: self.encode + other.encode,
+            allocations: self.allocations + other.allocations,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<Stats>> = const { Cell::new(None) };
+}
+
+/// Runs `f`, returning its result with the stats of the projections it ran
+/// on this thread. Work handed to other threads, as by [`Batch`](crate::Batch),
+/// is not seen. Measurements nest: an inner one also counts toward the
+/// outer.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Stats) {
+    let outer = CURRENT.replace(Some(Stats::default()));
+    let value = f();
+    let stats = CURRENT.replace(outer).unwrap_or_default();
+    CURRENT.set(outer.map(|outer| outer.merge(stats)));
+    (value, stats)
+}
+
+fn record(update: impl FnOnce(&mut Stats)) {
+    CURRENT.with(|current| {
+        if let Some(mut stats) = current.get() {
+            update(&mut stats);
+            current.set(Some(stats));
+        }
+    });
+}
+
+pub(crate) fn count_allocation() {
+    record(|stats| stats.allocations += 1);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    MapBuild,
+    Remap,
+    Encode,
+}
+
+/// Adds the time until it is dropped to its stage.
+pub(crate) struct Timer {
+    stage: Stage,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+pub(crate) fn time(stage: Stage) -> Timer {
+    Timer {
+        stage,
+        start: Instant::now(),
+        #[cfg(feature = "tracing")]
+        _span: match stage {
+            Stage::MapBuild => tracing::debug_span!("map_build"),
+            Stage::Remap => tracing::debug_span!("remap"),
+            Stage::Encode => tracing::debug_span!("encode"),
+        }
+        .entered(),
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        record(|stats| match self.stage {
+            Stage::MapBuild => stats.map_build += elapsed,
+            Stage::Remap => stats.remap += elapsed,
+            Stage::Encode => stats.encode += elapsed,
+        });
+    }
+}
@@ -0,0 +1,114 @@
+use opencv::core::Vector;
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+
+/// How the two eyes of a stereo panorama are packed into one image. The
+/// left eye comes first (top or left half).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StereoLayout {
+    /// Over/under: left eye on top.
+    #[default]
+    TopBottom,
+    /// Left eye in the left half.
+    SideBySide,
+}
+
+impl StereoLayout {
+    /// Region of each eye in a packed image of `width`x`height`.
+    fn halves(self, width: i32, height: i32) -> (opencv::core::Rect, opencv::core::Rect) {
+        match self {
+            StereoLayout::TopBottom => (
+                opencv::core::Rect::new(0, 0, width, height / 2),
+                opencv::core::Rect::new(0, height / 2, width, height / 2),
+            ),
+            StereoLayout::SideBySide => (
+                opencv::core::Rect::new(0, 0, width / 2, height),
+                opencv::core::Rect::new(width / 2, 0, width / 2, height),
+            ),
+        }
+    }
+
+    /// Packs a left and a right image of the same size.
+    pub fn combine(self, left: &prelude::Mat, right: &prelude::Mat) -> Result<prelude::Mat> {
+        if left.size()? != right.size()? || left.typ() != right.typ() {
+            return Err(Error::InvalidParameter("stereo eyes must have the same size and type".to_string()));
+        }
+        let eyes = Vector::<prelude::Mat>::from_iter([left.try_clone()?, right.try_clone()?]);
+        let mut packed = prelude::Mat::default();
+        match self {
+            StereoLayout::TopBottom => opencv::core::vconcat(&eyes, &mut packed)?,
+            StereoLayout::SideBySide => opencv::core::hconcat(&eyes, &mut packed)?,
+        }
+        Ok(packed)
+    }
+}
+
+/// The two eyes of a stereo panorama.
+pub struct StereoPair {
+    left: Equirectangular,
+    right: Equirectangular,
+    layout: StereoLayout,
+}
+
+impl StereoPair {
+    pub fn new(left: Equirectangular, right: Equirectangular, layout: StereoLayout) -> Result<StereoPair> {
+        if left.width() != right.width() || left.height() != right.height() {
+            return Err(Error::InvalidParameter(format!(
+                "stereo eyes differ in size: {}x{} and {}x{}",
+                left.width(),
+                left.height(),
+                right.width(),
+                right.height()
+            )));
+        }
+        Ok(StereoPair { left, right, layout })
+    }
+
+    /// Splits a packed stereo panorama into its eyes.
+    pub fn split(pano: &Equirectangular, layout: StereoLayout) -> Result<StereoPair> {
+        let src = pano.as_mat();
+        let (left, right) = layout.halves(src.cols(), src.rows());
+        if left.width == 0 || left.height == 0 {
+            return Err(Error::InvalidParameter(format!("{}x{} image is too small to split", src.cols(), src.rows())));
+        }
+        let eye = |rect| -> Result<Equirectangular> {
+            Equirectangular::from_mat(prelude::Mat::roi(src, rect)?.try_clone()?)
+        };
+        StereoPair::new(eye(left)?, eye(right)?, layout)
+    }
+
+    pub fn left(&self) -> &Equirectangular {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Equirectangular {
+        &self.right
+    }
+
+    pub fn layout(&self) -> StereoLayout {
+        self.layout
+    }
+
+    /// Renders the same view from both eyes.
+    pub fn project(&self, params: &PerspectiveParams) -> Result<(prelude::Mat, prelude::Mat)> {
+        Ok((params.project(&self.left)?, params.project(&self.right)?))
+    }
+
+    /// Renders the same view from both eyes, packed in the pair's layout.
+    pub fn project_combined(&self, params: &PerspectiveParams) -> Result<prelude::Mat> {
+        let (left, right) = self.project(params)?;
+        self.layout.combine(&left, &right)
+    }
+}
+
+impl Equirectangular {
+    /// Splits a packed stereo panorama into its eyes.
+    pub fn split_stereo(&self, layout: StereoLayout) -> Result<StereoPair> {
+        StereoPair::split(self, layout)
+    }
+}
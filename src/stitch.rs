@@ -0,0 +1,244 @@
+use opencv::prelude;
+use opencv::prelude::MatTraitConst;
+
+use crate::angle::{Angle, Deg};
+use crate::backproject::{EquirectLayer, Perspective};
+use crate::blend;
+use crate::error::{Error, Result};
+use crate::fisheye::FisheyeLens;
+use crate::perspective::Equirectangular;
+use crate::projection::EquirectProjection;
+use crate::sampling::RenderOptions;
+use crate::source::{PerspectiveSource, SourceProjection};
+
+/// A dual-fisheye capture (Ricoh Theta, Insta360 and similar): two
+/// back-to-back lenses recorded in one image. Lens centers and radii are in
+/// pixels of the whole image.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DualFisheye {
+    pub(crate) front: FisheyeLens,
+    pub(crate) back: FisheyeLens,
+    pub(crate) blend: f64,
+}
+
+impl DualFisheye {
+    pub fn new(front: FisheyeLens, back: FisheyeLens) -> DualFisheye {
+        DualFisheye { front, back, blend: 5.0 }
+    }
+
+    /// The common layout of two circles side by side, front lens on the
+    /// left, each filling its half of a `width`x`height` image.
+    pub fn side_by_side(width: u32, height: u32, fov: impl Into<Angle>) -> DualFisheye {
+        let fov = fov.into();
+        let half = width as f64 / 2.0;
+        let radius = half.min(height as f64) / 2.0;
+        let cy = (height as f64 - 1.0) / 2.0;
+        let lens = FisheyeLens::new().fov(fov).radius(radius);
+        DualFisheye::new(
+            lens.clone().center(half / 2.0 - 0.5, cy),
+            lens.center(1.5 * half - 0.5, cy).yaw(Deg(180.0)),
+        )
+    }
+
+    pub fn front(mut self, lens: FisheyeLens) -> Self {
+        self.front = lens;
+        self
+    }
+
+    pub fn back(mut self, lens: FisheyeLens) -> Self {
+        self.back = lens;
+        self
+    }
+
+    /// Width of the feathered seam, measured inward from the edge of each
+    /// lens' field of view. Lenses need at least this much overlap.
+    pub fn blend_width(mut self, width: impl Into<Angle>) -> Self {
+        self.blend = width.into().degrees();
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.front.validate()?;
+        self.back.validate()?;
+        if !(self.blend >= 0.0 && self.blend.is_finite()) {
+            return Err(Error::InvalidParameter(format!("blend width must be non-negative, got {}", self.blend)));
+        }
+        Ok(())
+    }
+
+    /// Unwarps both lenses and blends them into a panorama `width` pixels
+    /// wide. Directions neither lens saw are black.
+    pub fn stitch(&self, image: &prelude::Mat, width: u32, options: &RenderOptions) -> Result<Equirectangular> {
+        self.validate()?;
+        if width < 2 {
+            return Err(Error::InvalidParameter(format!("panorama width must be at least 2, got {width}")));
+        }
+        let canvas_size = (width, width / 2);
+        let canvas = EquirectProjection::new(canvas_size.0, canvas_size.1);
+        let (image_width, image_height) = (image.cols() as u32, image.rows() as u32);
+        let blend = self.blend.to_radians();
+
+        let mut layers = Vec::with_capacity(2);
+        let mut weights = Vec::with_capacity(2);
+        for lens in [&self.front, &self.back] {
+            let source = lens.source(image_width, image_height)?;
+            layers.push(lens.to_equirectangular(image, canvas_size, options)?.image);
+            weights.push(blend::weight_map(&canvas, |dir| {
+                let inside = source.edge_distance(dir);
+                if blend > 0.0 {
+                    (inside / blend).clamp(0.0, 1.0) as f32
+                } else if inside >= 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }));
+        }
+        Equirectangular::from_mat(blend::weighted_average(&layers, weights)?)
+    }
+}
+
+/// How overlapping views are mixed in a [`Mosaic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// Weights fall off linearly towards each view's edges.
+    #[default]
+    Feather,
+    /// Laplacian pyramid blend over `levels` levels of the feather weights,
+    /// hiding exposure differences across wide overlaps.
+    Multiband { levels: u32 },
+}
+
+/// Composites perspective views with known poses onto one equirectangular
+/// canvas.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mosaic {
+    pub(crate) canvas_size: (u32, u32),
+    pub(crate) blend: BlendMode,
+    pub(crate) feather: f64,
+    pub(crate) options: RenderOptions,
+}
+
+impl Mosaic {
+    /// A mosaic on a `(width, height)` canvas.
+    pub fn new(canvas_size: (u32, u32)) -> Mosaic {
+        Mosaic {
+            canvas_size,
+            blend: BlendMode::default(),
+            feather: 0.1,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Width of the feathered margin along each view's edges, as a fraction
+    /// of the view's shorter side.
+    pub fn feather(mut self, feather: f64) -> Self {
+        self.feather = feather;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.canvas_size.0 == 0 || self.canvas_size.1 == 0 {
+            return Err(Error::InvalidParameter("canvas size must be non-zero".to_string()));
+        }
+        if !(self.feather > 0.0 && self.feather <= 0.5) {
+            return Err(Error::InvalidParameter(format!("feather must be in (0, 0.5], got {}", self.feather)));
+        }
+        Ok(())
+    }
+
+    /// Feather weight of every canvas pixel for one view: 1 inside, falling
+    /// to 0 at the view's edges and outside it.
+    fn weights(&self, view: &Perspective) -> Result<ndarray::Array2<f32>> {
+        let source = PerspectiveSource::new(view.params())?;
+        let (width, height) = source.source_size();
+        let margin = self.feather * width.min(height) as f64;
+        let canvas = EquirectProjection::new(self.canvas_size.0, self.canvas_size.1);
+        Ok(blend::weight_map(&canvas, |dir| match source.pixel_for_direction(dir) {
+            Some((x, y)) => {
+                let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+                let edge = x.min(width as f64 - x).min(y).min(height as f64 - y);
+                (edge / margin).clamp(0.0, 1.0) as f32
+            }
+            None => 0.0,
+        }))
+    }
+
+    fn combine(&self, layers: Vec<prelude::Mat>, weights: Vec<ndarray::Array2<f32>>) -> Result<prelude::Mat> {
+        match self.blend {
+            BlendMode::Feather => blend::weighted_average(&layers, weights),
+            BlendMode::Multiband { levels } => blend::multiband(&layers, weights, levels),
+        }
+    }
+
+    /// Blends `views` together. The mask marks canvas pixels covered by at
+    /// least one view; everything else is black.
+    pub fn composite(&self, views: &[Perspective]) -> Result<EquirectLayer> {
+        self.validate()?;
+        let mut layers = Vec::with_capacity(views.len());
+        let mut weights = Vec::with_capacity(views.len());
+        let mut mask = prelude::Mat::default();
+        for view in views {
+            let layer = view.to_equirectangular_with(self.canvas_size, &self.options)?;
+            if mask.empty() {
+                mask = layer.mask;
+            } else {
+                let mut union = prelude::Mat::default();
+                opencv::core::bitwise_or(&mask, &layer.mask, &mut union, &opencv::core::no_array())?;
+                mask = union;
+            }
+            layers.push(layer.image);
+            weights.push(self.weights(view)?);
+        }
+        Ok(EquirectLayer {
+            image: self.combine(layers, weights)?,
+            mask,
+        })
+    }
+
+    /// Merges edited `views` back into `base`, which must be a full
+    /// panorama of the canvas size. The base shows through wherever the
+    /// views' feather weights fall below 1.
+    pub fn merge_into(&self, base: &Equirectangular, views: &[Perspective]) -> Result<Equirectangular> {
+        self.validate()?;
+        if base.crop().is_some() || (base.width(), base.height()) != self.canvas_size {
+            return Err(Error::InvalidParameter(format!(
+                "base panorama must be an uncropped {}x{} frame",
+                self.canvas_size.0, self.canvas_size.1
+            )));
+        }
+        let mut layers = vec![base.as_mat().try_clone()?];
+        let mut weights = Vec::with_capacity(views.len() + 1);
+        let mut base_weight = ndarray::Array2::<f32>::ones((self.canvas_size.1 as usize, self.canvas_size.0 as usize));
+        for view in views {
+            layers.push(view.to_equirectangular_with(self.canvas_size, &self.options)?.image);
+            let weight = self.weights(view)?;
+            ndarray::Zip::from(&mut base_weight).and(&weight).for_each(|b, &w| *b = b.min(1.0 - w));
+            weights.push(weight);
+        }
+        weights.insert(0, base_weight);
+        Equirectangular::from_mat(self.combine(layers, weights)?)
+    }
+}
+
+impl Equirectangular {
+    /// Stitches a side-by-side dual-fisheye capture with `fov` degree lenses
+    /// into a panorama as wide as the capture.
+    pub fn from_dual_fisheye(image: &prelude::Mat, fov: f64) -> Result<Equirectangular> {
+        let (width, height) = (image.cols() as u32, image.rows() as u32);
+        DualFisheye::side_by_side(width, height, Deg(fov)).stitch(image, width, &RenderOptions::default())
+    }
+}
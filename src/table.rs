@@ -0,0 +1,89 @@
+//! Camera rays precomputed for one output size and lens, so services that
+//! always render the same resolution only pay for a rotation and the
+//! lon/lat conversion per request.
+
+use crate::coords;
+use crate::distortion::Distortion;
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::rotation::Matrix3;
+
+/// Unrotated camera rays of every output pixel of views sharing an output
+/// size, intrinsics and lens distortion. Pinhole views without skew keep
+/// one entry per column and one per row; other lenses keep a ray per pixel,
+/// which also saves undistorting every pixel again.
+#[derive(Debug, Clone)]
+pub struct RayTable {
+    width: u32,
+    height: u32,
+    k: Matrix3,
+    distortion: Option<Distortion>,
+    rays: Rays,
+}
+
+#[derive(Debug, Clone)]
+enum Rays {
+    /// `x` of each column and `y` of each row on the `z = 1` plane.
+    Separable { columns: Vec<f64>, rows: Vec<f64> },
+    PerPixel(ndarray::Array2<[f32; 3]>),
+}
+
+impl RayTable {
+    /// Tables for the output size, intrinsics and distortion of `params`;
+    /// its orientation is ignored.
+    pub fn new(params: &PerspectiveParams) -> Result<RayTable> {
+        params.validate()?;
+        let k = params.intrinsics();
+        if k[0][0] == 0.0 || k[1][1] == 0.0 {
+            return Err(Error::InvalidParameter(format!("intrinsics {k:?} are not invertible")));
+        }
+        let distortion = params.distortion_coefficients();
+        let (width, height) = (params.width, params.height);
+        let rays = if distortion.is_none() && k[0][1] == 0.0 {
+            Rays::Separable {
+                columns: (0..width).map(|u| coords::unproject_lens(&k, None, u as f64, 0.0)[0]).collect(),
+                rows: (0..height).map(|v| coords::unproject_lens(&k, None, 0.0, v as f64)[1]).collect(),
+            }
+        } else {
+            Rays::PerPixel(ndarray::Array2::from_shape_fn((height as usize, width as usize), |(v, u)| {
+                coords::unproject_lens(&k, distortion.as_ref(), u as f64, v as f64).map(|c| c as f32)
+            }))
+        };
+        Ok(RayTable {
+            width,
+            height,
+            k,
+            distortion,
+            rays,
+        })
+    }
+
+    /// Whether views with `params` can use the table.
+    pub fn matches(&self, params: &PerspectiveParams) -> bool {
+        (params.width, params.height) == (self.width, self.height)
+            && params.intrinsics() == self.k
+            && params.distortion_coefficients() == self.distortion
+    }
+
+    pub(crate) fn check(&self, params: &PerspectiveParams) -> Result<()> {
+        if !self.matches(params) {
+            return Err(Error::InvalidParameter(format!(
+                "ray table was built for {}x{} views with other intrinsics or distortion",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+
+    /// Camera ray of output pixel `(u, v)`, on the `z = 1` plane.
+    pub(crate) fn ray(&self, u: usize, v: usize) -> [f64; 3] {
+        match &self.rays {
+            Rays::Separable { columns, rows } => [columns[u], rows[v], 1.0],
+            Rays::PerPixel(rays) => rays[(v, u)].map(f64::from),
+        }
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
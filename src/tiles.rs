@@ -0,0 +1,108 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::error::{Error, Result};
+use crate::params::PerspectiveParams;
+use crate::perspective::Equirectangular;
+
+/// Covers the whole sphere with overlapping square gnomonic (pinhole)
+/// tiles arranged in rings of latitude, one tile on each pole. This is the
+/// usual way to run planar detectors or CNNs on a panorama.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GnomonicTiling {
+    pub(crate) fov: f64,
+    pub(crate) overlap: f64,
+    pub(crate) size: u32,
+}
+
+impl Default for GnomonicTiling {
+    fn default() -> Self {
+        GnomonicTiling::new()
+    }
+}
+
+impl GnomonicTiling {
+    pub fn new() -> GnomonicTiling {
+        GnomonicTiling {
+            fov: 60.0,
+            overlap: 0.2,
+            size: 512,
+        }
+    }
+
+    /// Field of view across each tile.
+    pub fn fov(mut self, fov: impl Into<Angle>) -> Self {
+        self.fov = fov.into().degrees();
+        self
+    }
+
+    /// Fraction of the field of view shared between neighboring tiles.
+    pub fn overlap(mut self, overlap: f64) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Width and height of each tile.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(Error::InvalidParameter(format!("tile fov must be in (0, 180) degrees, got {}", self.fov)));
+        }
+        if !(0.0..0.9).contains(&self.overlap) {
+            return Err(Error::InvalidParameter(format!("tile overlap must be in [0, 0.9), got {}", self.overlap)));
+        }
+        if self.size == 0 {
+            return Err(Error::InvalidParameter("tile size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Tile views, ring by ring from the north pole down, each ring
+    /// starting at longitude -180.
+    pub fn tiles(&self) -> Result<Vec<PerspectiveParams>> {
+        self.validate()?;
+        let step = self.fov * (1.0 - self.overlap);
+        let rings = (180.0 / step).ceil().max(1.0) as usize;
+        let mut tiles = Vec::new();
+        for ring in 0..=rings {
+            let lat = 90.0 - 180.0 * ring as f64 / rings as f64;
+            // Tiles must still meet at the ring's edge nearest the equator,
+            // where the circle of latitude is longest.
+            let widest = (lat.abs() - step / 2.0).max(0.0);
+            let count = if lat.abs() >= 90.0 {
+                1
+            } else {
+                (360.0 * widest.to_radians().cos() / step).ceil().max(1.0) as usize
+            };
+            tiles.extend((0..count).map(|i| {
+                PerspectiveParams::new()
+                    .fov(Deg(self.fov))
+                    .yaw(Deg(-180.0 + 360.0 * i as f64 / count as f64))
+                    .pitch(Deg(lat))
+                    .output_size(self.size, self.size)
+            }));
+        }
+        Ok(tiles)
+    }
+
+    /// Renders every tile, paired with its view.
+    pub fn render(&self, pano: &Equirectangular) -> Result<Vec<(PerspectiveParams, prelude::Mat)>> {
+        let tiles = self.tiles()?;
+        let images = pano.get_perspectives(&tiles)?;
+        Ok(tiles.into_iter().zip(images).collect())
+    }
+}
+
+impl Equirectangular {
+    /// Gnomonic tiles of `fov` degrees and `size` pixels with the default
+    /// overlap; see [`GnomonicTiling`].
+    pub fn gnomonic_tiles(&self, fov: f64, size: u32) -> Result<Vec<(PerspectiveParams, prelude::Mat)>> {
+        GnomonicTiling::new().fov(Deg(fov)).size(size).render(self)
+    }
+}
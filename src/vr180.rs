@@ -0,0 +1,139 @@
+use opencv::prelude;
+
+use crate::angle::{Angle, Deg};
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::perspective::{CroppedPanoParams, Equirectangular};
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// A VR180 half-equirectangular view: longitude -90 to 90 around the
+/// viewing direction across the width, the full latitude range down the
+/// height, in a square image.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Vr180Params {
+    pub(crate) yaw: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    pub(crate) size: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for Vr180Params {
+    fn default() -> Self {
+        Vr180Params::new()
+    }
+}
+
+impl Vr180Params {
+    pub fn new() -> Vr180Params {
+        Vr180Params {
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            size: 2048,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Longitude of the hemisphere center.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    pub fn pitch(mut self, pitch: impl Into<Angle>) -> Self {
+        self.pitch = pitch.into().degrees();
+        self
+    }
+
+    pub fn roll(mut self, roll: impl Into<Angle>) -> Self {
+        self.roll = roll.into().degrees();
+        self
+    }
+
+    /// Width and height of the square output.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.size < 2 {
+            return Err(Error::InvalidParameter(format!("VR180 size must be at least 2, got {}", self.size)));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<HalfEquirect> {
+        self.validate()?;
+        Ok(HalfEquirect {
+            rotation: rotation::euler_to_matrix(self.yaw, self.pitch, self.roll),
+            size: self.size,
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+
+    /// Like [`project`](Self::project), also returning the validity mask of
+    /// [`Equirectangular::render_projection_with_mask`].
+    pub fn project_with_mask(&self, pano: &Equirectangular) -> Result<(prelude::Mat, prelude::Mat)> {
+        pano.render_projection_with_mask(&self.camera()?, &self.options)
+    }
+}
+
+/// A half-equirectangular view resolved from [`Vr180Params`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HalfEquirect {
+    rotation: Matrix3,
+    size: u32,
+}
+
+impl Projection for HalfEquirect {
+    fn output_size(&self) -> (u32, u32) {
+        (self.size, self.size)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let last = self.size as f64 - 1.0;
+        let lon = (u / last - 0.5) * 180.0;
+        let lat = (0.5 - v / last) * 180.0;
+        Some(rotation::apply(&self.rotation, coords::lonlat_to_ray(lon, lat)))
+    }
+}
+
+impl Equirectangular {
+    /// Renders the hemisphere centered on longitude `yaw` (degrees) as a
+    /// `size`x`size` VR180 image.
+    pub fn to_vr180(&self, yaw: f64, size: u32) -> Result<prelude::Mat> {
+        Vr180Params::new().yaw(Deg(yaw)).size(size).project(self)
+    }
+
+    /// Wraps a VR180 half-equirectangular image covering longitudes -90 to
+    /// 90. Views reaching behind it are filled with the border color.
+    pub fn from_vr180(src: prelude::Mat) -> Result<Equirectangular> {
+        Equirectangular::from_mat(src)?.into_vr180()
+    }
+
+    /// Marks an already loaded image as VR180, i.e. as the front half of a
+    /// panorama twice its width.
+    pub fn into_vr180(self) -> Result<Equirectangular> {
+        let (width, height) = (self.width(), self.height());
+        self.with_crop(CroppedPanoParams {
+            full_width: 2 * width,
+            full_height: height,
+            left: width / 2,
+            top: 0,
+        })
+    }
+}
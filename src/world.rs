@@ -0,0 +1,194 @@
+use opencv::prelude;
+
+use crate::angle::Angle;
+use crate::coords;
+use crate::error::{Error, Result};
+use crate::perspective::Equirectangular;
+use crate::projection::{Projection, Vec3};
+use crate::rotation::{self, Matrix3};
+use crate::sampling::RenderOptions;
+
+/// Whole-sphere map projections with less polar stretching than
+/// equirectangular. All of them fill an ellipse or lens in a 2:1 frame;
+/// pixels outside are filled with the border color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WorldProjection {
+    /// Equal-area, elliptical outline.
+    #[default]
+    Hammer,
+    /// Compromise projection with the same outline as Hammer, inverted
+    /// numerically.
+    Aitoff,
+    /// Equal-area, true scale along every parallel.
+    Sinusoidal,
+}
+
+impl WorldProjection {
+    /// Half width and half height of the projected sphere.
+    fn extent(self) -> (f64, f64) {
+        use std::f64::consts::{FRAC_PI_2, PI, SQRT_2};
+        match self {
+            WorldProjection::Hammer => (2.0 * SQRT_2, SQRT_2),
+            WorldProjection::Aitoff | WorldProjection::Sinusoidal => (PI, FRAC_PI_2),
+        }
+    }
+
+    /// Longitude and latitude in radians (latitude positive up) of the map
+    /// point `(x, y)`, `y` pointing north.
+    fn inverse(self, x: f64, y: f64) -> Option<(f64, f64)> {
+        use std::f64::consts::{FRAC_PI_2, PI};
+        match self {
+            WorldProjection::Sinusoidal => {
+                let lon = x / y.cos();
+                (y.abs() <= FRAC_PI_2 && lon.abs() <= PI).then_some((lon, y))
+            }
+            WorldProjection::Hammer => {
+                let (a, b) = self.extent();
+                if (x / a).powi(2) + (y / b).powi(2) > 1.0 {
+                    return None;
+                }
+                let z = (1.0 - (x / 4.0).powi(2) - (y / 2.0).powi(2)).sqrt();
+                let lon = 2.0 * (z * x).atan2(2.0 * (2.0 * z * z - 1.0));
+                Some((lon, (z * y).clamp(-1.0, 1.0).asin()))
+            }
+            WorldProjection::Aitoff => {
+                let (a, b) = self.extent();
+                if (x / a).powi(2) + (y / b).powi(2) > 1.0 {
+                    return None;
+                }
+                // Newton's method from the Hammer point with the same
+                // relative position in the outline.
+                let (ha, hb) = WorldProjection::Hammer.extent();
+                let (mut lon, mut lat) = WorldProjection::Hammer.inverse(x / a * ha, y / b * hb)?;
+                for _ in 0..20 {
+                    let (fx, fy) = aitoff(lon, lat);
+                    let (ex, ey) = (fx - x, fy - y);
+                    if ex.abs() < 1e-10 && ey.abs() < 1e-10 {
+                        break;
+                    }
+                    let h = 1e-7;
+                    let (lx, ly) = aitoff(lon + h, lat);
+                    let (tx, ty) = aitoff(lon, lat + h);
+                    let (j00, j01, j10, j11) = ((lx - fx) / h, (tx - fx) / h, (ly - fy) / h, (ty - fy) / h);
+                    let det = j00 * j11 - j01 * j10;
+                    if det.abs() < 1e-12 {
+                        break;
+                    }
+                    lon = (lon - (j11 * ex - j01 * ey) / det).clamp(-PI, PI);
+                    lat = (lat - (j00 * ey - j10 * ex) / det).clamp(-FRAC_PI_2, FRAC_PI_2);
+                }
+                Some((lon, lat))
+            }
+        }
+    }
+}
+
+fn aitoff(lon: f64, lat: f64) -> (f64, f64) {
+    let cos_lat = lat.cos();
+    let alpha = (cos_lat * (0.5 * lon).cos()).clamp(-1.0, 1.0).acos();
+    let sinc = if alpha < 1e-12 { 1.0 } else { alpha.sin() / alpha };
+    (2.0 * cos_lat * (0.5 * lon).sin() / sinc, lat.sin() / sinc)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WorldMapParams {
+    pub(crate) projection: WorldProjection,
+    pub(crate) yaw: f64,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) options: RenderOptions,
+}
+
+impl Default for WorldMapParams {
+    fn default() -> Self {
+        WorldMapParams::new(WorldProjection::default())
+    }
+}
+
+impl WorldMapParams {
+    pub fn new(projection: WorldProjection) -> WorldMapParams {
+        WorldMapParams {
+            projection,
+            yaw: 0.0,
+            width: 2048,
+            height: 1024,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Longitude at the center of the map.
+    pub fn yaw(mut self, yaw: impl Into<Angle>) -> Self {
+        self.yaw = yaw.into().degrees();
+        self
+    }
+
+    /// Output size; the sphere is stretched to fill it, so keep 2:1 for
+    /// the true outline.
+    pub fn output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidParameter("output size must be non-zero".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Result<WorldMap> {
+        self.validate()?;
+        Ok(WorldMap {
+            projection: self.projection,
+            rotation: rotation::euler_to_matrix(self.yaw, 0.0, 0.0),
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    pub fn project(&self, pano: &Equirectangular) -> Result<prelude::Mat> {
+        pano.render_projection(&self.camera()?, &self.options)
+    }
+}
+
+/// A whole-sphere map resolved from [`WorldMapParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMap {
+    projection: WorldProjection,
+    rotation: Matrix3,
+    width: u32,
+    height: u32,
+}
+
+impl Projection for WorldMap {
+    fn output_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn direction_for_pixel(&self, u: f64, v: f64) -> Option<Vec3> {
+        let (a, b) = self.projection.extent();
+        let x = (2.0 * (u + 0.5) / self.width as f64 - 1.0) * a;
+        let y = (1.0 - 2.0 * (v + 0.5) / self.height as f64) * b;
+        let (lon, lat) = self.projection.inverse(x, y)?;
+        let ray = coords::lonlat_to_ray(lon.to_degrees(), lat.to_degrees());
+        Some(rotation::apply(&self.rotation, ray))
+    }
+}
+
+impl Equirectangular {
+    /// Renders the whole panorama as a 2:1 map of `width` pixels.
+    pub fn to_world_map(&self, projection: WorldProjection, width: u32) -> Result<prelude::Mat> {
+        WorldMapParams::new(projection)
+            .output_size(width, width / 2)
+            .project(self)
+    }
+}